@@ -1,21 +1,167 @@
 pub mod json;
 pub mod py_utils;
+pub mod pytest;
 pub mod sqlalchemy_v2;
+pub mod sqlx;
 
-use std::error::Error;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    io::Write,
+};
 
 use serde::{Deserialize, Serialize};
-use sql_infer_core::inference::QueryItem;
+use sql_infer_core::{
+    inference::{CopyFrom, QueryItem, SqlType},
+    parser::StatementKind,
+};
+
+use crate::{config::Pass, schema::ddl::sql_type_name};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryDefinition {
     pub query: String,
     pub inputs: Box<[QueryItem]>,
     pub outputs: Box<[QueryItem]>,
+    /// SQL literal of each input with a declared default, by input name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defaults: BTreeMap<String, String>,
+    /// Inputs bound as a list of values for `IN`.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub expanding: BTreeSet<String>,
+    /// Parts of `query` only included when their input is given.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<OptionalBlock>,
+    /// Set by the `:one`, `:many` or `:exec` suffix of a `-- name:` comment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub returns: Option<Returns>,
+    /// Set by the `-- sql-infer: async=` setting of the query, the mode of the target otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#async: Option<bool>,
+    /// Passes turned off by the `-- sql-infer: no-<pass>` settings of the query.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub disabled_passes: BTreeSet<Pass>,
+    /// The query writes rows without returning any, the generated function returns how many.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rows_affected: bool,
+    /// Whether the query reads, writes or changes the schema.
+    #[serde(default)]
+    pub kind: StatementKind,
+    /// Table and columns loaded by a `COPY ... FROM STDIN` query.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copy_from: Option<CopyFrom>,
+}
+
+/// What the generated function for a query returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Returns {
+    /// The first row, if any.
+    One,
+    /// Every row.
+    Many,
+    /// Nothing, the query is only executed.
+    Exec,
+}
+
+/// Part of a query, from byte `start` to `end`, only included when `param` is not `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OptionalBlock {
+    pub param: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Types of the generated code replacing the built-in ones, by SQL type name, from the
+/// `[type-map]` config table.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TypeMap(BTreeMap<String, String>);
+
+impl TypeMap {
+    /// Type replacing `sql_type`, looked up by its full name, e.g. `varchar(50)`, then by the
+    /// name of the type without its modifiers. Common aliases such as `int4` are accepted.
+    pub fn get(&self, sql_type: &SqlType) -> Option<&str> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let name = sql_type_name(sql_type);
+        let base = name.split('(').next().unwrap_or(&name);
+        [name.as_str(), base].into_iter().find_map(|name| {
+            self.0
+                .iter()
+                .find(|(key, _)| canonical_type_name(key) == name)
+                .map(|(_, mapped)| mapped.as_str())
+        })
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &str> {
+        self.0.values().map(String::as_str)
+    }
 }
 
+/// Spelling of the type `name` used by [`sql_type_name`].
+fn canonical_type_name(name: &str) -> &str {
+    match name.trim() {
+        "bool" => "boolean",
+        "int2" => "smallint",
+        "int" | "int4" => "integer",
+        "int8" => "bigint",
+        "serial2" => "smallserial",
+        "serial4" => "serial",
+        "serial8" => "bigserial",
+        "decimal" => "numeric",
+        "float4" => "real",
+        "float8" => "double precision",
+        "character varying" => "varchar",
+        "character" => "char",
+        "timestamp with time zone" => "timestamptz",
+        "timestamp without time zone" => "timestamp",
+        "time with time zone" => "timetz",
+        "time without time zone" => "time",
+        "bit varying" => "varbit",
+        name => name,
+    }
+}
+
+/// Content of generated files, by file name.
+pub type Files = Vec<(String, String)>;
+
 pub trait CodeGen {
     fn push(&mut self, name: &str, query: QueryDefinition) -> Result<(), Box<dyn Error>>;
 
-    fn finalize(&self) -> Result<String, Box<dyn Error>>;
+    /// Write the generated code to `out` as it is produced.
+    fn write(&self, out: &mut dyn Write) -> Result<(), Box<dyn Error>>;
+
+    /// Whether the generator also produces a type stub (`.pyi`).
+    fn has_stub(&self) -> bool {
+        false
+    }
+
+    /// Write the type stub accompanying the generated code, see [`CodeGen::has_stub`].
+    fn write_stub(&self, _out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<String, Box<dyn Error>> {
+        let mut code = vec![];
+        self.write(&mut code)?;
+        Ok(String::from_utf8(code)?)
+    }
+
+    /// Type stub (`.pyi`) accompanying the generated code, if the generator produces one.
+    fn finalize_stub(&self) -> Result<Option<String>, Box<dyn Error>> {
+        if !self.has_stub() {
+            return Ok(None);
+        }
+        let mut code = vec![];
+        self.write_stub(&mut code)?;
+        Ok(Some(String::from_utf8(code)?))
+    }
+
+    /// Files written to the target as a directory, by file name. Generators returning them are
+    /// not asked to [`CodeGen::write`].
+    fn files(&self) -> Result<Option<Files>, Box<dyn Error>> {
+        Ok(None)
+    }
 }
@@ -1,4 +1,4 @@
-use std::{collections::BTreeMap, error::Error};
+use std::{collections::BTreeMap, error::Error, io::Write};
 
 use crate::codegen::QueryDefinition;
 
@@ -15,7 +15,8 @@ impl CodeGen for JsonCodeGen {
         Ok(())
     }
 
-    fn finalize(&self) -> Result<String, Box<dyn Error>> {
-        Ok(serde_json::to_string_pretty(&self.queries)?)
+    fn write(&self, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(out, &self.queries)?;
+        Ok(())
     }
 }
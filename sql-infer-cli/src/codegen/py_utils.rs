@@ -1,7 +1,62 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, error::Error};
 
 const ILLEGAL_CHARACTERS: &[&str] = &["\"", "\'"];
 
+/// Reserved words of Python, soft keywords such as `match` or `type` are valid names and so
+/// not listed.
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// `name` as a Python identifier, keywords get a trailing underscore. Runs of characters not
+/// allowed in identifiers become a single underscore, e.g. `?column?` becomes `column` and
+/// `first name` becomes `first_name`, and a leading digit is prefixed with one.
+pub fn py_identifier(name: &str) -> Cow<'_, str> {
+    if KEYWORDS.contains(&name) {
+        return Cow::Owned(format!("{name}_"));
+    }
+    let valid = |char: char| char.is_alphanumeric() || char == '_';
+    if !name.is_empty()
+        && name.chars().all(valid)
+        && !name.starts_with(|char: char| char.is_ascii_digit())
+    {
+        return Cow::Borrowed(name);
+    }
+    let identifier = name
+        .split(|char| !valid(char))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    match identifier.chars().next() {
+        None => Cow::Borrowed("column"),
+        Some(first) if first.is_ascii_digit() => Cow::Owned(format!("_{identifier}")),
+        Some(_) => py_identifier(&identifier).into_owned().into(),
+    }
+}
+
+/// Python literal for a SQL literal used as a parameter default.
+pub fn to_py_literal(sql: &str) -> Result<String, Box<dyn Error>> {
+    let literal = match sql.to_lowercase().as_str() {
+        "null" => "None".to_string(),
+        "true" => "True".to_string(),
+        "false" => "False".to_string(),
+        _ if sql.parse::<i64>().is_ok() || sql.parse::<f64>().is_ok() => sql.to_string(),
+        _ => match sql
+            .strip_prefix('\'')
+            .and_then(|string| string.strip_suffix('\''))
+        {
+            Some(string) => format!("{:?}", string.replace("''", "'")),
+            None => Err(format!(
+                "unsupported default {sql}, expected a number, string, boolean or null"
+            ))?,
+        },
+    };
+    Ok(literal)
+}
+
 pub fn escape_string(string: &str) -> String {
     let mut out = Cow::Borrowed(string);
     for char in ILLEGAL_CHARACTERS {
@@ -9,3 +64,18 @@ pub fn escape_string(string: &str) -> String {
     }
     out.into_owned()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::py_utils::py_identifier;
+
+    #[test]
+    fn identifiers_are_sanitized() {
+        assert_eq!(py_identifier("user_id"), "user_id");
+        assert_eq!(py_identifier("class"), "class_");
+        assert_eq!(py_identifier("first name"), "first_name");
+        assert_eq!(py_identifier("?column?"), "column");
+        assert_eq!(py_identifier("2fa"), "_2fa");
+        assert_eq!(py_identifier("from."), "from_");
+    }
+}
@@ -0,0 +1,292 @@
+use std::{collections::BTreeMap, error::Error, io::Write};
+
+use sql_infer_core::parser::split_statements;
+
+use crate::codegen::{
+    QueryDefinition,
+    py_utils::{escape_string, py_identifier},
+    sqlalchemy_v2::{field_names, uses_param},
+};
+
+use super::CodeGen;
+
+/// Generates a pytest module running every query function of the `sql-alchemy-v2` module
+/// `module` with `None` parameters, in a transaction rolled back after each test. The columns
+/// of queries returning rows are compared with the inferred ones.
+pub struct PytestCodeGen {
+    queries: BTreeMap<String, QueryDefinition>,
+    module: String,
+    r#async: bool,
+}
+
+impl PytestCodeGen {
+    pub fn new(module: String, r#async: bool) -> Self {
+        Self {
+            queries: BTreeMap::new(),
+            module,
+            r#async,
+        }
+    }
+
+    fn is_async(&self, query: &QueryDefinition) -> bool {
+        query.r#async.unwrap_or(self.r#async)
+    }
+
+    fn test_function(
+        &self,
+        fn_name: &str,
+        query: &QueryDefinition,
+    ) -> Result<String, Box<dyn Error>> {
+        let is_async = self.is_async(query);
+        let (conn, execute, call) = match is_async {
+            true => ("sql_infer_async_conn", "await ", "await "),
+            false => ("sql_infer_conn", "", ""),
+        };
+        let savepoint = match is_async {
+            true => format!("async with {conn}.begin_nested():"),
+            false => format!("with {conn}.begin_nested():"),
+        };
+        let py_fn_name = py_identifier(fn_name);
+        let mut body = vec![];
+
+        // The columns are checked by running the query on its own, as the generated function
+        // only returns them by position.
+        let single_statement = split_statements(&query.query)?.len() <= 1;
+        if !query.outputs.is_empty() && query.copy_from.is_none() && single_statement {
+            let mut statement = format!("text(\"\"\"{}\"\"\")", query.query);
+            let expanding = query
+                .expanding
+                .iter()
+                .filter(|name| uses_param(&query.query, name))
+                .map(|name| format!("bindparam(\"{name}\", expanding=True)"))
+                .collect::<Vec<_>>();
+            if !expanding.is_empty() {
+                statement += &format!(".bindparams({})", expanding.join(", "));
+            }
+            let binds = query
+                .inputs
+                .iter()
+                .map(|input| match query.expanding.contains(&input.name) {
+                    true => format!("\"{}\": []", input.name),
+                    false => format!("\"{}\": None", input.name),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let columns = query
+                .outputs
+                .iter()
+                .map(|output| format!("\"{}\"", escape_string(&output.name)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            body.push(format!("        {savepoint}"));
+            body.push(format!(
+                "            result = {execute}{conn}.execute({statement}, {{{binds}}})"
+            ));
+            body.push(format!(
+                "            assert list(result.keys()) == [{columns}]"
+            ));
+        }
+
+        let args = match &query.copy_from {
+            Some(_) => vec!["rows=[]".to_string()],
+            None => query
+                .inputs
+                .iter()
+                .zip(field_names(&query.inputs))
+                // Optional inputs and inputs with a default are left out.
+                .filter(|(input, _)| {
+                    !query.defaults.contains_key(&input.name)
+                        && !query.blocks.iter().any(|block| block.param == input.name)
+                })
+                .map(
+                    |(input, py_name)| match query.expanding.contains(&input.name) {
+                        true => format!("{py_name}=[]"),
+                        false => format!("{py_name}=None"),
+                    },
+                )
+                .collect(),
+        };
+        let args = std::iter::once(conn.to_string())
+            .chain(args)
+            .collect::<Vec<_>>()
+            .join(", ");
+        body.push(format!("        {savepoint}"));
+        body.push(format!(
+            "            _consume({call}queries.{py_fn_name}({args}))"
+        ));
+
+        let signature = match is_async {
+            true => format!("@pytest.mark.asyncio\nasync def test_{py_fn_name}({conn}) -> None:"),
+            false => format!("def test_{py_fn_name}({conn}) -> None:"),
+        };
+        Ok(format!(
+            "\n\n{signature}\n    try:\n{}\n    except IntegrityError:\n        # The query ran, `None` parameters broke a constraint.\n        pass\n",
+            body.join("\n")
+        ))
+    }
+}
+
+const TEMPLATE: &str = r#""""Smoke tests generated by sql-infer.
+
+Every query runs with `None` parameters in a transaction rolled back after the test. The
+`engine` fixture, `async_engine` for async queries, is expected from `conftest.py`.
+"""
+
+from __future__ import annotations
+
+import pytest
+"#;
+
+const IMPORTS: &str = r#"from sqlalchemy import bindparam, text
+from sqlalchemy.exc import IntegrityError
+"#;
+
+const SYNC_FIXTURE: &str = r#"
+
+@pytest.fixture
+def sql_infer_conn(engine):
+    with engine.connect() as conn:
+        transaction = conn.begin()
+        try:
+            yield conn
+        finally:
+            transaction.rollback()
+"#;
+
+const ASYNC_FIXTURE: &str = r#"
+
+@pytest_asyncio.fixture
+async def sql_infer_async_conn(async_engine):
+    async with async_engine.connect() as conn:
+        transaction = await conn.begin()
+        try:
+            yield conn
+        finally:
+            await transaction.rollback()
+"#;
+
+const CONSUME: &str = r#"
+
+def _consume(output: object) -> None:
+    """Build every row of `output`, failing when they do not match their dataclass."""
+    if isinstance(output, queries.DbOutput):
+        list(output.all())
+"#;
+
+impl CodeGen for PytestCodeGen {
+    fn push(&mut self, file_name: &str, query: QueryDefinition) -> Result<(), Box<dyn Error>> {
+        self.queries.insert(file_name.to_string(), query);
+        Ok(())
+    }
+
+    fn write(&self, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        out.write_all(TEMPLATE.as_bytes())?;
+        let has_async = self.queries.values().any(|query| self.is_async(query));
+        let has_sync = self.queries.values().any(|query| !self.is_async(query));
+        if has_async {
+            out.write_all(b"import pytest_asyncio\n")?;
+        }
+        out.write_all(IMPORTS.as_bytes())?;
+        writeln!(out, "\nimport {} as queries", self.module)?;
+        if has_sync {
+            out.write_all(SYNC_FIXTURE.as_bytes())?;
+        }
+        if has_async {
+            out.write_all(ASYNC_FIXTURE.as_bytes())?;
+        }
+        out.write_all(CONSUME.as_bytes())?;
+        for (file_name, query) in &self.queries {
+            out.write_all(self.test_function(file_name, query)?.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use sql_infer_core::{
+        inference::{Nullability, QueryItem, SqlType},
+        parser::StatementKind,
+    };
+
+    use crate::codegen::{CodeGen, QueryDefinition, pytest::PytestCodeGen};
+
+    fn item(name: &str, sql_type: SqlType) -> QueryItem {
+        QueryItem {
+            name: name.to_string(),
+            sql_type,
+            nullable: Nullability::False,
+        }
+    }
+
+    fn query(sql: &str, inputs: &[QueryItem], outputs: &[QueryItem]) -> QueryDefinition {
+        QueryDefinition {
+            query: sql.to_string(),
+            inputs: inputs.into(),
+            outputs: outputs.into(),
+            defaults: BTreeMap::new(),
+            expanding: BTreeSet::new(),
+            blocks: vec![],
+            returns: None,
+            r#async: None,
+            disabled_passes: BTreeSet::new(),
+            rows_affected: false,
+            kind: StatementKind::Read,
+            copy_from: None,
+        }
+    }
+
+    #[test]
+    fn module_runs_every_query() {
+        let mut by_ids = query(
+            "select id, name from users where id in :ids",
+            &[item("ids", SqlType::Int4)],
+            &[item("id", SqlType::Int4), item("name", SqlType::Text)],
+        );
+        by_ids.expanding.insert("ids".to_string());
+        let mut deactivate = query(
+            "update users set active = false where id = :user_id",
+            &[item("user_id", SqlType::Int4)],
+            &[],
+        );
+        deactivate.rows_affected = true;
+        deactivate.kind = StatementKind::Write;
+
+        let mut codegen = PytestCodeGen::new("app.queries".to_string(), false);
+        codegen.push("users_by_ids", by_ids).unwrap();
+        codegen.push("deactivate", deactivate).unwrap();
+        let mut module = vec![];
+        codegen.write(&mut module).unwrap();
+        let module = String::from_utf8(module).unwrap();
+        assert!(module.contains("\nimport app.queries as queries\n"));
+        let tests = &module[module.find("\n\n\ndef test_").unwrap()..];
+        assert_eq!(
+            tests,
+            r#"
+
+
+def test_deactivate(sql_infer_conn) -> None:
+    try:
+        with sql_infer_conn.begin_nested():
+            _consume(queries.deactivate(sql_infer_conn, user_id=None))
+    except IntegrityError:
+        # The query ran, `None` parameters broke a constraint.
+        pass
+
+
+def test_users_by_ids(sql_infer_conn) -> None:
+    try:
+        with sql_infer_conn.begin_nested():
+            result = sql_infer_conn.execute(text("""select id, name from users where id in :ids""").bindparams(bindparam("ids", expanding=True)), {"ids": []})
+            assert list(result.keys()) == ["id", "name"]
+        with sql_infer_conn.begin_nested():
+            _consume(queries.users_by_ids(sql_infer_conn, ids=[]))
+    except IntegrityError:
+        # The query ran, `None` parameters broke a constraint.
+        pass
+"#
+        );
+    }
+}
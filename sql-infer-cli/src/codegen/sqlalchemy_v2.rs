@@ -1,9 +1,15 @@
-use std::{borrow::Cow, collections::BTreeMap, error::Error, fmt::Display};
+use std::{borrow::Cow, collections::BTreeMap, error::Error, fmt::Display, io::Write};
 
 use serde::{Deserialize, Serialize};
-use sql_infer_core::inference::{Nullability, QueryItem, SqlType};
+use sql_infer_core::{
+    inference::{CopyFrom, Nullability, QueryItem, SqlType, deduplicate_names},
+    parser::split_statements,
+};
 
-use crate::codegen::{QueryDefinition, py_utils::escape_string};
+use crate::codegen::{
+    QueryDefinition, Returns, TypeMap,
+    py_utils::{escape_string, py_identifier, to_py_literal},
+};
 
 use super::CodeGen;
 
@@ -85,11 +91,18 @@ impl TypeBounds for NoBounds {
     }
 }
 
+/// Python type of a parameter, timestamps are `AwareDatetime` or `NaiveDatetime` when
+/// `distinct_datetimes` is set. Types in `type_map` replace the built-in ones.
 fn to_py_input_type(
     sql_type: &SqlType,
     nullable: Nullability,
     bounds: &mut dyn TypeBounds,
+    distinct_datetimes: bool,
+    type_map: &TypeMap,
 ) -> String {
+    if let Some(mapped) = type_map.get(sql_type) {
+        return nullable_py_type(mapped_py_type(mapped), nullable);
+    }
     let py_type: Cow<'_, str> = match sql_type {
         SqlType::Bool => Cow::Borrowed("bool"),
         SqlType::Int2
@@ -99,7 +112,9 @@ fn to_py_input_type(
         | SqlType::Serial
         | SqlType::BigSerial => Cow::Borrowed("int"),
         SqlType::Decimal { .. } => Cow::Borrowed("Decimal"),
-        SqlType::Timestamp { .. } => Cow::Borrowed("datetime"),
+        SqlType::Timestamp { .. } if !distinct_datetimes => Cow::Borrowed("datetime"),
+        SqlType::Timestamp { tz: false } => Cow::Borrowed("NaiveDatetime"),
+        SqlType::Timestamp { tz: true } => Cow::Borrowed("AwareDatetime"),
         SqlType::Date => Cow::Borrowed("date"),
         SqlType::Time { .. } => Cow::Borrowed("time"),
         SqlType::Char { .. }
@@ -118,54 +133,28 @@ fn to_py_input_type(
                 .join(", ")
         )),
         SqlType::Unknown => Cow::Borrowed("Any"),
-        SqlType::Array(inner_type) => {
-            let inner = to_py_input_type(inner_type, Nullability::True, bounds);
-            let var = bounds.bounds(&inner);
-            Cow::Owned(format!("list[{var}]"))
-        }
-    };
-    match nullable {
-        Nullability::True | Nullability::Unknown => format!("{py_type} | None"),
-        Nullability::False => py_type.to_string(),
-    }
-}
-
-fn to_pydantic_input_type(
-    sql_type: &SqlType,
-    nullable: Nullability,
-    bounds: &mut dyn TypeBounds,
-) -> String {
-    let py_type: Cow<'_, str> = match &sql_type {
-        SqlType::Bool => Cow::Borrowed("bool"),
-        SqlType::Int2
-        | SqlType::Int4
-        | SqlType::Int8
-        | SqlType::SmallSerial
-        | SqlType::Serial
-        | SqlType::BigSerial => Cow::Borrowed("int"),
-        SqlType::Decimal { .. } => Cow::Borrowed("Decimal"),
-        SqlType::Timestamp { tz: false } => Cow::Borrowed("NaiveDatetime"),
-        SqlType::Timestamp { tz: true } => Cow::Borrowed("AwareDatetime"),
-        SqlType::Date => Cow::Borrowed("date"),
-        SqlType::Time { .. } => Cow::Borrowed("time"),
-        SqlType::Char { .. }
-        | SqlType::VarChar { .. }
-        | SqlType::Text
-        | SqlType::Json
-        | SqlType::Jsonb => Cow::Borrowed("str"),
-        SqlType::Float4 | SqlType::Float8 => Cow::Borrowed("float"),
-        SqlType::Interval => Cow::Borrowed("timedelta"),
-        SqlType::Bit { .. } | SqlType::VarBit { .. } => Cow::Borrowed("str"),
-        SqlType::Enum { tags, .. } => Cow::Owned(format!(
-            "Literal[{}]",
-            tags.iter()
-                .map(|tag| format!("{:?}", escape_string(tag)))
+        SqlType::Record(fields) => Cow::Owned(format!(
+            "tuple[{}]",
+            fields
+                .iter()
+                .map(|field| to_py_input_type(
+                    &field.sql_type,
+                    field.nullable,
+                    bounds,
+                    distinct_datetimes,
+                    type_map
+                ))
                 .collect::<Vec<_>>()
                 .join(", ")
         )),
-        SqlType::Unknown => Cow::Borrowed("Any"),
         SqlType::Array(inner_type) => {
-            let inner = to_pydantic_input_type(inner_type, Nullability::True, bounds);
+            let inner = to_py_input_type(
+                inner_type,
+                Nullability::True,
+                bounds,
+                distinct_datetimes,
+                type_map,
+            );
             let var = bounds.bounds(&inner);
             Cow::Owned(format!("list[{var}]"))
         }
@@ -176,11 +165,17 @@ fn to_pydantic_input_type(
     }
 }
 
-fn to_py_output_type(item: &QueryItem) -> String {
+fn to_py_output_type(item: &QueryItem, distinct_datetimes: bool, type_map: &TypeMap) -> String {
     let py_type = match item.sql_type {
-        SqlType::Json | SqlType::Jsonb => "Json",
+        SqlType::Json | SqlType::Jsonb if type_map.get(&item.sql_type).is_none() => "Json",
         _ => {
-            return to_py_input_type(&item.sql_type, item.nullable, &mut NoBounds);
+            return to_py_input_type(
+                &item.sql_type,
+                item.nullable,
+                &mut NoBounds,
+                distinct_datetimes,
+                type_map,
+            );
         }
     }
     .to_owned();
@@ -190,20 +185,59 @@ fn to_py_output_type(item: &QueryItem) -> String {
     }
 }
 
-fn to_pydantic_output_type(item: &QueryItem) -> String {
-    let py_type = match item.sql_type {
-        SqlType::Json | SqlType::Jsonb => "Json",
-        _ => {
-            return to_pydantic_input_type(&item.sql_type, item.nullable, &mut NoBounds);
-        }
+/// Name a `[type-map]` entry is referred to by, `app.types.Money` is imported as `Money`.
+fn mapped_py_type(mapped: &str) -> &str {
+    match qualified_py_name(mapped) {
+        Some((_, name)) => name,
+        None => mapped,
     }
-    .to_owned();
-    match item.nullable {
+}
+
+/// Module and name of a dotted path such as `app.types.Money`.
+fn qualified_py_name(path: &str) -> Option<(&str, &str)> {
+    let is_path = path
+        .chars()
+        .all(|character| character.is_alphanumeric() || character == '_' || character == '.');
+    path.rsplit_once('.').filter(|_| is_path)
+}
+
+fn nullable_py_type(py_type: &str, nullable: Nullability) -> String {
+    match nullable {
         Nullability::True | Nullability::Unknown => format!("{py_type} | None"),
-        Nullability::False => py_type,
+        Nullability::False => py_type.to_string(),
     }
 }
 
+/// Python type of a JSON value parsed by the generated code.
+fn to_py_json_type(nullable: Nullability) -> String {
+    nullable_py_type("dict[str, Any] | list[Any]", nullable)
+}
+
+/// Helpers of the generated module converting JSON values, drivers may return them parsed
+/// already.
+const JSON_HELPERS: &str = r#"
+import json
+
+
+def _load_json(value: Any) -> Any:
+    return json.loads(value) if isinstance(value, (str, bytes)) else value
+
+
+def _dump_json(value: Any) -> str | None:
+    return None if value is None else json.dumps(value)
+"#;
+
+/// Helpers of the generated module converting `numeric` columns.
+const NUMERIC_HELPERS: &str = r#"
+
+def _to_float(value: Decimal | None) -> float | None:
+    return None if value is None else float(value)
+
+
+def _to_str(value: Decimal | None) -> str | None:
+    return None if value is None else str(value)
+"#;
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum ArgumentMode {
@@ -220,6 +254,46 @@ pub enum TypeGen {
     Pydantic,
 }
 
+/// Python representation of `json` and `jsonb` values.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum JsonMode {
+    /// `str` parameters and `Json` columns, as the driver returns them.
+    #[default]
+    Text,
+    /// `dict[str, Any] | list[Any]`, serialized and parsed by the generated functions.
+    Parsed,
+}
+
+/// Python type of `numeric` values.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumericMode {
+    #[default]
+    Decimal,
+    Float,
+    Str,
+}
+
+impl NumericMode {
+    fn py_type(self) -> &'static str {
+        match self {
+            NumericMode::Decimal => "Decimal",
+            NumericMode::Float => "float",
+            NumericMode::Str => "str",
+        }
+    }
+
+    /// Helper of the generated module converting a column from the `Decimal` drivers return.
+    fn conversion(self) -> Option<&'static str> {
+        match self {
+            NumericMode::Decimal => None,
+            NumericMode::Float => Some("_to_float"),
+            NumericMode::Str => Some("_to_str"),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct SqlAlchemyV2CodeGen {
     queries: BTreeMap<String, QueryDefinition>,
@@ -227,6 +301,15 @@ pub struct SqlAlchemyV2CodeGen {
     argument_mode: ArgumentMode,
     type_gen: TypeGen,
     generic_param_types: bool,
+    stub: bool,
+    /// Distinguish `timestamp` from `timestamptz` with aliases of `datetime` in `python` type
+    /// generation, as `pydantic` type generation does.
+    datetime_aliases: bool,
+    json: JsonMode,
+    numeric: NumericMode,
+    /// Overrides of `numeric` for columns, keyed by `query.column`.
+    numeric_columns: BTreeMap<String, NumericMode>,
+    type_map: TypeMap,
 }
 
 impl SqlAlchemyV2CodeGen {
@@ -235,6 +318,9 @@ impl SqlAlchemyV2CodeGen {
         argument_mode: ArgumentMode,
         type_gen: TypeGen,
         generic_param_types: bool,
+        stub: bool,
+        datetime_aliases: bool,
+        json: JsonMode,
     ) -> Self {
         Self {
             queries: Default::default(),
@@ -242,28 +328,275 @@ impl SqlAlchemyV2CodeGen {
             argument_mode,
             type_gen,
             generic_param_types,
+            stub,
+            datetime_aliases,
+            json,
+            numeric: NumericMode::default(),
+            numeric_columns: BTreeMap::new(),
+            type_map: TypeMap::default(),
+        }
+    }
+
+    /// Annotate values with the types of `type_map` instead of the built-in ones.
+    pub fn with_type_map(mut self, type_map: TypeMap) -> Self {
+        self.type_map = type_map;
+        self
+    }
+
+    /// Map `numeric` values to `numeric`, or to the mode of `columns` for the columns it holds.
+    pub fn with_numeric(
+        mut self,
+        numeric: NumericMode,
+        columns: BTreeMap<String, NumericMode>,
+    ) -> Self {
+        self.numeric = numeric;
+        self.numeric_columns = columns;
+        self
+    }
+
+    /// Mode of numeric values in `item`, a column of `fn_name` or a parameter when `fn_name` is
+    /// `None`.
+    fn numeric_mode(&self, fn_name: Option<&str>, item: &QueryItem) -> Option<NumericMode> {
+        if !matches!(item.sql_type, SqlType::Decimal { .. }) {
+            return None;
+        }
+        let column = fn_name.and_then(|fn_name| {
+            self.numeric_columns
+                .get(&format!("{fn_name}.{}", item.name))
+                .copied()
+        });
+        Some(column.unwrap_or(self.numeric))
+    }
+
+    /// Helper of the generated module converting the column `item` of `fn_name`, if any.
+    fn output_conversion(&self, fn_name: &str, item: &QueryItem) -> Option<&'static str> {
+        match self.is_parsed_json(item) {
+            true => Some("_load_json"),
+            false => self
+                .numeric_mode(Some(fn_name), item)
+                .and_then(NumericMode::conversion),
         }
     }
 
-    fn conn_param(&self) -> &str {
-        match self.r#async {
+    fn conn_param(is_async: bool) -> &'static str {
+        match is_async {
             false => "conn: Connection",
             true => "conn: AsyncConnection",
         }
     }
 
+    /// Whether `timestamp` and `timestamptz` map to different Python types.
+    fn distinct_datetimes(&self) -> bool {
+        self.type_gen == TypeGen::Pydantic || self.datetime_aliases
+    }
+
+    /// Whether `item` is a JSON value parsed by the generated code.
+    fn is_parsed_json(&self, item: &QueryItem) -> bool {
+        self.json == JsonMode::Parsed && matches!(item.sql_type, SqlType::Json | SqlType::Jsonb)
+    }
+
     fn to_input_type(&self, item: &QueryItem, bounds: &mut dyn TypeBounds) -> String {
-        match self.type_gen {
-            TypeGen::Python => to_py_input_type(&item.sql_type, item.nullable, bounds),
-            TypeGen::Pydantic => to_pydantic_input_type(&item.sql_type, item.nullable, bounds),
+        if let Some(mapped) = self.type_map.get(&item.sql_type) {
+            return nullable_py_type(mapped_py_type(mapped), item.nullable);
+        }
+        if self.is_parsed_json(item) {
+            return to_py_json_type(item.nullable);
+        }
+        if let Some(numeric) = self.numeric_mode(None, item) {
+            return nullable_py_type(numeric.py_type(), item.nullable);
+        }
+        to_py_input_type(
+            &item.sql_type,
+            item.nullable,
+            bounds,
+            self.distinct_datetimes(),
+            &self.type_map,
+        )
+    }
+
+    fn to_output_type(&self, fn_name: &str, item: &QueryItem) -> String {
+        if let Some(mapped) = self.type_map.get(&item.sql_type) {
+            return nullable_py_type(mapped_py_type(mapped), item.nullable);
+        }
+        if self.is_parsed_json(item) {
+            return to_py_json_type(item.nullable);
+        }
+        if let Some(numeric) = self.numeric_mode(Some(fn_name), item) {
+            return nullable_py_type(numeric.py_type(), item.nullable);
         }
+        to_py_output_type(item, self.distinct_datetimes(), &self.type_map)
     }
 
-    fn to_output_type(&self, item: &QueryItem) -> String {
-        match self.type_gen {
-            TypeGen::Python => to_py_output_type(item),
-            TypeGen::Pydantic => to_pydantic_output_type(item),
+    fn output_class_name(fn_name: &str) -> String {
+        to_pascal(&format!("{fn_name}_output"))
+    }
+
+    fn row_class_name(fn_name: &str) -> String {
+        to_pascal(&format!("{fn_name}_row"))
+    }
+
+    /// Names exported by the generated module, in the order they are defined.
+    fn exports(&self) -> Vec<String> {
+        let mut exports = vec!["DbOutput".to_string()];
+        for (file_name, query) in &self.queries {
+            if query.copy_from.is_some() {
+                exports.push(Self::row_class_name(file_name));
+            } else if !query.outputs.is_empty() && query.returns != Some(Returns::Exec) {
+                exports.push(Self::output_class_name(file_name));
+            }
+            exports.push(py_identifier(file_name).into_owned());
         }
+        exports
+    }
+
+    fn dunder_all(&self) -> String {
+        format!(
+            "\n__all__ = [{}]\n",
+            self.exports()
+                .iter()
+                .map(|name| format!("\"{name}\""))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// Write `template` followed by every query, as function stubs when `stub` is set.
+    fn write_module(
+        &self,
+        out: &mut dyn Write,
+        template: &str,
+        stub: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        out.write_all(template.as_bytes())?;
+        if self.type_gen == TypeGen::Pydantic {
+            out.write_all(b"\nfrom pydantic import AwareDatetime, NaiveDatetime\n")?;
+        } else if self.datetime_aliases {
+            // Aliases are evaluated lazily, `datetime` is only imported for type checking.
+            out.write_all(b"\ntype AwareDatetime = datetime\ntype NaiveDatetime = datetime\n")?;
+        }
+        if self.json == JsonMode::Parsed && !stub {
+            out.write_all(JSON_HELPERS.as_bytes())?;
+        }
+        if (self.numeric != NumericMode::Decimal || !self.numeric_columns.is_empty()) && !stub {
+            out.write_all(NUMERIC_HELPERS.as_bytes())?;
+        }
+        let mut imports = self
+            .type_map
+            .values()
+            .filter_map(qualified_py_name)
+            .map(|(module, name)| format!("from {module} import {name}"))
+            .collect::<Vec<_>>();
+        imports.sort();
+        imports.dedup();
+        if !imports.is_empty() {
+            // Annotations are not evaluated, the generated module does not import them at runtime.
+            let imports = match stub {
+                true => format!("\n{}\n", imports.join("\n")),
+                false => format!("\nif TYPE_CHECKING:\n    {}\n", imports.join("\n    ")),
+            };
+            out.write_all(imports.as_bytes())?;
+        }
+        // Queries overriding the mode of the target need the other connection type.
+        if self
+            .queries
+            .iter()
+            .any(|(_, query)| query.r#async == Some(!self.r#async))
+        {
+            out.write_all(match self.r#async {
+                true => b"\nfrom sqlalchemy import Connection\n",
+                false => b"\nfrom sqlalchemy.ext.asyncio import AsyncConnection\n",
+            })?;
+        }
+        out.write_all(self.dunder_all().as_bytes())?;
+        for (file_name, query) in &self.queries {
+            let is_async = query.r#async.unwrap_or(self.r#async);
+            let func = self.query_to_sql_alchemy(file_name, query, is_async, stub)?;
+            writeln!(out, "{func}")?;
+        }
+        Ok(())
+    }
+
+    /// Function loading rows with `COPY`, through `copy_records_to_table` of asyncpg when
+    /// `is_async` is set and `cursor.copy` of psycopg otherwise, as SQLAlchemy has no bulk
+    /// loading of its own.
+    fn copy_to_sql_alchemy(
+        &self,
+        fn_name: &str,
+        query: &str,
+        copy: &CopyFrom,
+        is_async: bool,
+        stub: bool,
+    ) -> String {
+        let class_name = Self::row_class_name(fn_name);
+        let fields = field_names(&copy.columns);
+        let class_fields = copy
+            .columns
+            .iter()
+            .zip(&fields)
+            .map(|(column, field)| {
+                format!("    {field}: {}", self.to_input_type(column, &mut NoBounds))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let row_class = format!("@dataclass\nclass {class_name}:\n{class_fields}\n");
+        let py_fn_name = py_identifier(fn_name);
+        let params = format!(
+            "{}, rows: Iterable[{class_name}]",
+            Self::conn_param(is_async)
+        );
+        let function_signature = match is_async {
+            true => format!("async def {py_fn_name}({params}) -> int:"),
+            false => format!("def {py_fn_name}({params}) -> int:"),
+        };
+        if stub {
+            return format!("{row_class}\n\n{function_signature} ...\n");
+        }
+        let values = copy
+            .columns
+            .iter()
+            .zip(&fields)
+            .map(|(column, field)| match self.is_parsed_json(column) {
+                true => format!("_dump_json(row.{field})"),
+                false => format!("row.{field}"),
+            })
+            .collect::<Vec<_>>();
+        let values = match values.as_slice() {
+            // A trailing comma keeps rows of a single column tuples.
+            [value] => format!("({value},)"),
+            _ => format!("({})", values.join(", ")),
+        };
+        let function_content = match is_async {
+            true => {
+                let columns = copy
+                    .columns
+                    .iter()
+                    .map(|column| format!("{:?}", escape_string(&column.name)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let schema = match &copy.schema {
+                    Some(schema) => format!(" schema_name={:?},", escape_string(schema)),
+                    None => String::new(),
+                };
+                format!(
+                    "    raw = await conn.get_raw_connection()
+    status = await raw.driver_connection.copy_records_to_table(  # type: ignore
+        {:?},{schema} columns=[{columns}], records=({values} for row in rows)
+    )
+    return int(status.split()[-1])
+",
+                    escape_string(&copy.table)
+                )
+            }
+            false => format!(
+                "    with conn.connection.driver_connection.cursor() as cursor:  # type: ignore
+        with cursor.copy(\"\"\"{query}\"\"\") as copy:
+            for row in rows:
+                copy.write_row({values})
+        return cursor.rowcount
+"
+            ),
+        };
+        format!("{row_class}\n\n{function_signature}\n{function_content}")
     }
 
     fn query_to_sql_alchemy(
@@ -271,8 +604,12 @@ impl SqlAlchemyV2CodeGen {
         fn_name: &str,
         query_fn: &QueryDefinition,
         is_async: bool,
+        stub: bool,
     ) -> Result<String, Box<dyn Error>> {
-        let mut params = vec![self.conn_param().to_string()];
+        if let Some(copy) = &query_fn.copy_from {
+            return Ok(self.copy_to_sql_alchemy(fn_name, &query_fn.query, copy, is_async, stub));
+        }
+        let mut params = vec![Self::conn_param(is_async).to_string()];
         if !query_fn.inputs.is_empty() && self.argument_mode == ArgumentMode::Keyword {
             params.push("*".to_string());
         }
@@ -283,25 +620,53 @@ impl SqlAlchemyV2CodeGen {
         } else {
             &mut NoBounds {}
         };
-        for query_value in &query_fn.inputs {
+        let mut has_default = false;
+        for (query_value, py_name) in query_fn.inputs.iter().zip(field_names(&query_fn.inputs)) {
             let param_name = &query_value.name;
+            let optional = query_fn
+                .blocks
+                .iter()
+                .any(|block| block.param == *param_name);
+            let default = match query_fn.defaults.get(param_name) {
+                Some(_) if stub => " = ...".to_string(),
+                Some(default) => format!(" = {}", to_py_literal(default)?),
+                None if optional && stub => " = ...".to_string(),
+                None if optional => " = None".to_string(),
+                None if has_default && self.argument_mode == ArgumentMode::Positional => {
+                    Err(format!(
+                        "{param_name} of {fn_name} has no default but follows a parameter with one, \
+                         declare its default or use the keyword argument mode"
+                    ))?
+                }
+                None => String::new(),
+            };
+            has_default |= !default.is_empty();
+            // The bind keeps the name used in the query.
             params.push(format!(
-                "{}: {}",
-                param_name,
+                "{py_name}: {}{default}",
                 self.to_input_type(query_value, &mut *bounds)
             ));
-            binds.push(format!("\"{param_name}\": {param_name}"));
+            match self.is_parsed_json(query_value) {
+                true => binds.push(format!("\"{param_name}\": _dump_json({py_name})")),
+                false => binds.push(format!("\"{param_name}\": {py_name}")),
+            }
         }
         let mut outs = vec![];
 
-        for query_value in &query_fn.outputs {
-            let py_type = self.to_output_type(query_value);
-            outs.push(format!("    {}: {}", query_value.name, py_type));
+        for (query_value, field) in query_fn.outputs.iter().zip(field_names(&query_fn.outputs)) {
+            let py_type = self.to_output_type(fn_name, query_value);
+            outs.push(format!("    {field}: {py_type}"));
+        }
+        if query_fn.returns == Some(Returns::Exec) {
+            outs.clear();
         }
-        let class_name = to_pascal(&format!("{fn_name}_output"));
-        let out_types = match outs.is_empty() {
-            true => "None",
-            false => &format!("DbOutput[{class_name}]"),
+        let class_name = Self::output_class_name(fn_name);
+        let rows_affected = query_fn.rows_affected && query_fn.returns != Some(Returns::Exec);
+        let out_types = match (outs.is_empty(), query_fn.returns) {
+            (true, _) if rows_affected => "int",
+            (true, _) => "None",
+            (false, Some(Returns::One)) => &format!("{class_name} | None"),
+            (false, _) => &format!("DbOutput[{class_name}]"),
         };
         let return_type = match outs.is_empty() {
             true => "",
@@ -309,9 +674,10 @@ impl SqlAlchemyV2CodeGen {
         };
 
         let in_types = params.join(", ");
+        let py_fn_name = py_identifier(fn_name);
         let function_signature = match is_async {
-            true => format!("async def {fn_name}{bounds}({in_types}) -> {out_types}:"),
-            false => format!("def {fn_name}{bounds}({in_types}) -> {out_types}:"),
+            true => format!("async def {py_fn_name}{bounds}({in_types}) -> {out_types}:"),
+            false => format!("def {py_fn_name}{bounds}({in_types}) -> {out_types}:"),
         };
 
         let bind_text = match binds.len() {
@@ -319,20 +685,96 @@ impl SqlAlchemyV2CodeGen {
             _ => format!("{{{}}}", binds.join(", ")),
         };
 
-        let mut function_content = match is_async {
-            true => format!(
-                "    result = await conn.execute(text(\"\"\"{}\"\"\"), {})\n",
-                query_fn.query, bind_text
-            ),
-            false => format!(
-                "    result = conn.execute(text(\"\"\"{}\"\"\"), {})\n",
-                query_fn.query, bind_text
-            ),
+        if stub {
+            return Ok(format!("{return_type}\n\n{function_signature} ...\n"));
+        }
+
+        let mut function_content = String::new();
+        let statements = split_statements(&query_fn.query)?;
+        // Each statement of a query holding several is executed on its own, the result is the
+        // one of the last.
+        let mut executed = match statements.len() {
+            0 | 1 => vec![(
+                format!("text(\"\"\"{}\"\"\")", query_fn.query),
+                query_fn.query.as_str(),
+            )],
+            _ if !query_fn.blocks.is_empty() => Err(format!(
+                "{fn_name} has several statements, which optional blocks can not be used with"
+            ))?,
+            _ => statements
+                .iter()
+                .map(|statement| {
+                    let sql = statement.sql.trim();
+                    (format!("text(\"\"\"{sql}\"\"\")"), sql)
+                })
+                .collect(),
         };
-        if !outs.is_empty() {
-            function_content.push_str(&format!(
-                "    return DbOutput({class_name}(*row) for row in result) # type: ignore\n"
-            ));
+        if !query_fn.blocks.is_empty() {
+            // Optional blocks are joined with the rest of the query when their input is given.
+            let mut parts = vec![];
+            let mut head = 0;
+            for block in &query_fn.blocks {
+                let block_text = &query_fn.query[block.start..block.end];
+                parts.push(format!(
+                    "        \"\"\"{}\"\"\",",
+                    &query_fn.query[head..block.start]
+                ));
+                parts.push(format!(
+                    "        \"\"\"{block_text}\"\"\" if {} is not None else \"\",",
+                    py_identifier(&block.param)
+                ));
+                head = block.end;
+            }
+            parts.push(format!("        \"\"\"{}\"\"\",", &query_fn.query[head..]));
+            function_content += &format!("    query = \"\".join((\n{}\n    ))\n", parts.join("\n"));
+            executed[0].0 = "text(query)".to_string();
+        }
+        let last = executed.len() - 1;
+        for (index, (mut statement, sql)) in executed.into_iter().enumerate() {
+            let expanding = query_fn
+                .expanding
+                .iter()
+                .filter(|name| uses_param(sql, name))
+                .map(|name| format!("bindparam(\"{name}\", expanding=True)"))
+                .collect::<Vec<_>>();
+            if !expanding.is_empty() {
+                statement += &format!(".bindparams({})", expanding.join(", "));
+            }
+            let assign = match index == last {
+                true => "result = ",
+                false => "",
+            };
+            function_content += &match is_async {
+                true => format!("    {assign}await conn.execute({statement}, {bind_text})\n"),
+                false => format!("    {assign}conn.execute({statement}, {bind_text})\n"),
+            };
+        }
+        let conversions = query_fn
+            .outputs
+            .iter()
+            .map(|item| self.output_conversion(fn_name, item))
+            .collect::<Vec<_>>();
+        let fields = match conversions.iter().any(Option::is_some) {
+            true => conversions
+                .iter()
+                .enumerate()
+                .map(|(index, conversion)| match conversion {
+                    Some(conversion) => format!("{conversion}(row[{index}])"),
+                    None => format!("row[{index}]"),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            false => "*row".to_string(),
+        };
+        match (outs.is_empty(), query_fn.returns) {
+            (true, _) if rows_affected => function_content.push_str("    return result.rowcount\n"),
+            (true, _) => {}
+            (false, Some(Returns::One)) => function_content.push_str(&format!(
+                "    row = result.first()\n    return None if row is None else {class_name}({fields}) # type: ignore\n"
+            )),
+            (false, _) => function_content.push_str(&format!(
+                "    return DbOutput({class_name}({fields}) for row in result) # type: ignore\n"
+            )),
         }
         Ok(format!(
             "{return_type}\n\n{function_signature}\n{function_content}"
@@ -340,25 +782,166 @@ impl SqlAlchemyV2CodeGen {
     }
 }
 
+/// Whether `sql` has a `:name` placeholder for `name`.
+pub(super) fn uses_param(sql: &str, name: &str) -> bool {
+    let placeholder = format!(":{name}");
+    sql.match_indices(&placeholder).any(|(start, _)| {
+        !sql[start + placeholder.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+    })
+}
+
+/// Python name of each column or parameter, names that end up equal are suffixed like
+/// duplicate columns are by inference.
+pub(super) fn field_names(items: &[QueryItem]) -> Vec<String> {
+    let mut names = items
+        .iter()
+        .map(|item| py_identifier(&item.name).into_owned())
+        .collect::<Vec<_>>();
+    deduplicate_names(&mut names);
+    names
+}
+
 impl CodeGen for SqlAlchemyV2CodeGen {
     fn push(&mut self, file_name: &str, query: QueryDefinition) -> Result<(), Box<dyn Error>> {
+        for (item, field) in query.outputs.iter().zip(field_names(&query.outputs)) {
+            // Keywords getting a trailing underscore is documented, other renames are not.
+            if field != item.name && field != format!("{}_", item.name) {
+                tracing::warn!(
+                    "Column {:?} of {file_name} is named {field} in generated code",
+                    item.name
+                );
+            }
+        }
         self.queries.insert(file_name.to_string(), query);
         Ok(())
     }
 
-    fn finalize(&self) -> Result<String, Box<dyn Error>> {
-        let mut code = match self.r#async {
-            true => include_str!("./sqlalchemy_async/template.txt").to_string(),
-            false => include_str!("./sqlalchemy/template.txt").to_string(),
+    fn write(&self, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let template = match self.r#async {
+            true => include_str!("./sqlalchemy_async/template.txt"),
+            false => include_str!("./sqlalchemy/template.txt"),
         };
-        if self.type_gen == TypeGen::Pydantic {
-            code += "\nfrom pydantic import AwareDatetime, NaiveDatetime\n"
+        self.write_module(out, template, false)
+    }
+
+    fn has_stub(&self) -> bool {
+        self.stub
+    }
+
+    fn write_stub(&self, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let template = match self.r#async {
+            true => include_str!("./sqlalchemy_async/stub.txt"),
+            false => include_str!("./sqlalchemy/stub.txt"),
+        };
+        self.write_module(out, template, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use sql_infer_core::{
+        inference::{Nullability, QueryItem, SqlType},
+        parser::StatementKind,
+    };
+
+    use crate::codegen::{
+        CodeGen, QueryDefinition,
+        sqlalchemy_v2::{ArgumentMode, JsonMode, SqlAlchemyV2CodeGen, TypeGen, field_names},
+    };
+
+    fn item(name: &str, sql_type: SqlType) -> QueryItem {
+        QueryItem {
+            name: name.to_string(),
+            sql_type,
+            nullable: Nullability::False,
         }
-        for (file_name, query) in &self.queries {
-            let func = self.query_to_sql_alchemy(file_name, query, self.r#async)?;
-            code.push_str(&func);
-            code.push('\n');
+    }
+
+    fn query(sql: &str, inputs: &[QueryItem], outputs: &[QueryItem]) -> QueryDefinition {
+        QueryDefinition {
+            query: sql.to_string(),
+            inputs: inputs.into(),
+            outputs: outputs.into(),
+            defaults: BTreeMap::new(),
+            expanding: BTreeSet::new(),
+            blocks: vec![],
+            returns: None,
+            r#async: None,
+            disabled_passes: BTreeSet::new(),
+            rows_affected: false,
+            kind: StatementKind::Read,
+            copy_from: None,
         }
-        Ok(code)
+    }
+
+    fn module(datetime_aliases: bool, name: &str, query: QueryDefinition) -> String {
+        let mut codegen = SqlAlchemyV2CodeGen::new(
+            false,
+            ArgumentMode::Keyword,
+            TypeGen::Python,
+            false,
+            false,
+            datetime_aliases,
+            JsonMode::default(),
+        );
+        codegen.push(name, query).unwrap();
+        let mut module = vec![];
+        codegen.write(&mut module).unwrap();
+        String::from_utf8(module).unwrap()
+    }
+
+    #[test]
+    fn datetime_aliases_tell_timestamps_apart() {
+        let events = || {
+            query(
+                "select created_at, logged_at from events where created_at > :since",
+                &[item("since", SqlType::Timestamp { tz: true })],
+                &[
+                    item("created_at", SqlType::Timestamp { tz: true }),
+                    item("logged_at", SqlType::Timestamp { tz: false }),
+                ],
+            )
+        };
+        let plain = module(false, "events", events());
+        assert!(plain.contains("    created_at: datetime\n    logged_at: datetime\n"));
+        assert!(plain.contains("*, since: datetime)"));
+        assert!(!plain.contains("AwareDatetime"));
+
+        let aliased = module(true, "events", events());
+        assert!(aliased.contains("type AwareDatetime = datetime\ntype NaiveDatetime = datetime\n"));
+        assert!(aliased.contains("    created_at: AwareDatetime\n    logged_at: NaiveDatetime\n"));
+        assert!(aliased.contains("*, since: AwareDatetime)"));
+    }
+
+    #[test]
+    fn field_names_are_valid_and_distinct() {
+        let items = [
+            "first name",
+            "first.name",
+            "café",
+            "größe",
+            "a",
+            "a",
+            "a_2",
+            "?column?",
+            "?column?",
+        ]
+        .map(|name| item(name, SqlType::Text));
+        assert_eq!(
+            field_names(&items),
+            [
+                "first_name",
+                "first_name_2",
+                "café",
+                "größe",
+                "a",
+                "a_3",
+                "a_2",
+                "column",
+                "column_2"
+            ]
+        );
     }
 }
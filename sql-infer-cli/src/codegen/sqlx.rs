@@ -0,0 +1,193 @@
+use std::{collections::BTreeMap, error::Error, io::Write};
+
+use serde::Serialize;
+use serde_json::{Value, json};
+use sql_infer_core::inference::{Nullability, SqlType};
+
+use crate::{
+    codegen::{Files, QueryDefinition},
+    config::PlaceholderStyle,
+    manifest::sha256,
+    utils::parse_into_postgres,
+};
+
+use super::CodeGen;
+
+/// Writes the `query-<hash>.json` files `sqlx::query!` reads in offline mode, the target being
+/// the `.sqlx` directory.
+#[derive(Default)]
+pub struct SqlxCodeGen {
+    queries: BTreeMap<String, QueryDefinition>,
+}
+
+#[derive(Serialize)]
+struct Column {
+    ordinal: usize,
+    name: String,
+    type_info: Value,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+enum Parameters {
+    Left(Vec<Value>),
+}
+
+#[derive(Serialize)]
+struct Describe {
+    columns: Vec<Column>,
+    parameters: Parameters,
+    nullable: Vec<Option<bool>>,
+}
+
+#[derive(Serialize)]
+struct QueryData {
+    db_name: &'static str,
+    query: String,
+    describe: Describe,
+    hash: String,
+}
+
+/// Name of `sql_type` as sqlx serializes its `PgTypeInfo`.
+fn type_info(sql_type: &SqlType) -> Value {
+    let name = match sql_type {
+        SqlType::Bool => "Bool",
+        SqlType::Int2 | SqlType::SmallSerial => "Int2",
+        SqlType::Int4 | SqlType::Serial => "Int4",
+        SqlType::Int8 | SqlType::BigSerial => "Int8",
+        SqlType::Decimal { .. } => "Numeric",
+        SqlType::Timestamp { tz: true } => "Timestamptz",
+        SqlType::Timestamp { tz: false } => "Timestamp",
+        SqlType::Date => "Date",
+        SqlType::Time { tz: true } => "Timetz",
+        SqlType::Time { tz: false } => "Time",
+        SqlType::Interval => "Interval",
+        SqlType::Char { .. } => "Bpchar",
+        SqlType::VarChar { .. } => "Varchar",
+        SqlType::Bit { .. } => "Bit",
+        SqlType::VarBit { .. } => "Varbit",
+        SqlType::Text => "Text",
+        SqlType::Json => "Json",
+        SqlType::Jsonb => "Jsonb",
+        SqlType::Float4 => "Float4",
+        SqlType::Float8 => "Float8",
+        SqlType::Record(_) => "Record",
+        SqlType::Unknown => "Unknown",
+        SqlType::Enum { name, tags } => {
+            return json!({ "Custom": { "name": name, "kind": { "Enum": tags } } });
+        }
+        // Postgres arrays have no nesting in their type, `int[][]` is `int[]`.
+        SqlType::Array(inner) if matches!(**inner, SqlType::Array(_)) => return type_info(inner),
+        SqlType::Array(inner) => {
+            return match type_info(inner) {
+                Value::String(name) => Value::String(format!("{name}Array")),
+                element => {
+                    let name = match &**inner {
+                        SqlType::Enum { name, .. } => format!("_{name}"),
+                        _ => "unknown[]".to_string(),
+                    };
+                    json!({ "Custom": { "name": name, "kind": { "Array": element } } })
+                }
+            };
+        }
+    };
+    Value::String(name.to_string())
+}
+
+fn nullable(nullability: Nullability) -> Option<bool> {
+    match nullability {
+        Nullability::True => Some(true),
+        Nullability::False => Some(false),
+        Nullability::Unknown => None,
+    }
+}
+
+impl SqlxCodeGen {
+    /// The metadata of `query`, keyed by its text with `$n` placeholders. Queries written with
+    /// `$n` placeholders in a file of their own keep their text, so `sqlx::query_file!` finds
+    /// them.
+    fn query_data(query: &QueryDefinition) -> Result<QueryData, Box<dyn Error>> {
+        let raw_query = parse_into_postgres(&query.query, PlaceholderStyle::Named)?.raw_query;
+        let columns = query
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(ordinal, output)| Column {
+                ordinal,
+                name: output.name.clone(),
+                type_info: type_info(&output.sql_type),
+            })
+            .collect();
+        let parameters = query
+            .inputs
+            .iter()
+            .map(|input| type_info(&input.sql_type))
+            .collect();
+        Ok(QueryData {
+            db_name: "PostgreSQL",
+            hash: sha256(&raw_query),
+            query: raw_query,
+            describe: Describe {
+                columns,
+                parameters: Parameters::Left(parameters),
+                nullable: query
+                    .outputs
+                    .iter()
+                    .map(|output| nullable(output.nullable))
+                    .collect(),
+            },
+        })
+    }
+}
+
+impl CodeGen for SqlxCodeGen {
+    fn push(&mut self, file_name: &str, query: QueryDefinition) -> Result<(), Box<dyn Error>> {
+        self.queries.insert(file_name.to_string(), query);
+        Ok(())
+    }
+
+    fn write(&self, _out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+        Err("the sqlx mode writes a directory of files")?
+    }
+
+    fn files(&self) -> Result<Option<Files>, Box<dyn Error>> {
+        let mut files = vec![];
+        for query in self.queries.values() {
+            let data = Self::query_data(query)?;
+            files.push((
+                format!("query-{}.json", data.hash),
+                serde_json::to_string_pretty(&data)?,
+            ));
+        }
+        Ok(Some(files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sql_infer_core::inference::SqlType;
+
+    use crate::codegen::sqlx::type_info;
+
+    #[test]
+    fn types_are_named_like_sqlx() {
+        let mood = SqlType::Enum {
+            name: "mood".to_string(),
+            tags: ["happy".to_string(), "sad".to_string()].into(),
+        };
+        assert_eq!(type_info(&SqlType::Serial), json!("Int4"));
+        assert_eq!(
+            type_info(&SqlType::Array(Box::new(SqlType::Timestamp { tz: true }))),
+            json!("TimestamptzArray")
+        );
+        assert_eq!(
+            type_info(&mood),
+            json!({ "Custom": { "name": "mood", "kind": { "Enum": ["happy", "sad"] } } })
+        );
+        assert_eq!(
+            type_info(&SqlType::Array(Box::new(mood.clone()))),
+            json!({ "Custom": { "name": "_mood", "kind": { "Array": type_info(&mood) } } })
+        );
+    }
+}
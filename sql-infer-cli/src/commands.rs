@@ -1,5 +1,13 @@
 pub mod analyze;
+pub mod check;
+pub mod completions;
+pub mod config;
+pub mod doctor;
+pub mod explain;
 pub mod generate;
+pub mod init;
+pub mod lint_queries;
+pub mod list;
 pub mod schema;
 
 pub use generate::*;
@@ -2,12 +2,13 @@ use std::error::Error;
 
 use clap::{Parser, ValueEnum};
 use sql_infer_core::{
-    inference::{self},
+    SqlInferBuilder,
+    inference::{self, nullability::ColumnNullability},
     parser,
 };
 use sqlx::postgres::PgPoolOptions;
 
-use crate::config;
+use crate::{config::DatabaseArgs, failure::Failure, utils};
 
 #[derive(ValueEnum, Debug, Clone, Default)]
 pub enum Analysis {
@@ -15,6 +16,8 @@ pub enum Analysis {
     Columns,
     ColumnsWithDb,
     Tables,
+    /// Table columns each output column of the query is computed from.
+    Lineage,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -22,19 +25,14 @@ pub enum Analysis {
 pub struct Analyze {
     analysis: Analysis,
     query: Vec<String>,
+    #[command(flatten)]
+    database: DatabaseArgs,
 }
 
 impl Analyze {
-    fn get_query(query: String) -> Result<String, Box<dyn Error>> {
-        Ok(match std::fs::exists(&query)? {
-            true => std::fs::read_to_string(query)?,
-            false => query,
-        })
-    }
-
     pub async fn run(self) -> Result<(), Box<dyn Error>> {
         for query in self.query {
-            let query = &Self::get_query(query)?;
+            let query = &utils::read_query(query)?;
             let statements = parser::to_ast(query)?;
             match self.analysis {
                 Analysis::Columns => {
@@ -56,17 +54,50 @@ impl Analyze {
                 Analysis::ColumnsWithDb => {
                     let pool = PgPoolOptions::new()
                         .max_connections(1)
-                        .connect(&config::db_url()?)
-                        .await?;
+                        .connect(&self.database.url()?)
+                        .await
+                        .map_err(Failure::Connection)?;
                     for statement in statements {
                         let fields = parser::find_fields(&statement)?;
                         for (field, column) in fields {
                             let (column, _) =
-                                inference::get_column_information_schema(&pool, &column).await?;
+                                inference::get_column_information_schema(&pool, None, &column)
+                                    .await?;
                             println!("{field}: {column}");
                         }
                     }
                 }
+                Analysis::Lineage => {
+                    let pool = PgPoolOptions::new()
+                        .max_connections(1)
+                        .connect(&self.database.url()?)
+                        .await
+                        .map_err(Failure::Connection)?;
+                    let mut sql_infer = SqlInferBuilder::default();
+                    // Origins are only explained along with an information schema pass.
+                    sql_infer
+                        .add_information_schema_pass(ColumnNullability)
+                        .explain_column_origins(true);
+                    let types = sql_infer.build().infer_types(&pool, query).await?;
+                    for (output, lineage) in types.output.iter().zip(&types.lineage) {
+                        let Some(lineage) = lineage else {
+                            println!("{}: unknown", output.name);
+                            continue;
+                        };
+                        let mut sources = lineage
+                            .columns()
+                            .into_iter()
+                            .map(|(table, column)| format!("{table}.{column}"))
+                            .collect::<Vec<_>>();
+                        if lineage.is_partial() {
+                            sources.push("(unresolved expressions)".to_string());
+                        }
+                        match sources.is_empty() {
+                            true => println!("{}: constant", output.name),
+                            false => println!("{}: {}", output.name, sources.join(", ")),
+                        }
+                    }
+                }
             }
         }
         Ok(())
@@ -0,0 +1,88 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Parser, ValueHint};
+
+use crate::{
+    commands::generate::generate,
+    config::{DatabaseArgs, SqlInferConfig},
+    diagnostics::{DiagnosticsFormat, report},
+    failure::Failure,
+    migrations::migrate,
+    temp_db::TempDb,
+};
+
+#[derive(Parser, Debug, Clone)]
+#[must_use]
+pub struct Check {
+    #[arg(value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+    /// Print every error and warning encountered during inference in the given format.
+    #[arg(long, value_enum, alias = "output-format")]
+    diagnostics: Option<DiagnosticsFormat>,
+    /// Fail when inference produces any warning.
+    #[arg(long)]
+    strict: bool,
+    /// Number of queries inferred concurrently, overriding `jobs` in the config.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    /// Only infer the queries of these files again, taking the others from the manifest, e.g.
+    /// the files staged in a pre-commit hook.
+    #[arg(long, num_args = 1.., value_hint = ValueHint::FilePath)]
+    changed: Vec<PathBuf>,
+    /// Only check the targets of this `[profile.<name>]` entry.
+    #[arg(long)]
+    profile: Option<String>,
+    /// Run against a disposable database with the `migrations` applied, removed afterwards.
+    #[arg(long, conflicts_with = "database_url")]
+    with_temp_db: bool,
+    #[command(flatten)]
+    database: DatabaseArgs,
+}
+
+impl Check {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        let mut config = SqlInferConfig::load(self.config)?;
+        config.select_profile(self.profile)?;
+        config.select_database(self.database)?;
+        // Kept until the end of the run, dropping it removes the database.
+        let temp_db = match self.with_temp_db {
+            true => Some(TempDb::start(&config.temp_db).await?),
+            false => None,
+        };
+        if let Some(temp_db) = &temp_db {
+            config.database_url = Some(temp_db.url().to_string());
+        }
+        if !self.changed.is_empty() {
+            // Files that were deleted or are not queries can not be found among the sources.
+            let changed = self
+                .changed
+                .iter()
+                .filter_map(|path| path.canonicalize().ok())
+                .collect();
+            config.changed = Some(changed);
+        }
+        if let Some(jobs) = self.jobs {
+            config.jobs = jobs.max(1);
+        }
+        migrate(&config).await?;
+        let generated = generate(&config).await?;
+        if let Some(format) = self.diagnostics {
+            report(&generated.diagnostics, format)?;
+        }
+
+        generated.status(self.strict)?;
+
+        let mut outdated = 0;
+        for (path, code) in generated.files()? {
+            let current = std::fs::read_to_string(&path).ok();
+            if current.as_ref() != Some(&code) {
+                tracing::error!("{} is out of date", path.display());
+                outdated += 1;
+            }
+        }
+        if outdated > 0 {
+            Err(Failure::Drift { count: outdated })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,19 @@
+use std::error::Error;
+
+use clap::Parser;
+use clap_complete::Shell;
+
+#[derive(Parser, Debug, Clone)]
+#[must_use]
+pub struct Completions {
+    shell: Shell,
+}
+
+impl Completions {
+    /// Print the completions of `command`, the whole CLI as defined by the binary.
+    pub async fn run(self, mut command: clap::Command) -> Result<(), Box<dyn Error>> {
+        let name = command.get_name().to_string();
+        clap_complete::generate(self.shell, &mut command, name, &mut std::io::stdout());
+        Ok(())
+    }
+}
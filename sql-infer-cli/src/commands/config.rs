@@ -0,0 +1,69 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Parser, Subcommand, ValueHint};
+
+use crate::config::{self, upgrade::upgrade_config};
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Action {
+    /// Check the config for unknown keys and invalid values, then print it with its defaults
+    /// filled in and its environment variables replaced. Passwords of database URLs are masked.
+    Validate {
+        #[arg(value_hint = ValueHint::FilePath)]
+        config: Option<PathBuf>,
+    },
+    /// Rewrite a config written for an older version to the current layout, listing each
+    /// change. Comments are not kept.
+    Upgrade {
+        #[arg(value_hint = ValueHint::FilePath)]
+        config: Option<PathBuf>,
+        /// Print the upgraded config instead of writing it.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Parser, Debug, Clone)]
+#[must_use]
+pub struct Config {
+    #[command(subcommand)]
+    action: Action,
+}
+
+impl Config {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        match self.action {
+            Action::Validate { config } => {
+                let validation = config::validate(config)?;
+                let path = validation.path.display();
+                for key in &validation.unknown_keys {
+                    match key.line {
+                        Some(line) => eprintln!("{path}:{line}: unknown key `{}`", key.path),
+                        None => eprintln!("{path}: unknown key `{}`", key.path),
+                    }
+                }
+                print!("{}", validation.effective);
+                if !validation.unknown_keys.is_empty() {
+                    Err(format!(
+                        "{} unknown key(s) found",
+                        validation.unknown_keys.len()
+                    ))?;
+                }
+            }
+            Action::Upgrade { config, dry_run } => {
+                let upgrade = upgrade_config(config)?;
+                for change in &upgrade.changes {
+                    eprintln!("{change}");
+                }
+                if dry_run {
+                    print!("{}", upgrade.upgraded);
+                } else if upgrade.changes.is_empty() {
+                    eprintln!("{} is up to date", upgrade.path.display());
+                } else {
+                    std::fs::write(&upgrade.path, upgrade.upgraded)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use std::{error::Error, fmt::Display, path::PathBuf};
+
+use clap::{Parser, ValueHint};
+use sqlx::{Pool, Postgres, postgres::PgPoolOptions, query_scalar};
+
+use crate::{
+    commands::generate::is_glob,
+    config::{self, DatabaseArgs, SqlInferConfig},
+};
+
+enum Status {
+    Ok(String),
+    Error { msg: String, hint: String },
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Ok(msg) => write!(f, "[ok] {msg}"),
+            Status::Error { msg, hint } => write!(f, "[error] {msg}\n        hint: {hint}"),
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+#[must_use]
+pub struct Doctor {
+    #[arg(value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    database: DatabaseArgs,
+}
+
+/// Check the database at `url`, read from `source`, e.g. `DATABASE_URL`.
+async fn check_database(url: Result<String, Box<dyn Error>>, source: &str) -> Vec<Status> {
+    let url = match url {
+        Ok(url) => url,
+        Err(error) => {
+            return vec![Status::Error {
+                msg: error.to_string(),
+                hint: "add DATABASE_URL to the environment or a .env file".into(),
+            }];
+        }
+    };
+    let mut statuses = vec![Status::Ok(format!("{source} is set"))];
+    let pool: Pool<Postgres> = match PgPoolOptions::new().max_connections(1).connect(&url).await {
+        Ok(pool) => pool,
+        Err(error) => {
+            statuses.push(Status::Error {
+                msg: format!("could not connect to the database: {error}"),
+                hint: format!("check that the database is running and {source} is correct"),
+            });
+            return statuses;
+        }
+    };
+    statuses.push(Status::Ok("connected to the database".into()));
+    match query_scalar!(r#"select current_schemas(false)::text[] as "schemas!""#)
+        .fetch_one(&pool)
+        .await
+    {
+        Ok(schemas) if schemas.is_empty() => statuses.push(Status::Error {
+            msg: "none of the schemas in search_path exist".into(),
+            hint: "create the schema or fix search_path for the database user".into(),
+        }),
+        Ok(schemas) => statuses.push(Status::Ok(format!(
+            "search_path schemas exist: {}",
+            schemas.join(", ")
+        ))),
+        Err(error) => statuses.push(Status::Error {
+            msg: format!("could not read search_path: {error}"),
+            hint: "check that the database user can read the catalog".into(),
+        }),
+    }
+    statuses
+}
+
+fn check_sources(config: &SqlInferConfig) -> Vec<Status> {
+    let mut statuses = vec![];
+    for directory in &config.source {
+        if is_glob(directory) {
+            let matches = glob::glob(&directory.to_string_lossy())
+                .map(|paths| paths.flatten().filter(|path| path.is_file()).count());
+            match matches {
+                Ok(0) | Err(_) => statuses.push(Status::Error {
+                    msg: format!("{} matches no files", directory.display()),
+                    hint: "fix the pattern in `path`".into(),
+                }),
+                Ok(count) => statuses.push(Status::Ok(format!(
+                    "{} matches {count} file(s)",
+                    directory.display()
+                ))),
+            }
+            continue;
+        }
+        if directory.is_file() {
+            statuses.push(Status::Ok(format!("{} exists", directory.display())));
+            continue;
+        }
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            statuses.push(Status::Error {
+                msg: format!("{} is not a readable directory", directory.display()),
+                hint: "fix `path` in the config or create the directory".into(),
+            });
+            continue;
+        };
+        let queries = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "sql"))
+            .count();
+        match queries {
+            0 => statuses.push(Status::Error {
+                msg: format!("{} contains no .sql files", directory.display()),
+                hint: "add queries or remove the directory from `path`".into(),
+            }),
+            _ => statuses.push(Status::Ok(format!(
+                "{} contains {queries} .sql file(s)",
+                directory.display()
+            ))),
+        }
+    }
+    statuses
+}
+
+impl Doctor {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        let mut statuses = vec![];
+        let mut url = Some((config::db_url(), "DATABASE_URL".to_string()));
+        let database_url = self.database.database_url.clone();
+        match SqlInferConfig::load(self.config) {
+            Ok(mut config) => {
+                statuses.push(Status::Ok("config parsed".into()));
+                statuses.extend(check_sources(&config));
+                match config.select_database(self.database) {
+                    Ok(()) => {
+                        if database_url.is_some() {
+                            url = Some((config.db_url(), "--database-url".to_string()));
+                        } else if let Some(env) = &config.env {
+                            url = Some((config.db_url(), format!("the url of [databases.{env}]")));
+                        }
+                    }
+                    Err(error) => {
+                        statuses.push(Status::Error {
+                            msg: error.to_string(),
+                            hint: "add the database to the config or pick another --env".into(),
+                        });
+                        url = None;
+                    }
+                }
+            }
+            Err(error) => statuses.push(Status::Error {
+                msg: format!("could not load config: {error}"),
+                hint: "run `sql-infer init` to create a config or pass its path".into(),
+            }),
+        }
+        if let Some((url, source)) = url {
+            statuses.extend(check_database(url, &source).await);
+        }
+
+        let mut failures = 0;
+        for status in &statuses {
+            println!("{status}");
+            if let Status::Error { .. } = status {
+                failures += 1;
+            }
+        }
+        if failures != 0 {
+            Err(format!("{failures} check(s) failed"))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use std::error::Error;
+
+use clap::Parser;
+use serde_json::Value;
+use sqlx::{Executor, postgres::PgPoolOptions, query_scalar};
+
+use crate::{
+    config::{DatabaseArgs, PlaceholderStyle},
+    failure::Failure,
+    utils::{self, ParametrizedQuery, parse_into_postgres},
+};
+
+#[derive(Parser, Debug, Clone)]
+#[must_use]
+pub struct Explain {
+    /// Path to a query file or the SQL itself.
+    query: String,
+    /// How parameters are written in the query.
+    #[arg(long, value_enum, default_value_t)]
+    placeholders: PlaceholderStyle,
+    #[command(flatten)]
+    database: DatabaseArgs,
+}
+
+struct PlanSummary {
+    lines: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl PlanSummary {
+    fn visit(&mut self, plan: &Value, depth: usize) {
+        let node_type = plan["Node Type"].as_str().unwrap_or("Unknown");
+        let mut line = format!("{}{node_type}", "  ".repeat(depth));
+        if let Some(join_type) = plan["Join Type"].as_str() {
+            line += &format!(" ({join_type})");
+        }
+        if let Some(relation) = plan["Relation Name"].as_str() {
+            line += &format!(" on {relation}");
+            if node_type == "Seq Scan" {
+                self.warnings.push(format!("sequential scan on {relation}"));
+            }
+        }
+        if let Some(cost) = plan["Total Cost"].as_f64() {
+            line += &format!("  (cost: {cost:.2})");
+        }
+        self.lines.push(line);
+        for child in plan["Plans"].as_array().into_iter().flatten() {
+            self.visit(child, depth + 1);
+        }
+    }
+}
+
+impl Explain {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        let query = utils::read_query(self.query)?;
+        let ParametrizedQuery {
+            raw_query, params, ..
+        } = parse_into_postgres(&query, self.placeholders)?;
+
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&self.database.url()?)
+            .await
+            .map_err(Failure::Connection)?;
+
+        // Parameters are bound to NULL, a generic plan keeps the planner from folding them away.
+        let mut conn = pool.acquire().await?;
+        conn.execute("SET plan_cache_mode = force_generic_plan")
+            .await?;
+        conn.execute(format!("PREPARE sql_infer_explain AS {raw_query}").as_str())
+            .await?;
+        let nulls = vec!["NULL"; params.len()].join(", ");
+        let execute = match params.is_empty() {
+            true => "EXECUTE sql_infer_explain".to_string(),
+            false => format!("EXECUTE sql_infer_explain({nulls})"),
+        };
+        let plan: Value = query_scalar(&format!("EXPLAIN (FORMAT JSON) {execute}"))
+            .fetch_one(&mut *conn)
+            .await?;
+        conn.execute("DEALLOCATE sql_infer_explain").await?;
+        let plan = &plan[0]["Plan"];
+
+        println!(
+            "cost: {}..{} (rows: {})",
+            plan["Startup Cost"], plan["Total Cost"], plan["Plan Rows"]
+        );
+        let mut summary = PlanSummary {
+            lines: vec![],
+            warnings: vec![],
+        };
+        summary.visit(plan, 0);
+        println!("plan:");
+        for line in summary.lines {
+            println!("  {line}");
+        }
+        if !summary.warnings.is_empty() {
+            println!("warnings:");
+            for warning in summary.warnings {
+                println!("  {warning}");
+            }
+        }
+        Ok(())
+    }
+}
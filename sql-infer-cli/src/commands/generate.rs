@@ -1,136 +1,1225 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     error::Error,
-    fs::OpenOptions,
-    io::{BufReader, Read},
-    path::PathBuf,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
 };
 
-use clap::Parser;
+use clap::{Parser, ValueHint};
+use futures_util::{StreamExt, stream};
+use similar::TextDiff;
 use sql_infer_core::{
-    SqlInferBuilder,
-    inference::{
-        QueryItem,
-        datatypes::{DecimalPrecision, TextLength},
-        nullability::ColumnNullability,
-    },
+    SqlInfer,
+    inference::{PrefetchedColumns, QueryItem, QueryTypes, SqlType},
+    parser::to_ast,
 };
-use sqlx::postgres::PgPoolOptions;
+use sqlx::{Pool, Postgres, postgres::PgPoolOptions};
+use tracing::Instrument;
 
 use crate::{
-    codegen::{CodeGen, QueryDefinition, json::JsonCodeGen, sqlalchemy_v2::SqlAlchemyV2CodeGen},
-    config::{CodeGenerator, SqlInferConfig, TomlConfig, db_url},
-    utils::{ParametrizedQuery, parse_into_postgres},
+    codegen::{
+        CodeGen, QueryDefinition, Returns, TypeMap, json::JsonCodeGen, pytest::PytestCodeGen,
+        sqlalchemy_v2::SqlAlchemyV2CodeGen, sqlx::SqlxCodeGen,
+    },
+    config::{CodeGenerator, DatabaseArgs, Features, Pass, SelectStarMode, SqlInferConfig},
+    diagnostics::{Diagnostic, DiagnosticsFormat, Severity, Span, report},
+    failure::Failure,
+    manifest::{self, Cache, Manifest},
+    migrations::migrate,
+    query_lint::{QueryLint, SelectStar},
+    temp_db::TempDb,
+    timings::Timings,
+    utils::{
+        Include, ParametrizedQuery, STDIN, include_directives, named_queries, normalize_query,
+        parse_into_postgres, pragma, quote_ident, referenced_tables, select_star,
+    },
 };
 
 #[derive(Parser, Debug, Clone)]
 #[must_use]
 pub struct Generate {
+    #[arg(value_hint = ValueHint::FilePath)]
     config: Option<PathBuf>,
+    /// Query files to generate instead of the configured `path`, `-` reads a query from stdin.
+    #[arg(short, long = "file", num_args = 1.., value_hint = ValueHint::FilePath)]
+    files: Vec<PathBuf>,
+    /// Print a diff of the changes to each target instead of writing them.
+    #[arg(long)]
+    dry_run: bool,
+    /// Print every error and warning encountered during inference in the given format.
+    #[arg(long, value_enum, alias = "output-format")]
+    diagnostics: Option<DiagnosticsFormat>,
+    /// Fail when inference produces any warning.
+    #[arg(long)]
+    strict: bool,
+    /// Leave every target untouched when any query fails inference, overriding `fail-fast` in
+    /// the config.
+    #[arg(long)]
+    fail_fast: bool,
+    /// Number of queries inferred concurrently, overriding `jobs` in the config.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    /// Only parse and validate the queries, without connecting to the database or writing
+    /// anything.
+    #[arg(long, conflicts_with = "dry_run")]
+    no_db: bool,
+    /// Print the time spent in each phase and the slowest queries to stderr.
+    #[arg(long)]
+    timings: bool,
+    /// Only generate the targets of this `[profile.<name>]` entry.
+    #[arg(long)]
+    profile: Option<String>,
+    /// Run against a disposable database with the `migrations` applied, removed afterwards.
+    #[arg(long, conflicts_with = "database_url", conflicts_with = "no_db")]
+    with_temp_db: bool,
+    #[command(flatten)]
+    database: DatabaseArgs,
 }
 
-impl Generate {
-    pub async fn run(self) -> Result<(), Box<dyn Error>> {
-        let config = match self.config {
-            Some(config) => config,
-            None => PathBuf::from("sql-infer.toml"),
-        };
-        let config: TomlConfig = toml::from_slice(&std::fs::read(&config).map_err(|error| {
-            format!(
-                "encountered '{error}' attempting to read {}",
-                config.display()
+/// Output of a generation run, kept in memory until the caller decides what to do with it.
+pub struct Generated {
+    /// Every target along with the code generator holding its queries.
+    pub targets: Vec<(PathBuf, Box<dyn CodeGen>)>,
+    /// Names of the queries that failed inference and are missing from the output.
+    pub failed: Vec<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub manifest: Option<Manifest>,
+    pub timings: Timings,
+    /// `SELECT *` to replace in query files, with `select-star = "expand"`.
+    pub expansions: Vec<Expansion>,
+    /// Columns typed as nullable as their nullability is unknown, each explained by a note.
+    pub unknown_nullability: usize,
+}
+
+impl Generated {
+    /// Every file along with its content, including stubs.
+    pub fn files(&self) -> Result<Vec<(PathBuf, String)>, Box<dyn Error>> {
+        let mut files = vec![];
+        for (target, codegen) in &self.targets {
+            if let Some(directory) = codegen.files()? {
+                for (name, content) in directory {
+                    files.push((target.join(name), content));
+                }
+                continue;
+            }
+            files.push((target.clone(), codegen.finalize()?));
+            if let Some(stub) = codegen.finalize_stub()? {
+                files.push((target.with_extension("pyi"), stub));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Write every file, including stubs, streaming the code to disk.
+    pub fn write(&self) -> Result<(), Box<dyn Error>> {
+        for (target, codegen) in &self.targets {
+            if let Some(directory) = codegen.files()? {
+                std::fs::create_dir_all(target)?;
+                for (name, content) in directory {
+                    std::fs::write(target.join(name), content)?;
+                }
+                continue;
+            }
+            let mut out = BufWriter::new(File::create(target)?);
+            codegen.write(&mut out)?;
+            out.flush()?;
+            if codegen.has_stub() {
+                let mut out = BufWriter::new(File::create(target.with_extension("pyi"))?);
+                codegen.write_stub(&mut out)?;
+                out.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Error for the queries that failed inference, or when `strict`, for any warning.
+    pub fn status(&self, strict: bool) -> Result<(), Box<dyn Error>> {
+        if !self.failed.is_empty() {
+            Err(Failure::Inference {
+                queries: self.failed.clone(),
+            })?;
+        }
+        let warnings = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+            .count();
+        if strict && warnings > 0 {
+            Err(Failure::Warnings { count: warnings })?;
+        }
+        Ok(())
+    }
+}
+
+pub fn codegen(mode: &CodeGenerator, type_map: &TypeMap) -> Box<dyn CodeGen> {
+    match mode {
+        CodeGenerator::Json => Box::new(JsonCodeGen::default()),
+        CodeGenerator::SqlAlchemyV2 {
+            r#async,
+            argument_mode,
+            type_gen,
+            generic_param_types,
+            stub,
+            datetime_aliases,
+            json,
+            numeric,
+            numeric_columns,
+        } => Box::new(
+            SqlAlchemyV2CodeGen::new(
+                *r#async,
+                *argument_mode,
+                *type_gen,
+                *generic_param_types,
+                *stub,
+                *datetime_aliases,
+                *json,
             )
-        })?)?;
-        let config: SqlInferConfig = SqlInferConfig::from_toml_config(config)?;
-
-        let mut sql_infer = SqlInferBuilder::default();
-        if config.experimental_features.nullability() {
-            sql_infer.add_information_schema_pass(ColumnNullability);
-        }
-        if config.experimental_features.decimal_precision() {
-            sql_infer.add_information_schema_pass(DecimalPrecision);
-        }
-        if config.experimental_features.text_length() {
-            sql_infer.add_information_schema_pass(TextLength);
-        }
-        let sql_infer = sql_infer.build();
-
-        let mut codegen: Box<dyn CodeGen> = match config.mode {
-            CodeGenerator::Json => Box::new(JsonCodeGen::default()),
-            CodeGenerator::SqlAlchemyV2 {
-                r#async,
-                argument_mode,
-                type_gen,
-                generic_param_types,
-            } => Box::new(SqlAlchemyV2CodeGen::new(
-                r#async,
-                argument_mode,
-                type_gen,
-                generic_param_types,
-            )),
-        };
+            .with_numeric(*numeric, numeric_columns.clone())
+            .with_type_map(type_map.clone()),
+        ),
+        CodeGenerator::Pytest { module, r#async } => {
+            Box::new(PytestCodeGen::new(module.clone(), *r#async))
+        }
+        CodeGenerator::Sqlx => Box::new(SqlxCodeGen::default()),
+    }
+}
 
-        let pool = PgPoolOptions::new()
-            .max_connections(1)
-            .connect(&db_url()?)
-            .await?;
+const DISABLED_PASS: &str = "-- sql-infer: no-";
 
-        let mut query = String::new();
-        let mut files = HashSet::<String>::new();
+/// A query file found in one of the configured source directories.
+#[derive(Debug, Clone)]
+pub struct QueryFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub query: String,
+    /// Byte offset of `query` within the file at `path`, unknown when it holds included
+    /// fragments or is read from stdin.
+    pub offset: Option<usize>,
+    /// Given by the `-- name:` comment of the query, for files holding several, or its
+    /// `-- sql-infer: fetch=` setting.
+    pub returns: Option<Returns>,
+    /// Given by the `-- sql-infer: async=` setting of the query.
+    pub r#async: Option<bool>,
+    /// Passes the query opts out of with `-- sql-infer: no-<pass>`.
+    pub disabled_passes: BTreeSet<Pass>,
+}
 
-        for directory in config.source {
-            for file in std::fs::read_dir(directory)? {
-                let file = file?;
-                if !file.metadata()?.is_file() {
-                    continue;
-                }
-                let file_path = file.path();
-                let Some(stem) = file_path.file_stem() else {
-                    tracing::info!("Skipping {file_path:?} as the filename is not valid.");
-                    continue;
-                };
-                query.clear();
-                let file_name = stem.to_string_lossy().to_string();
+impl QueryFile {
+    /// Features covering the query, less the passes it opts out of.
+    pub fn features(&self, config: &SqlInferConfig) -> Features {
+        self.disabled_passes
+            .iter()
+            .fold(config.features(&self.path), |features, pass| {
+                features.without(*pass)
+            })
+    }
+}
+
+pub fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Replace the `-- include: <path>` directives of `query` with the file they name, relative to
+/// `directory`. Included files may include others, each one read is added to `included`.
+fn resolve_includes(
+    query: &str,
+    directory: &Path,
+    stack: &mut Vec<PathBuf>,
+    included: &mut BTreeSet<PathBuf>,
+) -> Result<String, Box<dyn Error>> {
+    let mut resolved = String::new();
+    let mut head = 0;
+    for Include { range, path } in include_directives(query)? {
+        let path = directory.join(&path);
+        let canonical = path
+            .canonicalize()
+            .map_err(|err| format!("could not include {}: {err}", path.display()))?;
+        if stack.contains(&canonical) {
+            Err(format!("{} includes itself", path.display()))?;
+        }
+        let fragment = std::fs::read_to_string(&canonical)?;
+        stack.push(canonical.clone());
+        let fragment = resolve_includes(
+            &fragment,
+            canonical.parent().unwrap_or(directory),
+            stack,
+            included,
+        )?;
+        stack.pop();
+        included.insert(canonical);
+        resolved += &query[head..range.start];
+        resolved += fragment.trim_end().trim_end_matches(';');
+        if query[range.clone()].ends_with('\n') {
+            resolved.push('\n');
+        }
+        head = range.end;
+    }
+    resolved += &query[head..];
+    Ok(resolved)
+}
+
+/// Split the contents of a file on its `-- name:` comments, each query is named by `prefix`
+/// followed by its name, or `name` for a file without any. The `-- sql-infer:` settings of
+/// each query are applied. `verbatim` tells whether `query` is the file as written, so the
+/// queries can be located in it.
+fn split_queries(
+    prefix: &str,
+    name: String,
+    path: PathBuf,
+    query: String,
+    verbatim: bool,
+) -> Result<Vec<QueryFile>, Box<dyn Error>> {
+    let in_file = |err: Box<dyn Error>| format!("{}: {err}", path.display());
+    let named = named_queries(&query).map_err(in_file)?;
+    let files = match named.is_empty() {
+        true => vec![QueryFile {
+            name,
+            path: path.clone(),
+            query,
+            offset: verbatim.then_some(0),
+            returns: None,
+            r#async: None,
+            disabled_passes: BTreeSet::new(),
+        }],
+        false => named
+            .into_iter()
+            .map(|named| QueryFile {
+                name: format!("{prefix}{}", named.name),
+                path: path.clone(),
+                query: named.query,
+                offset: verbatim.then_some(named.offset),
+                returns: named.returns,
+                r#async: None,
+                disabled_passes: BTreeSet::new(),
+            })
+            .collect(),
+    };
+    files
+        .into_iter()
+        .map(|mut file| {
+            let pragma = pragma(&file.query).map_err(in_file)?;
+            if let Some(name) = pragma.name {
+                file.name = format!("{prefix}{name}");
+            }
+            file.returns = pragma.fetch.or(file.returns);
+            file.r#async = pragma.r#async;
+            file.disabled_passes = pragma.disabled_passes;
+            Ok(file)
+        })
+        .collect()
+}
+
+/// Read the queries at `path`, a file with a single query is named by its file stem prefixed
+/// by `prefix`, queries following a `-- name:` comment by that prefix and name.
+fn read_query_file(
+    path: PathBuf,
+    prefix: &str,
+    included: &mut BTreeSet<PathBuf>,
+) -> Result<Vec<QueryFile>, Box<dyn Error>> {
+    let Some(stem) = path.file_stem() else {
+        tracing::info!("Skipping {path:?} as the filename is not valid.");
+        return Ok(vec![]);
+    };
+    let name = format!("{prefix}{}", stem.to_string_lossy());
+
+    let mut query = String::new();
+    let file = OpenOptions::new().read(true).open(&path)?;
+    let mut reader = BufReader::new(file);
+    reader.read_to_string(&mut query)?;
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let mut stack = vec![path.canonicalize()?];
+    let resolved = resolve_includes(&query, directory, &mut stack, included)
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    let verbatim = resolved == query;
+    split_queries(prefix, name, path, resolved, verbatim)
+}
+
+/// Collect the files in `directory` along with the name prefix derived from their parent
+/// directories relative to the source directory.
+fn walk(
+    directory: &Path,
+    prefix: &str,
+    recursive: bool,
+    paths: &mut Vec<(PathBuf, String)>,
+) -> Result<(), Box<dyn Error>> {
+    for file in std::fs::read_dir(directory)? {
+        let path = file?.path();
+        if recursive && path.is_dir() {
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+            let prefix = format!("{prefix}{}_", name.to_string_lossy());
+            walk(&path, &prefix, recursive, paths)?;
+            continue;
+        }
+        paths.push((path, prefix.to_string()));
+    }
+    Ok(())
+}
 
-                let file = OpenOptions::new().read(true).open(file_path)?;
-                let mut reader = BufReader::new(file);
-                reader.read_to_string(&mut query)?;
+/// Find every query file in the configured sources, which may be directories, files or glob
+/// patterns. Files are sorted by path within each source.
+pub fn discover(config: &SqlInferConfig) -> Result<Vec<QueryFile>, Box<dyn Error>> {
+    let mut queries = vec![];
+    let mut included = BTreeSet::new();
+    for source in &config.source {
+        if source.as_os_str() == STDIN {
+            let query = std::io::read_to_string(std::io::stdin())?;
+            let query = resolve_includes(&query, Path::new("."), &mut vec![], &mut included)?;
+            queries.extend(split_queries(
+                "",
+                "stdin".to_string(),
+                source.clone(),
+                query,
+                false,
+            )?);
+            continue;
+        }
+        let mut paths = vec![];
+        if is_glob(source) {
+            for path in glob::glob(&source.to_string_lossy())? {
+                paths.push((path?, String::new()));
+            }
+        } else if source.is_file() {
+            paths.push((source.clone(), String::new()));
+        } else {
+            walk(source, "", config.recursive, &mut paths)?;
+        }
+        paths.sort();
+        for (path, prefix) in paths {
+            if !path.metadata()?.is_file() {
+                continue;
+            }
+            queries.extend(read_query_file(path, &prefix, &mut included)?);
+        }
+    }
+    // Fragments within a source directory are not queries of their own.
+    queries.retain(|file| {
+        file.path
+            .canonicalize()
+            .ok()
+            .is_none_or(|path| !included.contains(&path))
+    });
+    Ok(queries)
+}
+
+pub struct Inferred {
+    pub queries: Vec<(QueryFile, QueryDefinition)>,
+    /// Names of the queries that failed inference.
+    pub failed: Vec<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Manifest of the successfully inferred queries, when one is configured.
+    pub manifest: Option<Manifest>,
+    pub timings: Timings,
+    pub expansions: Vec<Expansion>,
+    /// Columns typed as nullable as their nullability is unknown, each explained by a note.
+    pub unknown_nullability: usize,
+}
+
+/// A `SELECT *` query along with the columns to replace the `*` by.
+pub struct Expansion {
+    pub path: PathBuf,
+    /// Byte range of the `*` within the file.
+    pub star: Range<usize>,
+    pub columns: Vec<String>,
+}
+
+/// Whether any `SELECT` of the query selects `*`.
+fn selects_star(raw_query: &str) -> bool {
+    to_ast(raw_query).is_ok_and(|statements| {
+        statements
+            .iter()
+            .any(|statement| !SelectStar.lint(statement, None).is_empty())
+    })
+}
+
+/// Replace the `*` of each query file by its columns, quoted where Postgres requires it.
+fn expand_select_star(expansions: &[Expansion]) -> Result<(), Box<dyn Error>> {
+    let mut by_file = BTreeMap::<_, Vec<_>>::new();
+    for expansion in expansions {
+        by_file.entry(&expansion.path).or_default().push(expansion);
+    }
+    for (path, mut expansions) in by_file {
+        let mut content = std::fs::read_to_string(path)?;
+        // Later queries first, so the offsets of earlier ones still hold.
+        expansions.sort_by_key(|expansion| std::cmp::Reverse(expansion.star.start));
+        for expansion in expansions {
+            let star = expansion.star.clone();
+            if content.get(star.clone()) != Some("*") {
+                Err(format!(
+                    "{} changed while generating, `SELECT *` was not expanded",
+                    path.display()
+                ))?;
+            }
+            let columns = expansion
+                .columns
+                .iter()
+                .map(|column| quote_ident(column))
+                .collect::<Vec<_>>();
+            content.replace_range(star, &columns.join(", "));
+        }
+        std::fs::write(path, content)?;
+        tracing::info!("Expanded `SELECT *` in {}", path.display());
+    }
+    Ok(())
+}
+
+/// Key of the queries inferred once, identical up to formatting. Declared parameter types are
+/// part of it as they can change the types Postgres infers.
+fn dedup_key(query: &ParametrizedQuery, features: &Features) -> String {
+    let normalized = normalize_query(&query.raw_query);
+    manifest::query_hash(&format!("{normalized}\n{:?}", query.param_types), features)
+}
+
+async fn connect(config: &SqlInferConfig) -> Result<Pool<Postgres>, Box<dyn Error>> {
+    let pool = PgPoolOptions::new()
+        .max_connections(config.jobs as u32)
+        .connect(&config.db_url()?)
+        .await
+        .map_err(Failure::Connection)?;
+    Ok(pool)
+}
+
+/// Run inference over every configured query.
+pub async fn infer(config: &SqlInferConfig) -> Result<Inferred, Box<dyn Error>> {
+    let mut files = HashSet::<String>::new();
+    let mut queries = vec![];
+    let mut failed = vec![];
+    let mut diagnostics = vec![];
+    let mut timings = Timings::default();
+    let mut expansions = vec![];
+    let mut unknown_nullability = 0;
+
+    let start = Instant::now();
+    let mut parsed = vec![];
+    for file in discover(config)? {
+        match parse_into_postgres(&file.query, config.placeholders(&file.path)) {
+            Ok(query)
+                if config.select_star == SelectStarMode::Reject
+                    && selects_star(&query.raw_query) =>
+            {
+                tracing::error!("{} selects *", file.name);
+                let star = select_star(&file.query)?;
+                diagnostics.push(Diagnostic {
+                    file: file.path.clone(),
+                    query: file.name.clone(),
+                    span: star.map(|star| {
+                        Span::at(&file.query, file.query[..star.start].chars().count())
+                    }),
+                    severity: Severity::Error,
+                    message: "`SELECT *` output changes whenever the table does, list the columns \
+                              instead or expand them with `select-star = \"expand\"`"
+                        .to_string(),
+                });
+                failed.push(file.name);
+            }
+            Ok(query) => parsed.push((file, query)),
+            Err(err) => {
+                tracing::error!("Parsing {} failed\n {err}", file.name);
+                diagnostics.push(Diagnostic {
+                    file: file.path.clone(),
+                    query: file.name.clone(),
+                    span: None,
+                    severity: Severity::Error,
+                    message: err.to_string(),
+                });
+                failed.push(file.name);
+            }
+        }
+    }
+    timings.parse = start.elapsed();
+
+    let start = Instant::now();
+    let previous = match &config.manifest {
+        Some(path) => Some(Manifest::load(path)?),
+        None if config.changed.is_some() => Err(
+            "--changed takes the types of unchanged queries from the `manifest`, which is not set",
+        )?,
+        None => None,
+    };
+    // Set for queries of files left out of `--changed`, which are not inferred again.
+    let unchanged = parsed
+        .iter()
+        .map(|(file, _)| match &config.changed {
+            Some(changed) => !file
+                .path
+                .canonicalize()
+                .is_ok_and(|path| changed.contains(&path)),
+            None => false,
+        })
+        .collect::<Vec<_>>();
+    let cache = config
+        .cache
+        .as_ref()
+        .map(|cache| Cache::new(&cache.directory));
+    let schema_files = config
+        .cache
+        .as_ref()
+        .filter(|cache| !cache.schema_files.is_empty())
+        .map(|cache| manifest::files_hash(&cache.schema_files))
+        .transpose()?;
+
+    // The database is only connected to once a query or fingerprint needs it.
+    let mut pool = None;
+    let hashes = match (&previous, &cache, schema_files) {
+        (None, None, _) => vec![None; parsed.len()],
+        _ if unchanged.iter().all(|unchanged| *unchanged) => vec![None; parsed.len()],
+        (_, _, Some(schema_hash)) => parsed
+            .iter()
+            .map(|(file, _)| {
+                let query_hash = manifest::query_hash(&file.query, &file.features(config));
+                Some((query_hash, schema_hash.clone()))
+            })
+            .collect(),
+        _ => manifest_hashes(config, pool.insert(connect(config).await?), &parsed).await?,
+    };
+    let cached = parsed
+        .iter()
+        .zip(&hashes)
+        .zip(&unchanged)
+        .map(|(((file, _), hashes), unchanged)| {
+            if *unchanged
+                && let Some(query_types) = previous
+                    .as_ref()
+                    .and_then(|previous| previous.types(&file.name))
+            {
+                return Some(query_types.clone());
+            }
+            let (query_hash, schema_hash) = hashes.as_ref()?;
+            match previous
+                .as_ref()
+                .and_then(|previous| previous.get(&file.name, query_hash, schema_hash))
+            {
+                Some(query_types) => Some(query_types.clone()),
+                None => cache.as_ref()?.get(query_hash, schema_hash),
+            }
+        })
+        .collect::<Vec<_>>();
+    if pool.is_none() && cached.iter().any(Option::is_none) {
+        pool = Some(connect(config).await?);
+    }
+    let prefetched = match &pool {
+        Some(pool) if config.prefetch_metadata => Some(Arc::new(
+            PrefetchedColumns::load(pool, &config.schema.schemas).await?,
+        )),
+        _ => None,
+    };
+    timings.setup = start.elapsed();
+    let mut manifest = previous.as_ref().map(|_| Manifest::default());
 
-                let ParametrizedQuery { raw_query, params } = parse_into_postgres(&query)?;
+    // Queries identical up to formatting are only prepared once, with the result shared by each.
+    let mut first = HashMap::<String, usize>::new();
+    let mut sources = vec![];
+    for (index, ((file, query), cached)) in parsed.iter().zip(&cached).enumerate() {
+        if cached.is_some() {
+            sources.push(index);
+            continue;
+        }
+        let key = dedup_key(query, &file.features(config));
+        let source = *first.entry(key).or_insert(index);
+        if source != index {
+            let message = format!(
+                "identical to {}, its types are inferred once",
+                parsed[source].0.name
+            );
+            tracing::info!("{}: {message}", file.name);
+            diagnostics.push(Diagnostic {
+                file: file.path.clone(),
+                query: file.name.clone(),
+                span: None,
+                severity: Severity::Note,
+                message,
+            });
+        }
+        sources.push(source);
+    }
 
-                let check_result = sql_infer.infer_types(&pool, &raw_query).await;
-                let query_types = match check_result {
-                    Ok(query_types) => query_types,
-                    Err(err) => {
-                        tracing::error!("Check for {file_name} failed\n {err}");
-                        continue;
+    // Up to `jobs` queries are prepared at once.
+    let pool = &pool;
+    let prefetched = &prefetched;
+    let results = stream::iter(
+        parsed
+            .iter()
+            .zip(cached)
+            .enumerate()
+            .filter(|(index, _)| sources[*index] == *index),
+    )
+    .map(|(index, ((file, query), cached))| async move {
+        let check_result = match (cached, pool) {
+            (Some(query_types), _) => {
+                tracing::debug!("Reusing the previously inferred types of {}", file.name);
+                Ok((query_types, None, vec![]))
+            }
+            (None, Some(pool)) => {
+                let mut sql_infer = file.features(config).sql_infer_builder();
+                if let Some(prefetched) = prefetched {
+                    sql_infer.prefetched_columns(prefetched.clone());
+                }
+                let sql_infer = sql_infer.build();
+                async {
+                    let (query_types, timings) = sql_infer
+                        .infer_types_with_timings(
+                            pool,
+                            &query.raw_query,
+                            &query.param_types,
+                            &query.params,
+                        )
+                        .await?;
+                    if let Some(without_blocks) = &query.without_blocks {
+                        check_without_blocks(&sql_infer, pool, without_blocks, &query_types)
+                            .await?;
                     }
-                };
-                tracing::info!("Check for {file_name} successful!");
-                if files.contains(&file_name) {
-                    tracing::error!("{file_name} already exists. Skipping...");
-                    continue;
+                    let conflicts = repeated_param_conflicts(&sql_infer, pool, query).await;
+                    Ok::<_, Box<dyn Error>>((query_types, Some(timings), conflicts))
                 }
-                let query = QueryDefinition {
-                    query: query.clone(),
-                    inputs: query_types
-                        .input
-                        .into_iter()
-                        .zip(params)
-                        .map(|(item, param_name)| QueryItem {
-                            name: param_name,
-                            sql_type: item.sql_type,
-                            nullable: item.nullable,
-                        })
-                        .collect(),
-                    outputs: query_types.output,
+                .instrument(tracing::warn_span!("infer", query = %file.name))
+                .await
+            }
+            (None, None) => {
+                unreachable!("the database is connected to when a query is not cached")
+            }
+        };
+        (index, check_result)
+    })
+    .buffered(config.jobs)
+    .collect::<HashMap<_, _>>()
+    .await;
+    // Waits for released connections, which would otherwise outlive a `--with-temp-db` database.
+    if let Some(pool) = pool {
+        pool.close().await;
+    }
+    for (index, result) in &results {
+        if let Ok((_, Some(query_timings), _)) = result {
+            timings.add_query(&parsed[*index].0.name, query_timings);
+        }
+    }
+
+    for (
+        (
+            (
+                file,
+                ParametrizedQuery {
+                    query: named_query,
+                    raw_query,
+                    params,
+                    param_defaults,
+                    expanding,
+                    blocks,
+                    ..
+                },
+            ),
+            hashes,
+        ),
+        source,
+    ) in parsed.into_iter().zip(hashes).zip(sources)
+    {
+        let check_result = &results[&source];
+        let file_name = &file.name;
+        let query_types = match check_result {
+            // Conflicts are named after the parameters of each query sharing the result.
+            Ok((query_types, _, conflicts)) if !conflicts.is_empty() => {
+                let mut query_types = query_types.clone();
+                let mut warnings = query_types.warnings.into_vec();
+                warnings.extend(conflict_warnings(&params, conflicts));
+                query_types.warnings = warnings.into_boxed_slice();
+                query_types
+            }
+            Ok((query_types, _, _)) => query_types.clone(),
+            Err(err) => {
+                tracing::error!("Check for {file_name} failed\n {err}");
+                diagnostics.push(Diagnostic {
+                    file: file.path.clone(),
+                    query: file.name.clone(),
+                    span: Span::from_error(err.as_ref(), &file.query, &raw_query),
+                    severity: Severity::Error,
+                    message: err.to_string(),
+                });
+                failed.push(file.name);
+                continue;
+            }
+        };
+        tracing::info!("Check for {file_name} successful!");
+        if files.contains(file_name) {
+            tracing::error!("{file_name} already exists. Skipping...");
+            diagnostics.push(Diagnostic {
+                file: file.path.clone(),
+                query: file.name.clone(),
+                span: None,
+                severity: Severity::Error,
+                message: format!("{file_name} already exists"),
+            });
+            continue;
+        }
+        for warning in &query_types.warnings {
+            diagnostics.push(Diagnostic {
+                file: file.path.clone(),
+                query: file.name.clone(),
+                span: None,
+                severity: Severity::Warning,
+                message: warning.clone(),
+            });
+        }
+        for pass in &file.disabled_passes {
+            let message = format!("the {pass} pass is disabled by `{DISABLED_PASS}{pass}`");
+            tracing::info!("{file_name}: {message}");
+            diagnostics.push(Diagnostic {
+                file: file.path.clone(),
+                query: file.name.clone(),
+                span: None,
+                severity: Severity::Note,
+                message,
+            });
+        }
+        unknown_nullability += query_types.notes.len();
+        for note in &query_types.notes {
+            tracing::info!("{file_name}: {note}");
+            diagnostics.push(Diagnostic {
+                file: file.path.clone(),
+                query: file.name.clone(),
+                span: None,
+                severity: Severity::Note,
+                message: note.clone(),
+            });
+        }
+        files.insert(file_name.clone());
+        if config.select_star == SelectStarMode::Expand && selects_star(&raw_query) {
+            let message = match (select_star(&file.query)?, file.offset) {
+                (Some(star), Some(offset)) => {
+                    expansions.push(Expansion {
+                        path: file.path.clone(),
+                        star: offset + star.start..offset + star.end,
+                        columns: query_types
+                            .output
+                            .iter()
+                            .map(|item| item.name.clone())
+                            .collect(),
+                    });
+                    None
+                }
+                (Some(_), None) => Some(
+                    "`SELECT *` can not be expanded in a query holding included fragments or \
+                     read from stdin, list the columns instead",
+                ),
+                (None, _) => {
+                    Some("only `SELECT * FROM ...` can be expanded, list the columns instead")
+                }
+            };
+            if let Some(message) = message {
+                tracing::warn!("{file_name}: {message}");
+                diagnostics.push(Diagnostic {
+                    file: file.path.clone(),
+                    query: file.name.clone(),
+                    span: None,
+                    severity: Severity::Warning,
+                    message: message.to_string(),
+                });
+            }
+        }
+        if let Some(cache) = &cache
+            && let Some((query_hash, schema_hash)) = &hashes
+            && let Err(error) = cache.insert(query_hash, schema_hash, &query_types)
+        {
+            tracing::warn!("Could not cache the types of {file_name}: {error}");
+        }
+        if let Some(manifest) = &mut manifest
+            && let Some((query_hash, schema_hash)) = hashes
+        {
+            manifest.insert(
+                file_name.clone(),
+                query_hash,
+                schema_hash,
+                query_types.clone(),
+            );
+        }
+        let defaults = params
+            .iter()
+            .zip(param_defaults)
+            .filter_map(|(name, default)| Some((name.clone(), default?)))
+            .collect();
+        let query = QueryDefinition {
+            query: named_query,
+            // Queries identical up to formatting share their types, so the inputs are named
+            // after the parameters of this one.
+            inputs: query_types
+                .input
+                .into_iter()
+                .zip(params)
+                .map(|(item, param_name)| QueryItem {
+                    name: param_name,
+                    sql_type: item.sql_type,
+                    nullable: item.nullable,
+                })
+                .collect(),
+            outputs: query_types.output,
+            defaults,
+            expanding,
+            blocks,
+            returns: file.returns,
+            r#async: file.r#async,
+            disabled_passes: file.disabled_passes.clone(),
+            rows_affected: query_types.rows_affected,
+            kind: query_types.kind,
+            copy_from: query_types.copy_from,
+        };
+        queries.push((file, query));
+    }
+    Ok(Inferred {
+        queries,
+        failed,
+        diagnostics,
+        manifest,
+        timings,
+        expansions,
+        unknown_nullability,
+    })
+}
+
+/// Check that the query still works with its optional blocks left out, and returns the same
+/// columns as `query_types` then.
+async fn check_without_blocks(
+    sql_infer: &SqlInfer,
+    pool: &Pool<Postgres>,
+    without_blocks: &ParametrizedQuery,
+    query_types: &QueryTypes,
+) -> Result<(), Box<dyn Error>> {
+    let output = sql_infer
+        .infer_types_with_timings(
+            pool,
+            &without_blocks.raw_query,
+            &without_blocks.param_types,
+            &without_blocks.params,
+        )
+        .await
+        .map_err(|err| format!("the query without its optional blocks fails: {err}"))?
+        .0
+        .output;
+    let types = |items: &[QueryItem]| {
+        items
+            .iter()
+            .map(|item| (item.name.clone(), item.sql_type.clone()))
+            .collect::<Vec<_>>()
+    };
+    if types(&output) != types(&query_types.output) {
+        Err("the query returns different columns without its optional blocks")?;
+    }
+    Ok(())
+}
+
+/// Index and types of the parameters used several times in `query` whose uses Postgres infers
+/// different types for, parameters with a declared type are not checked. Indexes rather than
+/// names are returned as queries identical up to formatting share the result.
+async fn repeated_param_conflicts(
+    sql_infer: &SqlInfer,
+    pool: &Pool<Postgres>,
+    query: &ParametrizedQuery,
+) -> Vec<(usize, Vec<SqlType>)> {
+    let Some(repeated) = &query.repeated else {
+        return vec![];
+    };
+    let uses = match sql_infer.infer_types(pool, &repeated.raw_query).await {
+        Ok(query_types) => query_types.input,
+        Err(err) => {
+            // e.g. a use that is only compared to null has no type on its own.
+            tracing::debug!("Could not type each use of the parameters separately\n {err}");
+            return vec![];
+        }
+    };
+    let mut conflicts = vec![];
+    for index in 0..query.params.len() {
+        if query.param_types.get(index).is_some_and(Option::is_some) {
+            continue;
+        }
+        let mut types = vec![];
+        for (item, _) in uses
+            .iter()
+            .zip(&repeated.uses)
+            .filter(|(_, param)| **param == index)
+        {
+            if !types.contains(&item.sql_type) {
+                types.push(item.sql_type.clone());
+            }
+        }
+        if types.len() > 1 {
+            conflicts.push((index, types));
+        }
+    }
+    conflicts
+}
+
+/// Warnings for the `conflicts` of [`repeated_param_conflicts`], naming the parameters `params`.
+fn conflict_warnings(params: &[String], conflicts: &[(usize, Vec<SqlType>)]) -> Vec<String> {
+    conflicts
+        .iter()
+        .map(|(index, types)| {
+            let name = &params[*index];
+            format!(
+                "parameter {name} is used as {}, declare its type with `-- param {name}: <type>`",
+                types
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            )
+        })
+        .collect()
+}
+
+/// Query and schema hash of each query, `None` for queries that can not be parsed and so are
+/// always inferred.
+async fn manifest_hashes(
+    config: &SqlInferConfig,
+    pool: &Pool<Postgres>,
+    parsed: &[(QueryFile, ParametrizedQuery)],
+) -> Result<Vec<Option<(String, String)>>, Box<dyn Error>> {
+    let tables = parsed
+        .iter()
+        .map(|(_, parsed)| {
+            to_ast(&parsed.raw_query)
+                .ok()
+                .map(|ast| referenced_tables(&ast))
+        })
+        .collect::<Vec<_>>();
+    let all_tables = tables.iter().flatten().flatten().cloned().collect();
+    let fingerprints = manifest::table_fingerprints(pool, &all_tables).await?;
+    Ok(parsed
+        .iter()
+        .zip(tables)
+        .map(|((file, _), tables)| {
+            let query_hash = manifest::query_hash(&file.query, &file.features(config));
+            Some((query_hash, manifest::schema_hash(&tables?, &fingerprints)))
+        })
+        .collect())
+}
+
+/// Parse every configured query without preparing it, catching syntax errors and duplicate
+/// query names without a database.
+pub fn validate(config: &SqlInferConfig) -> Result<Generated, Box<dyn Error>> {
+    let mut names = HashSet::<String>::new();
+    let mut failed = vec![];
+    let mut diagnostics = vec![];
+    for file in discover(config)? {
+        let result = parse_into_postgres(&file.query, config.placeholders(&file.path))
+            .and_then(|parsed| to_ast(&parsed.raw_query));
+        let message = match result {
+            Err(err) => err.to_string(),
+            Ok(_) if names.contains(&file.name) => format!("{} already exists", file.name),
+            Ok(_) => {
+                tracing::info!("Parsed {} successfully!", file.name);
+                names.insert(file.name);
+                continue;
+            }
+        };
+        tracing::error!("Validation of {} failed\n {message}", file.name);
+        diagnostics.push(Diagnostic {
+            file: file.path,
+            query: file.name.clone(),
+            span: None,
+            severity: Severity::Error,
+            message,
+        });
+        failed.push(file.name);
+    }
+    Ok(Generated {
+        targets: vec![],
+        failed,
+        diagnostics,
+        manifest: None,
+        timings: Timings::default(),
+        expansions: vec![],
+        unknown_nullability: 0,
+    })
+}
+
+/// Run inference over every configured query and generate code without touching the target.
+pub async fn generate(config: &SqlInferConfig) -> Result<Generated, Box<dyn Error>> {
+    let Inferred {
+        queries,
+        failed,
+        diagnostics,
+        manifest,
+        mut timings,
+        expansions,
+        unknown_nullability,
+    } = infer(config).await?;
+    let start = Instant::now();
+    let mut targets = vec![];
+    for target in config.all_targets() {
+        let mut codegen = codegen(&target.mode, &config.type_map);
+        for (file, query) in &queries {
+            if !config
+                .targets(&file.path)
+                .iter()
+                .any(|query_target| query_target.target == target.target)
+            {
+                continue;
+            }
+            codegen.push(&file.name, query.clone())?;
+        }
+        targets.push((target.target.clone(), codegen));
+    }
+    timings.codegen = start.elapsed();
+    Ok(Generated {
+        targets,
+        failed,
+        diagnostics,
+        manifest,
+        timings,
+        expansions,
+        unknown_nullability,
+    })
+}
+
+/// Write the targets of `generated`, expand `select *` as configured and save the manifest.
+pub async fn write(config: &SqlInferConfig, generated: &Generated) -> Result<(), Box<dyn Error>> {
+    generated.write()?;
+    expand_select_star(&generated.expansions)?;
+    if let Some((path, manifest)) = config.manifest.as_ref().zip(generated.manifest.as_ref()) {
+        manifest.save(path)?;
+    }
+    Ok(())
+}
+
+impl Generate {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        let mut config = SqlInferConfig::load(self.config)?;
+        config.select_profile(self.profile)?;
+        config.select_database(self.database)?;
+        // Kept until the end of the run, dropping it removes the database.
+        let temp_db = match self.with_temp_db {
+            true => Some(TempDb::start(&config.temp_db).await?),
+            false => None,
+        };
+        if let Some(temp_db) = &temp_db {
+            config.database_url = Some(temp_db.url().to_string());
+        }
+        if !self.files.is_empty() {
+            config.source = self.files;
+        }
+        if let Some(jobs) = self.jobs {
+            config.jobs = jobs.max(1);
+        }
+        config.fail_fast |= self.fail_fast;
+        let start = Instant::now();
+        let mut generated = if self.no_db {
+            validate(&config)?
+        } else {
+            migrate(&config).await?;
+            generate(&config).await?
+        };
+        if let Some(format) = self.diagnostics {
+            report(&generated.diagnostics, format)?;
+        }
+        let output = Instant::now();
+        if config.fail_fast && !generated.failed.is_empty() {
+            tracing::error!("Not writing any target as inference failed");
+            return generated.status(self.strict);
+        }
+        if self.dry_run {
+            for (path, code) in generated.files()? {
+                let current = match std::fs::read_to_string(&path) {
+                    Ok(current) => current,
+                    Err(error) if error.kind() == ErrorKind::NotFound => String::new(),
+                    Err(error) => Err(error)?,
                 };
-                codegen.push(&file_name, query)?;
-                files.insert(file_name);
+                let name = path.to_string_lossy();
+                print!(
+                    "{}",
+                    TextDiff::from_lines(&current, &code)
+                        .unified_diff()
+                        .header(&name, &name)
+                );
             }
+        } else {
+            write(&config, &generated).await?;
         }
-        let code = codegen.finalize()?;
-        std::fs::write(config.target, code)?;
-        Ok(())
+        generated.timings.write = output.elapsed();
+        if self.timings {
+            eprint!("{}", generated.timings.report(start.elapsed()));
+        }
+        let unknown = generated.unknown_nullability;
+        if unknown > 0 {
+            tracing::warn!(
+                "{unknown} column(s) of unknown nullability are typed as nullable, run with -v or \
+                 --diagnostics json to see why"
+            );
+        }
+        generated.status(self.strict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use sql_infer_core::inference::SqlType;
+
+    use crate::{
+        commands::generate::{
+            Expansion, conflict_warnings, dedup_key, expand_select_star, read_query_file,
+        },
+        config::{Features, PlaceholderStyle},
+        utils::{parse_into_postgres, select_star},
+    };
+
+    #[test]
+    fn declared_parameter_types_are_not_shared() {
+        let key = |query: &str| {
+            let parsed = parse_into_postgres(query, PlaceholderStyle::Named).unwrap();
+            dedup_key(&parsed, &Features::default())
+        };
+        let int4 = key("-- param id: int4\nselect :id as id");
+        assert_eq!(int4, key("-- param id: int4\nselect :id  as id;"));
+        assert_ne!(int4, key("-- param id: int8\nselect :id as id"));
+        assert_ne!(int4, key("select :id as id"));
+    }
+
+    #[test]
+    fn select_star_is_expanded_where_the_query_is() {
+        let path =
+            std::env::temp_dir().join(format!("sql-infer-{}-expand.sql", std::process::id()));
+        let content = "-- name: active :many\n-- was select * from users;\nselect * from users where active;\n\
+                       -- name: every :many\nselect * from users;\n";
+        std::fs::write(&path, content).unwrap();
+        let queries = read_query_file(path.clone(), "", &mut BTreeSet::new()).unwrap();
+        let expansions = queries
+            .iter()
+            .map(|file| {
+                let star = select_star(&file.query).unwrap().unwrap();
+                let offset = file.offset.unwrap();
+                Expansion {
+                    path: path.clone(),
+                    star: offset + star.start..offset + star.end,
+                    columns: vec!["id".to_string(), "First Name".to_string()],
+                }
+            })
+            .collect::<Vec<_>>();
+        expand_select_star(&expansions).unwrap();
+        let expanded = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            expanded,
+            content
+                .replace(
+                    "-- name: every :many\nselect *",
+                    "-- name: every :many\nselect id, \"First Name\""
+                )
+                .replace(
+                    ";\nselect * from users where",
+                    ";\nselect id, \"First Name\" from users where"
+                )
+        );
+    }
+
+    #[test]
+    fn conflicts_are_named_after_each_query() {
+        let conflicts = [(1, vec![SqlType::Int4, SqlType::Int8])];
+        assert_eq!(
+            conflict_warnings(&["id".to_string(), "y".to_string()], &conflicts),
+            ["parameter y is used as i32 and i64, declare its type with `-- param y: <type>`"]
+        );
     }
 }
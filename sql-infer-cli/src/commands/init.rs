@@ -0,0 +1,35 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::Parser;
+
+const FILES: &[(&str, &str)] = &[
+    ("sql-infer.toml", include_str!("./init/sql-infer.toml")),
+    ("queries/example.sql", include_str!("./init/example.sql")),
+    (".env", include_str!("./init/env.txt")),
+];
+
+#[derive(Parser, Debug, Clone)]
+#[must_use]
+pub struct Init {
+    /// Directory to scaffold the project in.
+    #[arg(default_value = ".")]
+    directory: PathBuf,
+}
+
+impl Init {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        for (name, content) in FILES {
+            let path = self.directory.join(name);
+            if path.exists() {
+                println!("{} already exists, skipping", path.display());
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, content)?;
+            println!("created {}", path.display());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Parser, ValueHint};
+use sql_infer_core::parser::to_ast;
+
+use crate::{
+    commands::generate::discover,
+    config::{DatabaseArgs, SqlInferConfig},
+    diagnostics::DiagnosticsFormat,
+    query_lint,
+    schema::{
+        DbSchema,
+        lint::{LintError, LintReport, Source},
+    },
+    utils::{ParametrizedQuery, parse_into_postgres},
+};
+
+#[derive(Parser, Debug, Clone)]
+#[must_use]
+pub struct LintQueries {
+    #[arg(value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+    /// Read the schema from a JSON snapshot, as written by `schema export`, instead of the database.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    schema: Option<PathBuf>,
+    /// Print the findings in the given format instead of as they are found.
+    #[arg(long, value_enum, alias = "output-format")]
+    diagnostics: Option<DiagnosticsFormat>,
+    #[command(flatten)]
+    database: DatabaseArgs,
+}
+
+impl LintQueries {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        let mut config = SqlInferConfig::load(self.config)?;
+        config.select_database(self.database)?;
+        let query_lints = query_lint::enabled(&config.lints);
+        // The database is only needed by lints looking at column types.
+        let schema = match query_lints.iter().any(|(_, lint)| lint.requires_schema()) {
+            true => Some(match &self.schema {
+                Some(snapshot) => DbSchema::from_snapshot(snapshot)?,
+                None => DbSchema::from_database(&config).await?,
+            }),
+            false => None,
+        };
+
+        let mut lint_report = LintReport::new(self.diagnostics);
+        for file in discover(&config)? {
+            let ParametrizedQuery { raw_query, .. } =
+                parse_into_postgres(&file.query, config.placeholders(&file.path))?;
+            let statements = match to_ast(&raw_query) {
+                Ok(statements) => statements,
+                Err(err) => {
+                    tracing::error!("Could not parse {}\n {err}", file.name);
+                    continue;
+                }
+            };
+            for statement in &statements {
+                for (setting, lint) in &query_lints {
+                    for msg in lint.lint(statement, schema.as_ref()) {
+                        let error = LintError::new(Source::Query(file.name.clone()), msg);
+                        lint_report.push(*setting, lint.name(), &error, &file.path);
+                    }
+                }
+            }
+        }
+        lint_report.finish()
+    }
+}
@@ -0,0 +1,80 @@
+use std::{cmp, collections::BTreeMap, error::Error, path::PathBuf};
+
+use clap::{Parser, ValueHint};
+use sql_infer_core::inference::{Nullability, QueryItem};
+
+use crate::{
+    commands::generate::{Inferred, infer},
+    config::{DatabaseArgs, SqlInferConfig},
+};
+
+#[derive(Parser, Debug, Clone)]
+#[must_use]
+pub struct List {
+    #[arg(value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+    /// Print the queries as JSON instead of a table.
+    #[arg(long)]
+    json: bool,
+    /// Number of queries inferred concurrently, overriding `jobs` in the config.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    #[command(flatten)]
+    database: DatabaseArgs,
+}
+
+fn rows<'a>(kind: &'a str, items: &'a [QueryItem]) -> impl Iterator<Item = [String; 3]> + 'a {
+    items.iter().map(move |item| {
+        let name = match item.nullable {
+            Nullability::False => item.name.clone(),
+            Nullability::True | Nullability::Unknown => format!("{}?", item.name),
+        };
+        [kind.to_string(), name, item.sql_type.to_string()]
+    })
+}
+
+impl List {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        let mut config = SqlInferConfig::load(self.config)?;
+        config.select_database(self.database)?;
+        if let Some(jobs) = self.jobs {
+            config.jobs = jobs.max(1);
+        }
+        let Inferred {
+            queries, failed, ..
+        } = infer(&config).await?;
+
+        if self.json {
+            let queries: BTreeMap<_, _> = queries
+                .into_iter()
+                .map(|(file, query)| (file.name, query))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&queries)?);
+        } else {
+            for (file, query) in queries {
+                println!("{} ({})", file.name, file.path.display());
+                let rows: Vec<_> = rows("in", &query.inputs)
+                    .chain(rows("out", &query.outputs))
+                    .collect();
+                let mut widths = [0; 3];
+                for row in &rows {
+                    for (width, cell) in widths.iter_mut().zip(row) {
+                        *width = cmp::max(*width, cell.len());
+                    }
+                }
+                for [kind, name, sql_type] in rows {
+                    println!(
+                        "  {kind:<kind_width$}  |  {name:<name_width$}  |  {sql_type}",
+                        kind_width = widths[0],
+                        name_width = widths[1],
+                    );
+                }
+                println!();
+            }
+        }
+        for name in failed {
+            eprintln!("{name} failed inference");
+        }
+        Ok(())
+    }
+}
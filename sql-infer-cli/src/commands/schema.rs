@@ -1,118 +1,376 @@
-use std::{error::Error, path::PathBuf};
-
-use clap::{Parser, ValueEnum};
-use sql_infer_core::{
-    SqlInferBuilder,
-    inference::{
-        Nullability,
-        datatypes::{DecimalPrecision, TextLength},
-        nullability::ColumnNullability,
-    },
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
 };
-use sqlx::{postgres::PgPoolOptions, query};
+
+use clap::{Args, Parser, Subcommand, ValueEnum, ValueHint};
+use sql_infer_core::parser::to_ast;
 
 use crate::{
-    config::{self, SqlInferConfig, TomlConfig},
-    schema::{self, ColumnSchema, DbSchema, TableSchema, lint::Lint},
+    commands::generate::discover,
+    config::{DatabaseArgs, Features, SqlInferConfig, find_config},
+    diagnostics::DiagnosticsFormat,
+    failure::Failure,
+    migrations::migrate,
+    schema::{
+        self, DbSchema, SchemaFilter,
+        erd::ErdFormat,
+        lint::{LintReport, Lints},
+    },
+    utils::{ParametrizedQuery, parse_into_postgres, referenced_relations},
 };
 
-#[derive(ValueEnum, Debug, Clone, Default)]
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeFormat {
+    Text,
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationFormat {
+    /// `alter table` statements.
+    Sql,
+    /// An Alembic revision file.
+    Alembic,
+}
+
+/// Where the schema is loaded from, the database unless a snapshot is given.
+#[derive(Args, Debug, Clone)]
+pub struct SchemaSource {
+    #[arg(value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+    /// Read the schema from a JSON snapshot, as written by `schema export`, instead of the database.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    schema: Option<PathBuf>,
+    /// Only introspect this schema, can be repeated.
+    #[arg(long = "db-schema", value_name = "SCHEMA")]
+    db_schemas: Vec<String>,
+    /// Only introspect relations matching this glob, can be repeated.
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+    /// Skip relations matching this glob, can be repeated.
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Skip partitions of partitioned tables.
+    #[arg(long)]
+    exclude_partitions: bool,
+    #[command(flatten)]
+    database: DatabaseArgs,
+}
+
+#[derive(Subcommand, Debug, Clone)]
 pub enum Analysis {
-    #[default]
-    Display,
-    Lint,
+    /// Pretty print every table with its columns and datatypes.
+    Display {
+        #[command(flatten)]
+        source: SchemaSource,
+    },
+    /// Point out potential problems in user defined tables.
+    Lint {
+        /// Print the findings in the given format instead of as they are found.
+        #[arg(long, value_enum, alias = "output-format")]
+        diagnostics: Option<DiagnosticsFormat>,
+        #[command(flatten)]
+        source: SchemaSource,
+    },
+    /// Compare the database against a JSON snapshot of a previous schema.
+    Diff {
+        #[arg(value_hint = ValueHint::FilePath)]
+        snapshot: PathBuf,
+        #[command(flatten)]
+        source: SchemaSource,
+    },
+    /// Compare the database against another live database, e.g. staging against production.
+    Compare {
+        /// URL of the database to compare against.
+        other: String,
+        #[arg(long, value_enum, default_value_t = ChangeFormat::Text)]
+        format: ChangeFormat,
+        #[command(flatten)]
+        source: SchemaSource,
+    },
+    /// Print DDL that migrates a database matching the snapshot to the current schema.
+    Migrate {
+        #[arg(value_hint = ValueHint::FilePath)]
+        snapshot: PathBuf,
+        #[arg(long, value_enum, default_value_t = MigrationFormat::Sql)]
+        format: MigrationFormat,
+        /// Message of the Alembic revision.
+        #[arg(short, long, default_value = "schema changes")]
+        message: String,
+        /// Revision the Alembic revision follows.
+        #[arg(long)]
+        down_revision: Option<String>,
+        #[command(flatten)]
+        source: SchemaSource,
+    },
+    /// Write a JSON snapshot of the schema, usable with `--schema` and as a `diff` snapshot.
+    Export {
+        /// File to write the snapshot to, stdout if omitted.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: Option<PathBuf>,
+        #[command(flatten)]
+        source: SchemaSource,
+    },
+    /// Render tables, columns and foreign keys as an entity relationship diagram.
+    Erd {
+        #[arg(short, long, value_enum, default_value_t = ErdFormat::Mermaid)]
+        format: ErdFormat,
+        /// File to write the diagram to, stdout if omitted.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: Option<PathBuf>,
+        #[command(flatten)]
+        source: SchemaSource,
+    },
+    /// Print `create` statements reconstructing the schema.
+    Ddl {
+        /// File to write the statements to, stdout if omitted.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: Option<PathBuf>,
+        #[command(flatten)]
+        source: SchemaSource,
+    },
+    /// Print pgTAP assertions checking every table and column exists with its type and nullability.
+    Pgtap {
+        /// File to write the test to, stdout if omitted.
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        out: Option<PathBuf>,
+        #[command(flatten)]
+        source: SchemaSource,
+    },
+    /// Write a Markdown page per table, listing the queries that use it.
+    Docs {
+        /// Directory to write the pages to.
+        #[arg(short, long, value_hint = ValueHint::DirPath, default_value = "schema-docs")]
+        out: PathBuf,
+        #[command(flatten)]
+        source: SchemaSource,
+    },
 }
 
 #[derive(Parser, Debug, Clone)]
 #[must_use]
 pub struct Schema {
+    #[command(subcommand)]
     analysis: Analysis,
-    config: Option<PathBuf>,
 }
 
-impl Schema {
-    pub async fn run(self) -> Result<(), Box<dyn Error>> {
-        // FIXME: Duplicate code
-        let config = match self.config {
-            Some(config) => config,
-            None => PathBuf::from("sql-infer.toml"),
-        };
-        let config: TomlConfig = toml::from_slice(&std::fs::read(&config).map_err(|error| {
-            format!(
-                "encountered '{error}' attempting to read {}",
-                config.display()
-            )
-        })?)?;
-        let config: SqlInferConfig = SqlInferConfig::from_toml_config(config)?;
-
-        let mut sql_infer = SqlInferBuilder::default();
-        if config.experimental_features.nullability() {
-            sql_infer.add_information_schema_pass(ColumnNullability);
-        }
-        if config.experimental_features.decimal_precision() {
-            sql_infer.add_information_schema_pass(DecimalPrecision);
-        }
-        if config.experimental_features.text_length() {
-            sql_infer.add_information_schema_pass(TextLength);
+impl SchemaSource {
+    /// Filter given on the command line.
+    fn filter(&self) -> SchemaFilter {
+        SchemaFilter {
+            schemas: self.db_schemas.clone(),
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            exclude_partitions: self.exclude_partitions,
         }
-        let sql_infer = sql_infer.build();
-
-        let pool = PgPoolOptions::new()
-            .max_connections(1)
-            .connect(&config::db_url()?)
-            .await?;
-        let tables = query!(
-            r#"SELECT
-    table_schema,
-    table_name
-FROM
-    information_schema.tables
-WHERE
-    table_schema NOT IN ('pg_catalog', 'information_schema')"#
-        )
-        .fetch_all(&pool)
-        .await?;
-        let tables: Vec<_> = tables
-            .into_iter()
-            .flat_map(|record| record.table_schema.zip(record.table_name))
-            .collect();
+    }
+}
 
-        let mut table_schemas = vec![];
-        for (schema, table) in tables {
-            let types = sql_infer.infer_table_types(&pool, &schema, &table).await?;
-            let mut columns = vec![];
-            for col in types.output {
-                columns.push(ColumnSchema {
-                    name: col.name,
-                    data_type: col.sql_type,
-                    nullable: col.nullable == Nullability::True,
-                });
-            }
-            table_schemas.push(TableSchema {
-                name: table,
-                columns,
-            });
+async fn load_schema(source: SchemaSource) -> Result<DbSchema, Box<dyn Error>> {
+    match source.schema {
+        Some(snapshot) => DbSchema::from_snapshot(&snapshot),
+        None => {
+            let mut config = SqlInferConfig::load(source.config.clone())?;
+            config.select_database(source.database.clone())?;
+            config.schema.extend(source.filter());
+            migrate(&config).await?;
+            DbSchema::from_database(&config).await
         }
-        let db_schema = DbSchema {
-            tables: table_schemas,
-        };
+    }
+}
 
+impl Schema {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
         match self.analysis {
-            Analysis::Display => {
+            Analysis::Display { source } => {
+                let db_schema = load_schema(source).await?;
                 println!("{db_schema}");
             }
-            Analysis::Lint => {
-                let ttz = schema::lint::TimeWithTimezone;
-                let twt = schema::lint::TimestampWithoutTimezone;
-                let tcnc = schema::lint::TableColumnNameClash;
-                for error in ttz.lint(&db_schema) {
-                    println!("{error}");
+            Analysis::Lint {
+                diagnostics,
+                source,
+            } => {
+                // A snapshot can be linted without a config, using the default levels.
+                let lints = match (&source.schema, &source.config) {
+                    (Some(_), None) if find_config().is_none() => Lints::default(),
+                    _ => SqlInferConfig::load(source.config.clone())?.lints,
+                };
+                let db_schema = load_schema(source).await?;
+                // Findings are about the schema rather than a file.
+                let mut lint_report = LintReport::new(diagnostics);
+                for (setting, lint) in lints.enabled() {
+                    for error in lint.lint(&db_schema) {
+                        lint_report.push(setting, lint.name(), &error, Path::new(""));
+                    }
+                }
+                for plugin in &lints.plugins {
+                    for error in plugin.run(&db_schema)? {
+                        lint_report.push(plugin.level, &plugin.name, &error, Path::new(""));
+                    }
+                }
+                lint_report.finish()?;
+            }
+            Analysis::Diff { snapshot, source } => {
+                let snapshot = DbSchema::from_snapshot(&snapshot)?;
+                let db_schema = load_schema(source).await?;
+                let changes = schema::diff::diff(&snapshot, &db_schema);
+                for change in &changes {
+                    println!("{change}");
+                }
+                if !changes.is_empty() {
+                    Err(Failure::Drift {
+                        count: changes.len(),
+                    })?;
+                }
+            }
+            Analysis::Compare {
+                other,
+                format,
+                source,
+            } => {
+                // Without a config both databases are introspected with the default features.
+                let (features, mut filter, url) = match (&source.config, find_config().is_some()) {
+                    (None, false) => (
+                        Features::default(),
+                        SchemaFilter::default(),
+                        source.database.url(),
+                    ),
+                    _ => {
+                        let mut config = SqlInferConfig::load(source.config.clone())?;
+                        config.select_database(source.database.clone())?;
+                        let url = config.db_url();
+                        (config.experimental_features, config.schema, url)
+                    }
+                };
+                filter.extend(source.filter());
+                let db_schema = match &source.schema {
+                    Some(snapshot) => DbSchema::from_snapshot(snapshot)?,
+                    None => DbSchema::from_url(&url?, &features.sql_infer(), &filter).await?,
+                };
+                let other = DbSchema::from_url(&other, &features.sql_infer(), &filter).await?;
+                let changes = schema::diff::diff(&db_schema, &other);
+                match format {
+                    ChangeFormat::Text => {
+                        for change in &changes {
+                            println!("{change}");
+                        }
+                    }
+                    ChangeFormat::Json => println!("{}", serde_json::to_string_pretty(&changes)?),
+                }
+                if !changes.is_empty() {
+                    Err(Failure::Drift {
+                        count: changes.len(),
+                    })?;
+                }
+            }
+            Analysis::Migrate {
+                snapshot,
+                format,
+                message,
+                down_revision,
+                source,
+            } => {
+                let snapshot = DbSchema::from_snapshot(&snapshot)?;
+                let db_schema = load_schema(source).await?;
+                let changes = schema::diff::diff(&snapshot, &db_schema);
+                match format {
+                    MigrationFormat::Sql => {
+                        for statement in schema::ddl::migration(&changes, &db_schema) {
+                            println!("{statement}");
+                        }
+                    }
+                    MigrationFormat::Alembic => print!(
+                        "{}",
+                        schema::alembic::revision(
+                            &changes,
+                            &snapshot,
+                            &db_schema,
+                            &message,
+                            down_revision.as_deref()
+                        )
+                    ),
                 }
-                for error in twt.lint(&db_schema) {
-                    println!("{error}");
+            }
+            Analysis::Export { out, source } => {
+                let db_schema = load_schema(source).await?;
+                let snapshot = serde_json::to_string_pretty(&db_schema)?;
+                match out {
+                    Some(out) => std::fs::write(out, snapshot)?,
+                    None => println!("{snapshot}"),
+                }
+            }
+            Analysis::Erd {
+                format,
+                out,
+                source,
+            } => {
+                let db_schema = load_schema(source).await?;
+                let diagram = schema::erd::render(&db_schema, format);
+                match out {
+                    Some(out) => std::fs::write(out, diagram)?,
+                    None => println!("{diagram}"),
+                }
+            }
+            Analysis::Ddl { out, source } => {
+                let db_schema = load_schema(source).await?;
+                let ddl = schema::ddl::schema(&db_schema).join("\n\n");
+                match out {
+                    Some(out) => std::fs::write(out, ddl)?,
+                    None => println!("{ddl}"),
+                }
+            }
+            Analysis::Pgtap { out, source } => {
+                let db_schema = load_schema(source).await?;
+                let test = schema::pgtap::test_file(&db_schema);
+                match out {
+                    Some(out) => std::fs::write(out, test)?,
+                    None => print!("{test}"),
+                }
+            }
+            Analysis::Docs { out, source } => {
+                // Queries are only listed when there is a config to find them with.
+                let config = match (&source.config, find_config().is_some()) {
+                    (None, false) => None,
+                    _ => Some(SqlInferConfig::load(source.config.clone())?),
+                };
+                let db_schema = load_schema(source).await?;
+                let mut queries: HashMap<(&str, &str), Vec<String>> = HashMap::new();
+                if let Some(config) = &config {
+                    for file in discover(config)? {
+                        let ParametrizedQuery { raw_query, .. } =
+                            parse_into_postgres(&file.query, config.placeholders(&file.path))?;
+                        let statements = match to_ast(&raw_query) {
+                            Ok(statements) => statements,
+                            Err(err) => {
+                                tracing::error!("Could not parse {}\n {err}", file.name);
+                                continue;
+                            }
+                        };
+                        for reference in referenced_relations(&statements) {
+                            let Some(table) = db_schema.resolve(&reference) else {
+                                continue;
+                            };
+                            // A table can be referenced both with and without its schema.
+                            let names = queries.entry((&table.schema, &table.name)).or_default();
+                            if !names.contains(&file.name) {
+                                names.push(file.name.clone());
+                            }
+                        }
+                    }
                 }
-                for error in tcnc.lint(&db_schema) {
-                    println!("{error}");
+                std::fs::create_dir_all(&out)?;
+                std::fs::write(out.join("index.md"), schema::docs::index(&db_schema))?;
+                for table in &db_schema.tables {
+                    let table_queries = queries
+                        .get(&(table.schema.as_str(), table.name.as_str()))
+                        .map(|names| names.iter().map(String::as_str).collect::<Vec<_>>())
+                        .unwrap_or_default();
+                    std::fs::write(
+                        out.join(schema::docs::page_name(table)),
+                        schema::docs::table_page(table, &table_queries),
+                    )?;
                 }
             }
         }
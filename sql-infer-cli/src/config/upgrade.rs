@@ -0,0 +1,211 @@
+use std::{error::Error, path::PathBuf};
+
+use toml::{Table, Value};
+
+use crate::config::{SqlInferConfig, TomlConfig, is_pyproject, read_config};
+
+/// Keys of `[experimental-features]`, given at the top level by older configs.
+const FEATURES: [&str; 4] = [
+    "infer-nullability",
+    "precise-output-datatypes",
+    "rename-duplicate-columns",
+    "explain-column-origins",
+];
+
+/// Name of the `[databases]` entry replacing a `[database]` table.
+const DATABASE: &str = "default";
+
+/// Outcome of `config upgrade`.
+#[derive(Debug, Clone)]
+pub struct Upgrade {
+    pub path: PathBuf,
+    /// The config in the current layout.
+    pub upgraded: String,
+    /// What was rewritten, empty when the config was up to date.
+    pub changes: Vec<String>,
+}
+
+/// Rewrite the config at `path` to the current layout. Comments and formatting are not kept.
+pub fn upgrade_config(path: Option<PathBuf>) -> Result<Upgrade, Box<dyn Error>> {
+    let (path, raw) = read_config(path)?;
+    if is_pyproject(&path) {
+        Err("only sql-infer.toml can be upgraded, older versions did not read pyproject.toml")?;
+    }
+    let mut config = raw
+        .parse::<Table>()
+        .map_err(|error| format!("{}: {error}", path.display()))?;
+    let changes = upgrade(&mut config);
+    let upgraded = toml::to_string_pretty(&config)?;
+    let parsed: TomlConfig = toml::from_str(&upgraded)
+        .map_err(|error| format!("the upgraded config is still invalid: {error}"))?;
+    SqlInferConfig::from_toml_config(parsed)
+        .map_err(|error| format!("the upgraded config is still invalid: {error}"))?;
+    Ok(Upgrade {
+        path,
+        upgraded,
+        changes,
+    })
+}
+
+/// Replace the settings of older versions in `config`, returning a description of each change.
+pub fn upgrade(config: &mut Table) -> Vec<String> {
+    let mut changes = vec![];
+    upgrade_targets(config, "", &mut changes);
+    for section in ["source", "profile"] {
+        if let Some(Value::Table(entries)) = config.get_mut(section) {
+            for (name, entry) in entries.iter_mut() {
+                if let Value::Table(entry) = entry {
+                    upgrade_targets(entry, &format!("{section}.{name}."), &mut changes);
+                }
+            }
+        }
+    }
+
+    for feature in FEATURES {
+        let Some(value) = config.remove(feature) else {
+            continue;
+        };
+        let features = config
+            .entry("experimental-features")
+            .or_insert_with(|| Value::Table(Table::new()));
+        if let Value::Table(features) = features {
+            features.insert(feature.to_string(), value);
+        }
+        changes.push(format!("`{feature}` moved to `[experimental-features]`"));
+    }
+
+    if let Some(Value::Table(database)) = config.remove("database") {
+        let mut entry = Table::new();
+        entry.insert("url".to_string(), Value::String(database_url(&database)));
+        let databases = config
+            .entry("databases")
+            .or_insert_with(|| Value::Table(Table::new()));
+        if let Value::Table(databases) = databases {
+            databases.insert(DATABASE.to_string(), Value::Table(entry));
+        }
+        if !config.contains_key("env") {
+            config.insert("env".to_string(), Value::String(DATABASE.to_string()));
+        }
+        changes.push(format!(
+            "`[database]` is now `[databases.{DATABASE}]`, picked with `env = \"{DATABASE}\"`"
+        ));
+    }
+    changes
+}
+
+/// Upgrade the `mode` of `table` and of its `[[generate]]` entries, `prefix` being the dotted
+/// path of `table`.
+fn upgrade_targets(table: &mut Table, prefix: &str, changes: &mut Vec<String>) {
+    if let Some(mode) = table.get_mut("mode") {
+        upgrade_mode(mode, &format!("{prefix}mode"), changes);
+    }
+    if let Some(Value::Array(targets)) = table.get_mut("generate") {
+        for (index, target) in targets.iter_mut().enumerate() {
+            if let Some(mode) = target.get_mut("mode") {
+                upgrade_mode(mode, &format!("{prefix}generate.{index}.mode"), changes);
+            }
+        }
+    }
+}
+
+/// Replace the `sql-alchemy` and `sql-alchemy-async` modes, removed in 0.15, by
+/// `sql-alchemy-v2`.
+fn upgrade_mode(mode: &mut Value, key: &str, changes: &mut Vec<String>) {
+    let (name, r#async) = match mode.as_str() {
+        Some(name @ "sql-alchemy") => (name, false),
+        Some(name @ "sql-alchemy-async") => (name, true),
+        _ => return,
+    };
+    changes.push(format!(
+        "`{key} = \"{name}\"` is now `sql-alchemy-v2` with `async = {}`",
+        r#async
+    ));
+    let mut options = Table::new();
+    options.insert("async".to_string(), Value::Boolean(r#async));
+    let mut generator = Table::new();
+    generator.insert("sql-alchemy-v2".to_string(), Value::Table(options));
+    *mode = Value::Table(generator);
+}
+
+/// URL of a `[database]` table, given either as `url` or as its parts.
+fn database_url(database: &Table) -> String {
+    let part = |keys: &[&str]| {
+        keys.iter().find_map(|key| match database.get(*key)? {
+            Value::String(value) => Some(value.clone()),
+            Value::Integer(value) => Some(value.to_string()),
+            _ => None,
+        })
+    };
+    if let Some(url) = part(&["url"]) {
+        return url;
+    }
+    let mut url = "postgres://".to_string();
+    if let Some(user) = part(&["user", "username"]) {
+        url += &user;
+        if let Some(password) = part(&["password"]) {
+            url += &format!(":{password}");
+        }
+        url += "@";
+    }
+    url += &part(&["host"]).unwrap_or_else(|| "localhost".to_string());
+    if let Some(port) = part(&["port"]) {
+        url += &format!(":{port}");
+    }
+    if let Some(name) = part(&["name", "database", "dbname"]) {
+        url += &format!("/{name}");
+    }
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::Table;
+
+    use crate::config::upgrade::upgrade;
+
+    #[test]
+    fn legacy_settings_are_rewritten() {
+        let mut config = r#"
+path = "queries"
+target = "queries.py"
+mode = "sql-alchemy-async"
+infer-nullability = true
+
+[[generate]]
+target = "sync.py"
+mode = "sql-alchemy"
+
+[database]
+host = "db"
+port = 5432
+user = "app"
+name = "app"
+"#
+        .parse::<Table>()
+        .unwrap();
+        let expected = r#"
+path = "queries"
+target = "queries.py"
+env = "default"
+
+[mode.sql-alchemy-v2]
+async = true
+
+[[generate]]
+target = "sync.py"
+[generate.mode.sql-alchemy-v2]
+async = false
+
+[experimental-features]
+infer-nullability = true
+
+[databases.default]
+url = "postgres://app@db:5432/app"
+"#
+        .parse::<Table>()
+        .unwrap();
+        assert_eq!(upgrade(&mut config).len(), 4);
+        assert_eq!(config, expected);
+        assert!(upgrade(&mut config).is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use sqlx::postgres::{PgDatabaseError, PgErrorPosition};
+
+use crate::utils::original_offset;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticsFormat {
+    Json,
+    /// Workflow commands GitHub Actions shows as annotations on the pull request.
+    Github,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Explains where inference was less precise, never fails a run.
+    Note,
+    Warning,
+    Error,
+}
+
+/// 1-based position within a query file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// Position of the character at `offset` in `query`.
+    pub fn at(query: &str, offset: usize) -> Self {
+        let mut span = Span { line: 1, column: 1 };
+        for char in query.chars().take(offset) {
+            match char {
+                '\n' => {
+                    span.line += 1;
+                    span.column = 1;
+                }
+                _ => span.column += 1,
+            }
+        }
+        span
+    }
+
+    /// Position reported by the database for an error raised by `raw_query`, the rewritten form
+    /// of `query`.
+    pub fn from_error(error: &(dyn Error + 'static), query: &str, raw_query: &str) -> Option<Self> {
+        let error = error
+            .downcast_ref::<sqlx::Error>()?
+            .as_database_error()?
+            .try_downcast_ref::<PgDatabaseError>()?;
+        let PgErrorPosition::Original(position) = error.position()? else {
+            return None;
+        };
+        // Postgres positions are 1-based.
+        let offset = original_offset(query, raw_query, position.saturating_sub(1));
+        Some(Self::at(query, offset))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub query: String,
+    pub span: Option<Span>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Escape `value` for a workflow command, `property` values also escaping their separators.
+fn escape_workflow(value: &str, property: bool) -> String {
+    let value = value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    match property {
+        true => value.replace(':', "%3A").replace(',', "%2C"),
+        false => value,
+    }
+}
+
+impl Diagnostic {
+    /// The diagnostic as a GitHub Actions `::error`, `::warning` or `::notice` command.
+    fn workflow_command(&self) -> String {
+        let command = match self.severity {
+            Severity::Note => "notice",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        let mut properties = vec![];
+        if !self.file.as_os_str().is_empty() {
+            properties.push(format!(
+                "file={}",
+                escape_workflow(&self.file.to_string_lossy(), true)
+            ));
+        }
+        if let Some(span) = self.span {
+            properties.push(format!("line={},col={}", span.line, span.column));
+        }
+        let title = match self.query.is_empty() {
+            true => "sql-infer".to_string(),
+            false => format!("sql-infer: {}", self.query),
+        };
+        properties.push(format!("title={}", escape_workflow(&title, true)));
+        format!(
+            "::{command} {}::{}",
+            properties.join(","),
+            escape_workflow(&self.message, false)
+        )
+    }
+}
+
+pub fn report(diagnostics: &[Diagnostic], format: DiagnosticsFormat) -> Result<(), Box<dyn Error>> {
+    match format {
+        DiagnosticsFormat::Json => println!("{}", serde_json::to_string_pretty(diagnostics)?),
+        DiagnosticsFormat::Github => {
+            for diagnostic in diagnostics {
+                println!("{}", diagnostic.workflow_command());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::diagnostics::{Diagnostic, Severity, Span};
+
+    #[test]
+    fn workflow_commands_are_escaped() {
+        let diagnostic = Diagnostic {
+            file: PathBuf::from("queries/a,b.sql"),
+            query: "get_user".to_string(),
+            span: Some(Span { line: 2, column: 7 }),
+            severity: Severity::Error,
+            message: "column \"x\" does not exist\n100% sure".to_string(),
+        };
+        assert_eq!(
+            diagnostic.workflow_command(),
+            "::error file=queries/a%2Cb.sql,line=2,col=7,title=sql-infer%3A get_user::column \"x\" does not exist%0A100%25 sure"
+        );
+    }
+}
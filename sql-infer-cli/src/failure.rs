@@ -0,0 +1,52 @@
+use std::{error::Error, fmt::Display, process::ExitCode};
+
+/// Errors that end the run with a specific exit code. Any other error is treated as a
+/// configuration error.
+#[derive(Debug)]
+pub enum Failure {
+    Connection(sqlx::Error),
+    Inference {
+        queries: Vec<String>,
+    },
+    Warnings {
+        count: usize,
+    },
+    /// Differences found by a command comparing against an expected state, e.g. a snapshot.
+    Drift {
+        count: usize,
+    },
+}
+
+impl Failure {
+    pub const CONFIG: u8 = 1;
+    pub const CONNECTION: u8 = 2;
+    pub const INFERENCE: u8 = 3;
+    pub const DRIFT: u8 = 4;
+
+    pub fn exit_code(error: &(dyn Error + 'static)) -> ExitCode {
+        let code = match error.downcast_ref::<Failure>() {
+            Some(Failure::Connection(_)) => Self::CONNECTION,
+            Some(Failure::Inference { .. } | Failure::Warnings { .. }) => Self::INFERENCE,
+            Some(Failure::Drift { .. }) => Self::DRIFT,
+            None => Self::CONFIG,
+        };
+        ExitCode::from(code)
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Connection(error) => write!(f, "failed to connect to the database: {error}"),
+            Failure::Inference { queries } => {
+                write!(f, "inference failed for: {}", queries.join(", "))
+            }
+            Failure::Warnings { count } => {
+                write!(f, "{count} warning(s) encountered in strict mode")
+            }
+            Failure::Drift { count } => write!(f, "{count} difference(s) found"),
+        }
+    }
+}
+
+impl Error for Failure {}
@@ -0,0 +1,45 @@
+//! The pipeline behind `sql-infer generate`, for running it from a `build.rs` or another Rust
+//! tool instead of the CLI.
+//!
+//! ```no_run
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let generated = sql_infer_cli::build(Some("sql-infer.toml".into()))?;
+//!     generated.status(false)
+//! }
+//! ```
+
+pub mod codegen;
+pub mod commands;
+pub mod config;
+pub mod diagnostics;
+pub mod failure;
+pub mod manifest;
+pub mod migrations;
+pub mod query_lint;
+pub mod schema;
+pub mod temp_db;
+pub mod timings;
+pub mod utils;
+
+use std::{error::Error, path::PathBuf};
+
+pub use commands::generate::{Generated, Inferred, QueryFile, discover, generate, infer, write};
+pub use config::SqlInferConfig;
+pub use migrations::migrate;
+
+/// Load the config at `path`, or the one found from the current directory, then migrate,
+/// infer and write every target as `sql-infer generate` does, on a runtime of its own.
+///
+/// Queries that failed inference are not an error here, see [`Generated::status`]. With
+/// `fail-fast` no target is written when any query failed.
+pub fn build(path: Option<PathBuf>) -> Result<Generated, Box<dyn Error>> {
+    let config = SqlInferConfig::load(path)?;
+    tokio::runtime::Runtime::new()?.block_on(async {
+        migrate(&config).await?;
+        let generated = generate(&config).await?;
+        if !config.fail_fast || generated.failed.is_empty() {
+            write(&config, &generated).await?;
+        }
+        Ok(generated)
+    })
+}
@@ -1,40 +1,97 @@
-pub mod codegen;
-mod commands;
-pub mod config;
-pub mod schema;
-pub mod utils;
+use std::process::ExitCode;
 
 use clap::*;
-use commands::Generate;
-use tracing::Level;
+use sql_infer_cli::{
+    commands::{
+        Generate, analyze::Analyze, check::Check, completions::Completions, config::Config,
+        doctor::Doctor, explain::Explain, init::Init, lint_queries::LintQueries, list::List,
+        schema::Schema,
+    },
+    failure::Failure,
+};
+use tracing::level_filters::LevelFilter;
 use tracing_subscriber::FmtSubscriber;
 
-use crate::commands::{analyze::Analyze, schema::Schema};
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    Text,
+    Json,
+}
 
 #[derive(Parser)]
 #[command(name = "sql-infer", bin_name = "sql-infer")]
+struct Cli {
+    /// Show more log output, repeat for more detail.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Show less log output, repeat to silence errors as well.
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+    quiet: u8,
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+    #[command(subcommand)]
+    command: Command,
+}
+
+impl Cli {
+    fn level(&self) -> LevelFilter {
+        // Warnings are shown by default.
+        match 2 + i16::from(self.verbose) - i16::from(self.quiet) {
+            ..=0 => LevelFilter::OFF,
+            1 => LevelFilter::ERROR,
+            2 => LevelFilter::WARN,
+            3 => LevelFilter::INFO,
+            4 => LevelFilter::DEBUG,
+            5.. => LevelFilter::TRACE,
+        }
+    }
+}
+
+#[derive(Subcommand)]
 enum Command {
+    Init(Init),
     Generate(Generate),
+    Check(Check),
+    List(List),
+    LintQueries(LintQueries),
     Analyze(Analyze),
+    Explain(Explain),
     Schema(Schema),
+    Doctor(Doctor),
+    Config(Config),
+    Completions(Completions),
 }
 
 #[tokio::main]
-async fn main() -> Result<(), String> {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::ERROR)
-        .finish();
-
-    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let builder = FmtSubscriber::builder()
+        .with_max_level(cli.level())
+        .with_writer(std::io::stderr);
+    let result = match cli.log_format {
+        LogFormat::Text => tracing::subscriber::set_global_default(builder.finish()),
+        LogFormat::Json => tracing::subscriber::set_global_default(builder.json().finish()),
+    };
+    result.expect("setting default subscriber failed");
 
-    let command = Command::parse();
-    let res = match command {
+    let res = match cli.command {
+        Command::Init(init) => init.run().await,
         Command::Generate(args) => args.run().await,
+        Command::Check(check) => check.run().await,
+        Command::List(list) => list.run().await,
+        Command::LintQueries(lint_queries) => lint_queries.run().await,
         Command::Analyze(analyze) => analyze.run().await,
+        Command::Explain(explain) => explain.run().await,
         Command::Schema(schema) => schema.run().await,
+        Command::Doctor(doctor) => doctor.run().await,
+        Command::Config(config) => config.run().await,
+        Command::Completions(completions) => completions.run(Cli::command()).await,
     };
-    if let Err(err) = res {
-        return Err(err.to_string());
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            Failure::exit_code(err.as_ref())
+        }
     }
-    Ok(())
 }
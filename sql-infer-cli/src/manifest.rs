@@ -0,0 +1,371 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error::Error,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sql_infer_core::inference::QueryTypes;
+use sqlx::{Pool, Postgres, query};
+
+use crate::config::Features;
+
+pub fn sha256(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+/// Hash of a query along with the features it is inferred with, as both decide its types.
+pub fn query_hash(query: &str, features: &Features) -> String {
+    sha256(&format!("{features:?}\n{query}"))
+}
+
+/// Hash of the definition of each table in `tables` that exists in the database, covering the
+/// name, type and nullability of its columns as well as the labels of enum columns.
+pub async fn table_fingerprints(
+    pool: &Pool<Postgres>,
+    tables: &BTreeSet<String>,
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let tables = tables.iter().cloned().collect::<Vec<_>>();
+    let records = query!(
+        r#"SELECT
+    rel.relname::text AS "name!",
+    md5(string_agg(
+        nsp.nspname || '.' || att.attname || ':' || format_type(att.atttypid, att.atttypmod)
+            || ':' || att.attnotnull::text || ':' || att.atthasdef::text || ':' || coalesce((
+                SELECT string_agg(enm.enumlabel, ',' ORDER BY enm.enumsortorder)
+                FROM pg_enum enm
+                    JOIN pg_type typ ON enm.enumtypid IN (typ.oid, typ.typelem)
+                WHERE typ.oid = att.atttypid
+            ), ''),
+        ',' ORDER BY nsp.nspname, att.attnum
+    )) AS "fingerprint!"
+FROM
+    pg_class rel
+    JOIN pg_namespace nsp ON nsp.oid = rel.relnamespace
+    JOIN pg_attribute att ON att.attrelid = rel.oid
+WHERE
+    rel.relname = ANY($1)
+    AND rel.relkind IN ('r', 'p', 'v', 'm', 'f')
+    AND att.attnum > 0
+    AND NOT att.attisdropped
+GROUP BY
+    rel.relname"#,
+        &tables
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(records
+        .into_iter()
+        .map(|record| (record.name, record.fingerprint))
+        .collect())
+}
+
+/// Hash of the definitions of the tables a query uses, tables missing from the database are
+/// included so creating them changes the hash.
+pub fn schema_hash(tables: &BTreeSet<String>, fingerprints: &HashMap<String, String>) -> String {
+    let mut content = String::new();
+    for table in tables {
+        let fingerprint = fingerprints.get(table).map(String::as_str).unwrap_or("");
+        content.push_str(&format!("{table}={fingerprint}\n"));
+    }
+    sha256(&content)
+}
+
+/// Hash of every file matching `patterns`, standing in for the schema when it only changes
+/// through these files, e.g. migrations.
+pub fn files_hash(patterns: &[String]) -> Result<String, Box<dyn Error>> {
+    let mut paths = vec![];
+    for pattern in patterns {
+        for path in glob::glob(pattern)? {
+            paths.push(path?);
+        }
+    }
+    paths.sort();
+    let mut hasher = Sha256::new();
+    for path in paths {
+        if !path.is_file() {
+            continue;
+        }
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(std::fs::read(&path)?);
+        hasher.update(b"\n");
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// The `[cache]` config table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CacheConfig {
+    pub directory: PathBuf,
+    /// Glob patterns of the files defining the schema. When set their content identifies the
+    /// schema instead of the database, so fully cached runs do not connect to it.
+    #[serde(default = "Vec::default")]
+    pub schema_files: Vec<String>,
+}
+
+/// Types of previously inferred queries keyed by query and schema hash, so entries are shared
+/// between query names, branches and CI runs.
+pub struct Cache {
+    directory: PathBuf,
+}
+
+impl Cache {
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            directory: directory.to_path_buf(),
+        }
+    }
+
+    fn path(&self, query_hash: &str, schema_hash: &str) -> PathBuf {
+        let key = sha256(&format!("{query_hash}\n{schema_hash}"));
+        self.directory.join(format!("{key}.json"))
+    }
+
+    /// Cached types, missing or unreadable entries are treated as misses.
+    pub fn get(&self, query_hash: &str, schema_hash: &str) -> Option<QueryTypes> {
+        let content = std::fs::read(self.path(query_hash, schema_hash)).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    pub fn insert(
+        &self,
+        query_hash: &str,
+        schema_hash: &str,
+        types: &QueryTypes,
+    ) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(&self.directory)?;
+        std::fs::write(
+            self.path(query_hash, schema_hash),
+            serde_json::to_string(types)?,
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ManifestEntry {
+    query_hash: String,
+    schema_hash: String,
+    types: QueryTypes,
+}
+
+/// Types inferred by a previous run, reused for queries whose text and tables are unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    queries: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Read the manifest at `path`, an unreadable manifest is treated as empty.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => Err(error)?,
+        };
+        match serde_json::from_slice(&content) {
+            Ok(manifest) => Ok(manifest),
+            Err(error) => {
+                tracing::warn!("Ignoring invalid manifest {}: {error}", path.display());
+                Ok(Self::default())
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str, query_hash: &str, schema_hash: &str) -> Option<&QueryTypes> {
+        self.queries
+            .get(name)
+            .filter(|entry| entry.query_hash == query_hash && entry.schema_hash == schema_hash)
+            .map(|entry| &entry.types)
+    }
+
+    /// Types recorded for `name`, whether or not the query or its tables changed since.
+    pub fn types(&self, name: &str) -> Option<&QueryTypes> {
+        self.queries.get(name).map(|entry| &entry.types)
+    }
+
+    pub fn insert(
+        &mut self,
+        name: String,
+        query_hash: String,
+        schema_hash: String,
+        types: QueryTypes,
+    ) {
+        self.queries.insert(
+            name,
+            ManifestEntry {
+                query_hash,
+                schema_hash,
+                types,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        path::PathBuf,
+    };
+
+    use sql_infer_core::inference::{Nullability, QueryItem, QueryTypes, SqlType};
+
+    use crate::{
+        config::{Features, Pass},
+        manifest::{Cache, Manifest, files_hash, query_hash, schema_hash},
+    };
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sql-infer-{}-{name}", std::process::id()))
+    }
+
+    fn types(column: &str) -> QueryTypes {
+        QueryTypes {
+            input: [].into(),
+            output: [QueryItem {
+                name: column.to_string(),
+                sql_type: SqlType::Text,
+                nullable: Nullability::False,
+            }]
+            .into(),
+            warnings: [].into(),
+            notes: [].into(),
+            rows_affected: false,
+            kind: Default::default(),
+            lineage: [].into(),
+            copy_from: None,
+        }
+    }
+
+    #[test]
+    fn unchanged_queries_are_reused() {
+        let path = temp_path("manifest.json");
+        let query = query_hash("select name from users", &Features::default());
+        let tables = BTreeSet::from(["users".to_string()]);
+        let fingerprints = HashMap::from([("users".to_string(), "a1".to_string())]);
+        let schema = schema_hash(&tables, &fingerprints);
+        let mut manifest = Manifest::default();
+        manifest.insert(
+            "users".to_string(),
+            query.clone(),
+            schema.clone(),
+            types("name"),
+        );
+        manifest.save(&path).unwrap();
+        let loaded = Manifest::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.get("users", &query, &schema), Some(&types("name")));
+        assert_eq!(loaded.get("posts", &query, &schema), None);
+    }
+
+    #[test]
+    fn changed_queries_and_tables_invalidate_entries() {
+        let features = Features::default();
+        let query = query_hash("select name from users", &features);
+        let tables = BTreeSet::from(["users".to_string(), "posts".to_string()]);
+        let fingerprints = HashMap::from([("users".to_string(), "a1".to_string())]);
+        let schema = schema_hash(&tables, &fingerprints);
+        let mut manifest = Manifest::default();
+        manifest.insert(
+            "users".to_string(),
+            query.clone(),
+            schema.clone(),
+            types("name"),
+        );
+
+        let edited = query_hash("select name, email from users", &features);
+        assert_eq!(manifest.get("users", &edited, &schema), None);
+        let altered = HashMap::from([("users".to_string(), "b2".to_string())]);
+        let altered = schema_hash(&tables, &altered);
+        assert_eq!(manifest.get("users", &query, &altered), None);
+        // Creating a table the query refers to changes the hash as well.
+        let created = HashMap::from([
+            ("users".to_string(), "a1".to_string()),
+            ("posts".to_string(), "c3".to_string()),
+        ]);
+        let created = schema_hash(&tables, &created);
+        assert_eq!(manifest.get("users", &query, &created), None);
+        // Queries left out of `--changed` take the recorded types regardless.
+        assert_eq!(manifest.types("users"), Some(&types("name")));
+    }
+
+    #[test]
+    fn unreadable_and_outdated_manifests_are_ignored() {
+        let query = query_hash("select name from users", &Features::default());
+        let missing = Manifest::load(&temp_path("missing.json")).unwrap();
+        assert_eq!(missing.types("users"), None);
+        for (name, content) in [
+            ("invalid.json", "{\"queries\": {\"users\":"),
+            (
+                "outdated.json",
+                "{\"queries\": {\"users\": {\"hash\": \"a1\", \"columns\": [\"name\"]}}}",
+            ),
+        ] {
+            let path = temp_path(name);
+            std::fs::write(&path, content).unwrap();
+            let manifest = Manifest::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(manifest.types("users"), None);
+            assert_eq!(manifest.get("users", &query, ""), None);
+        }
+    }
+
+    #[test]
+    fn cached_types_are_keyed_by_query_and_schema() {
+        let directory = temp_path("cache");
+        let cache = Cache::new(&directory);
+        let query = query_hash("select name from users", &Features::default());
+        let tables = BTreeSet::from(["users".to_string()]);
+        let fingerprints = HashMap::from([("users".to_string(), "a1".to_string())]);
+        let schema = schema_hash(&tables, &fingerprints);
+        assert_eq!(cache.get(&query, &schema), None);
+        cache.insert(&query, &schema, &types("name")).unwrap();
+        assert_eq!(cache.get(&query, &schema), Some(types("name")));
+
+        let altered = HashMap::from([("users".to_string(), "b2".to_string())]);
+        assert_eq!(cache.get(&query, &schema_hash(&tables, &altered)), None);
+        let features = Features::default().without(Pass::TextLength);
+        let query_with_features = query_hash("select name from users", &features);
+        assert_eq!(cache.get(&query_with_features, &schema), None);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn schema_files_changes_invalidate_cached_types() {
+        let directory = temp_path("schema-files");
+        std::fs::create_dir_all(&directory).unwrap();
+        let migration = directory.join("0001_users.sql");
+        let pattern = [format!("{}/*.sql", directory.display())];
+        std::fs::write(&migration, "create table users (name text);").unwrap();
+        let schema = files_hash(&pattern).unwrap();
+        assert_eq!(files_hash(&pattern).unwrap(), schema);
+
+        let cache = Cache::new(&directory.join("cache"));
+        let query = query_hash("select name from users", &Features::default());
+        cache.insert(&query, &schema, &types("name")).unwrap();
+        assert_eq!(
+            cache.get(&query, &files_hash(&pattern).unwrap()),
+            Some(types("name"))
+        );
+
+        std::fs::write(&migration, "create table users (name text not null);").unwrap();
+        let edited = files_hash(&pattern).unwrap();
+        assert_eq!(cache.get(&query, &edited), None);
+        std::fs::write(directory.join("0002_posts.sql"), "create table posts ();").unwrap();
+        let added = files_hash(&pattern).unwrap();
+        assert_ne!(added, edited);
+        assert_eq!(cache.get(&query, &added), None);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
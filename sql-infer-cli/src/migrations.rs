@@ -0,0 +1,91 @@
+use std::{collections::HashSet, error::Error, path::Path};
+
+use sqlx::{
+    Connection, PgConnection,
+    migrate::{Migrate, Migration, Migrator},
+};
+
+use crate::{config::SqlInferConfig, failure::Failure};
+
+/// Apply the pending migrations of the `migrations` directory to the selected database. Applied
+/// migrations are tracked in `_sqlx_migrations`, as `sqlx migrate run` does, so both tools can
+/// be used on the same database.
+pub async fn migrate(config: &SqlInferConfig) -> Result<(), Box<dyn Error>> {
+    let Some(directory) = &config.migrations else {
+        return Ok(());
+    };
+    let mut conn = PgConnection::connect(&config.db_url()?)
+        .await
+        .map_err(Failure::Connection)?;
+    apply(&mut conn, directory).await?;
+    conn.close().await?;
+    Ok(())
+}
+
+/// Migrations of `migrator` not in `applied`, in version order.
+fn pending<'a>(
+    migrator: &'a Migrator,
+    applied: &'a HashSet<i64>,
+) -> impl Iterator<Item = &'a Migration> {
+    migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+}
+
+async fn apply(conn: &mut PgConnection, directory: &Path) -> Result<(), Box<dyn Error>> {
+    let migrator = Migrator::new(directory)
+        .await
+        .map_err(|error| format!("{}: {error}", directory.display()))?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect::<HashSet<_>>();
+    for migration in pending(&migrator, &applied) {
+        tracing::info!(
+            "Applying migration {} {}",
+            migration.version,
+            migration.description
+        );
+    }
+    // Also run when nothing is pending, to check applied migrations were not edited since.
+    migrator
+        .run(&mut *conn)
+        .await
+        .map_err(|error| format!("{}: {error}", directory.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use sqlx::migrate::Migrator;
+
+    use crate::migrations::pending;
+
+    #[tokio::test]
+    async fn pending_migrations_follow_their_version() {
+        let directory =
+            std::env::temp_dir().join(format!("sql-infer-{}-migrations", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        for name in ["10_posts.sql", "2_users.sql", "1_init.sql", "README.md"] {
+            std::fs::write(directory.join(name), "select 1;").unwrap();
+        }
+        let migrator = Migrator::new(directory.as_path()).await.unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let versions = |applied: &[i64]| {
+            let applied = applied.iter().copied().collect::<HashSet<_>>();
+            pending(&migrator, &applied)
+                .map(|migration| migration.version)
+                .collect::<Vec<_>>()
+        };
+        // `10` sorts before `2` as text, migrations are ordered by number.
+        assert_eq!(versions(&[]), [1, 2, 10]);
+        assert_eq!(versions(&[1]), [2, 10]);
+        assert_eq!(versions(&[1, 2, 10]), [] as [i64; 0]);
+    }
+}
@@ -0,0 +1,421 @@
+use std::{borrow::Cow, ops::ControlFlow};
+
+use sql_infer_core::{
+    inference::SqlType,
+    parser::{self, Column, StatementKind},
+};
+use sqlparser::ast::{
+    BinaryOperator, Expr, GroupByExpr, Query, Select, SelectItem, SetExpr, Statement, Visit,
+    Visitor,
+};
+
+use crate::schema::{
+    DbSchema,
+    lint::{LintSetting, Lints},
+};
+
+pub trait QueryLint {
+    /// Key of the lint in the `[lints]` config table.
+    fn name(&self) -> &'static str;
+    /// Messages for every problem found in `statement`, `schema` is only given to lints that
+    /// require it.
+    fn lint(&self, statement: &Statement, schema: Option<&DbSchema>) -> Vec<Cow<'static, str>>;
+    fn requires_schema(&self) -> bool {
+        false
+    }
+}
+
+/// Every query lint that is not allowed along with its level.
+pub fn enabled(lints: &Lints) -> Vec<(LintSetting, Box<dyn QueryLint>)> {
+    let query_lints: [(LintSetting, Box<dyn QueryLint>); 5] = [
+        (lints.select_star, Box::new(SelectStar)),
+        (lints.unbounded_select, Box::new(UnboundedSelect)),
+        (lints.implicit_cross_join, Box::new(ImplicitCrossJoin)),
+        (lints.mismatched_comparison, Box::new(MismatchedComparison)),
+        (lints.ddl, Box::new(Ddl)),
+    ];
+    query_lints
+        .into_iter()
+        .filter(|(setting, _)| !matches!(setting, LintSetting::Allow))
+        .collect()
+}
+
+/// Calls `f` with every `SELECT` in the statement, including both sides of set operations,
+/// CTEs and subqueries.
+fn for_each_select(statement: &Statement, f: impl FnMut(&Select)) {
+    fn visit(set_expr: &SetExpr, f: &mut impl FnMut(&Select)) {
+        match set_expr {
+            SetExpr::Select(select) => f(select),
+            SetExpr::SetOperation { left, right, .. } => {
+                visit(left, f);
+                visit(right, f);
+            }
+            // Nested queries are visited on their own.
+            _ => {}
+        }
+    }
+    struct Selects<F>(F);
+    impl<F: FnMut(&Select)> Visitor for Selects<F> {
+        type Break = ();
+
+        fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+            visit(&query.body, &mut self.0);
+            ControlFlow::Continue(())
+        }
+    }
+    let _ = statement.visit(&mut Selects(f));
+}
+
+pub struct SelectStar;
+
+impl QueryLint for SelectStar {
+    fn name(&self) -> &'static str {
+        "select-star"
+    }
+
+    fn lint(&self, statement: &Statement, _: Option<&DbSchema>) -> Vec<Cow<'static, str>> {
+        let mut wildcard = false;
+        for_each_select(statement, |select| {
+            wildcard |= select.projection.iter().any(|item| {
+                matches!(
+                    item,
+                    SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..)
+                )
+            })
+        });
+        match wildcard {
+            true => vec![Cow::Borrowed(
+                "`SELECT *` output changes whenever the table does, list the columns instead",
+            )],
+            false => vec![],
+        }
+    }
+}
+
+const AGGREGATES: &[&str] = &[
+    "count",
+    "sum",
+    "avg",
+    "min",
+    "max",
+    "bool_and",
+    "bool_or",
+    "array_agg",
+    "string_agg",
+    "json_agg",
+    "jsonb_agg",
+];
+
+fn is_aggregate(item: &SelectItem) -> bool {
+    let (SelectItem::UnnamedExpr(Expr::Function(function))
+    | SelectItem::ExprWithAlias {
+        expr: Expr::Function(function),
+        ..
+    }) = item
+    else {
+        return false;
+    };
+    AGGREGATES.contains(&function.name.to_string().to_lowercase().as_str())
+}
+
+pub struct UnboundedSelect;
+
+impl QueryLint for UnboundedSelect {
+    fn name(&self) -> &'static str {
+        "unbounded-select"
+    }
+
+    fn lint(&self, statement: &Statement, _: Option<&DbSchema>) -> Vec<Cow<'static, str>> {
+        let Statement::Query(query) = statement else {
+            return vec![];
+        };
+        let SetExpr::Select(select) = &*query.body else {
+            return vec![];
+        };
+        let grouped = match &select.group_by {
+            GroupByExpr::All(_) => true,
+            GroupByExpr::Expressions(expressions, _) => !expressions.is_empty(),
+        };
+        let bounded = select.from.is_empty()
+            || select.selection.is_some()
+            || query.limit_clause.is_some()
+            || query.fetch.is_some()
+            || grouped
+            || select.projection.iter().all(is_aggregate);
+        match bounded {
+            true => vec![],
+            false => vec![Cow::Borrowed(
+                "`SELECT` without `WHERE` or `LIMIT` reads every row",
+            )],
+        }
+    }
+}
+
+pub struct ImplicitCrossJoin;
+
+impl QueryLint for ImplicitCrossJoin {
+    fn name(&self) -> &'static str {
+        "implicit-cross-join"
+    }
+
+    fn lint(&self, statement: &Statement, _: Option<&DbSchema>) -> Vec<Cow<'static, str>> {
+        let mut messages = vec![];
+        for_each_select(statement, |select| {
+            if select.from.len() < 2 {
+                return;
+            }
+            let tables = select
+                .from
+                .iter()
+                .map(|table| table.relation.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            messages.push(Cow::Owned(format!(
+                "`FROM {tables}` is an implicit cross join, use `JOIN ... ON` instead"
+            )));
+        });
+        messages
+    }
+}
+
+pub struct Ddl;
+
+impl QueryLint for Ddl {
+    fn name(&self) -> &'static str {
+        "ddl"
+    }
+
+    fn lint(&self, statement: &Statement, _: Option<&DbSchema>) -> Vec<Cow<'static, str>> {
+        match parser::statement_kind(statement) {
+            StatementKind::Ddl => vec![Cow::Borrowed(
+                "the query changes the schema, which belongs in migrations",
+            )],
+            _ => vec![],
+        }
+    }
+}
+
+/// Type of a column that is used to decide whether two columns are compared without a cast.
+fn comparable_type(sql_type: &SqlType) -> SqlType {
+    match sql_type {
+        SqlType::SmallSerial => SqlType::Int2,
+        SqlType::Serial => SqlType::Int4,
+        SqlType::BigSerial => SqlType::Int8,
+        SqlType::Decimal { .. } => SqlType::Decimal {
+            precision: None,
+            precision_radix: None,
+        },
+        SqlType::Char { .. } | SqlType::VarChar { .. } => SqlType::Text,
+        SqlType::Bit { .. } | SqlType::VarBit { .. } => SqlType::VarBit { length: None },
+        SqlType::Array(inner) => SqlType::Array(Box::new(comparable_type(inner))),
+        sql_type => sql_type.clone(),
+    }
+}
+
+fn column_type(column: &Column, schema: &DbSchema) -> Option<(String, SqlType)> {
+    match column {
+        Column::DependsOn { table, column } => {
+            let data_type = &schema.resolve(table)?.column(column)?.data_type;
+            Some((format!("{table}.{column}"), data_type.clone()))
+        }
+        Column::Maybe { column } => column_type(column, schema),
+        Column::Subscript { source, slice } => {
+            let (name, data_type) = column_type(source, schema)?;
+            Some((name, data_type.subscript(*slice)?))
+        }
+        _ => None,
+    }
+}
+
+fn find_mismatches(column: &Column, schema: &DbSchema, mismatches: &mut Vec<Cow<'static, str>>) {
+    let Column::BinaryOp { op, left, right } = column else {
+        return;
+    };
+    let comparison = matches!(
+        op.operator(),
+        Some(
+            BinaryOperator::Eq
+                | BinaryOperator::NotEq
+                | BinaryOperator::Lt
+                | BinaryOperator::LtEq
+                | BinaryOperator::Gt
+                | BinaryOperator::GtEq
+        )
+    );
+    if comparison
+        && let Some((left_name, left_type)) = column_type(left, schema)
+        && let Some((right_name, right_type)) = column_type(right, schema)
+        && comparable_type(&left_type) != comparable_type(&right_type)
+    {
+        mismatches.push(Cow::Owned(format!(
+            "{left_name} ({left_type}) is compared to {right_name} ({right_type}), the implicit cast may prevent index use"
+        )));
+    }
+    find_mismatches(left, schema, mismatches);
+    find_mismatches(right, schema, mismatches);
+}
+
+pub struct MismatchedComparison;
+
+impl QueryLint for MismatchedComparison {
+    fn name(&self) -> &'static str {
+        "mismatched-comparison"
+    }
+
+    fn lint(&self, statement: &Statement, schema: Option<&DbSchema>) -> Vec<Cow<'static, str>> {
+        let Some(schema) = schema else {
+            return vec![];
+        };
+        let mut mismatches = vec![];
+        for condition in parser::find_conditions(statement) {
+            find_mismatches(&condition, schema, &mut mismatches);
+        }
+        mismatches
+    }
+
+    fn requires_schema(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sql_infer_core::{inference::SqlType, parser::to_ast};
+
+    use crate::{
+        query_lint::{
+            Ddl, ImplicitCrossJoin, MismatchedComparison, QueryLint, SelectStar, UnboundedSelect,
+        },
+        schema::{ColumnSchema, DbSchema, SNAPSHOT_VERSION, TableKind, TableSchema},
+    };
+
+    fn table(name: &str, columns: &[(&str, SqlType)]) -> TableSchema {
+        TableSchema {
+            schema: "public".to_string(),
+            name: name.to_string(),
+            kind: TableKind::Table,
+            definition: None,
+            comment: None,
+            columns: columns
+                .iter()
+                .map(|(name, data_type)| ColumnSchema {
+                    name: name.to_string(),
+                    data_type: data_type.clone(),
+                    nullable: false,
+                    default: None,
+                    comment: None,
+                })
+                .collect(),
+            primary_key: None,
+            foreign_keys: vec![],
+            unique_constraints: vec![],
+            check_constraints: vec![],
+            indexes: vec![],
+            estimated_rows: None,
+        }
+    }
+
+    /// Number of problems `lint` finds in `query`.
+    fn count(lint: &dyn QueryLint, query: &str, schema: Option<&DbSchema>) -> usize {
+        let ast = to_ast(query).unwrap();
+        lint.lint(&ast[0], schema).len()
+    }
+
+    #[test]
+    fn select_star() {
+        assert_eq!(count(&SelectStar, "select * from users", None), 1);
+        assert_eq!(count(&SelectStar, "select u.* from users u", None), 1);
+        assert_eq!(
+            count(
+                &SelectStar,
+                "with u as (select * from users) select id from u",
+                None
+            ),
+            1
+        );
+        assert_eq!(
+            count(&SelectStar, "select id from (select * from users) u", None),
+            1
+        );
+        assert_eq!(
+            count(
+                &SelectStar,
+                "select id from users where exists (select * from posts)",
+                None
+            ),
+            1
+        );
+        assert_eq!(count(&SelectStar, "select count(*) from users", None), 0);
+        assert_eq!(count(&SelectStar, "select id, name from users", None), 0);
+    }
+
+    #[test]
+    fn unbounded_select() {
+        assert_eq!(count(&UnboundedSelect, "select id from users", None), 1);
+        assert_eq!(
+            count(&UnboundedSelect, "select id from users where active", None),
+            0
+        );
+        assert_eq!(
+            count(&UnboundedSelect, "select id from users limit 10", None),
+            0
+        );
+        assert_eq!(
+            count(&UnboundedSelect, "select count(*) from users", None),
+            0
+        );
+        assert_eq!(count(&UnboundedSelect, "select 1", None), 0);
+    }
+
+    #[test]
+    fn implicit_cross_join() {
+        assert_eq!(
+            count(&ImplicitCrossJoin, "select 1 from users, posts", None),
+            1
+        );
+        assert_eq!(
+            count(
+                &ImplicitCrossJoin,
+                "with p as (select 1 from users, posts) select * from p",
+                None
+            ),
+            1
+        );
+        assert_eq!(
+            count(
+                &ImplicitCrossJoin,
+                "select 1 from users join posts on posts.user_id = users.id",
+                None
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn mismatched_comparison() {
+        let schema = DbSchema {
+            version: SNAPSHOT_VERSION,
+            tables: vec![
+                table("users", &[("id", SqlType::Int4), ("name", SqlType::Text)]),
+                table(
+                    "posts",
+                    &[
+                        ("user_id", SqlType::Int8),
+                        ("author", SqlType::VarChar { length: Some(20) }),
+                    ],
+                ),
+            ],
+        };
+        let query = "select 1 from users join posts on posts.user_id = users.id";
+        assert_eq!(count(&MismatchedComparison, query, Some(&schema)), 1);
+        assert_eq!(count(&MismatchedComparison, query, None), 0);
+        let query = "select 1 from users join posts on posts.author = users.name";
+        assert_eq!(count(&MismatchedComparison, query, Some(&schema)), 0);
+    }
+
+    #[test]
+    fn ddl() {
+        assert_eq!(count(&Ddl, "create index on users (name)", None), 1);
+        assert_eq!(count(&Ddl, "delete from users where id = 1", None), 0);
+    }
+}
@@ -1,32 +1,715 @@
+pub mod alembic;
+pub mod ddl;
+pub mod diff;
+pub mod docs;
+pub mod erd;
 pub mod lint;
+pub mod pgtap;
 
-use std::{cmp, fmt::Display};
+use std::{cmp, collections::HashMap, error::Error, fmt::Display, path::Path};
 
+use glob::Pattern;
 use serde::{Deserialize, Serialize};
-use sql_infer_core::inference::SqlType;
+use sql_infer_core::{
+    SqlInfer, escape_ident,
+    inference::{Nullability, SqlType},
+};
+use sqlx::{Pool, Postgres, postgres::PgPoolOptions, query, query_scalar};
+
+use crate::{config::SqlInferConfig, failure::Failure};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TableKind {
+    #[default]
+    Table,
+    View,
+    MaterializedView,
+    ForeignTable,
+}
+
+impl TableKind {
+    /// Views and materialized views, whose columns are defined by a query.
+    pub fn is_view(self) -> bool {
+        matches!(self, Self::View | Self::MaterializedView)
+    }
+
+    fn from_relkind(relkind: &str) -> Self {
+        match relkind {
+            "v" => Self::View,
+            "m" => Self::MaterializedView,
+            "f" => Self::ForeignTable,
+            _ => Self::Table,
+        }
+    }
+}
+
+impl Display for TableKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableKind::Table => write!(f, "table"),
+            TableKind::View => write!(f, "view"),
+            TableKind::MaterializedView => write!(f, "materialized view"),
+            TableKind::ForeignTable => write!(f, "foreign table"),
+        }
+    }
+}
+
+/// Schema of relations whose name is shown without it.
+pub const DEFAULT_SCHEMA: &str = "public";
+
+fn default_schema() -> String {
+    DEFAULT_SCHEMA.to_string()
+}
+
+/// `name` qualified by `schema`, unless it is the default one.
+pub fn qualified_name(schema: &str, name: &str) -> String {
+    match schema == DEFAULT_SCHEMA {
+        true => name.to_string(),
+        false => format!("{schema}.{name}"),
+    }
+}
+
+/// [`qualified_name`] with each part escaped, for use in SQL.
+pub fn qualified_ident(schema: &str, name: &str) -> String {
+    match schema == DEFAULT_SCHEMA {
+        true => escape_ident(name),
+        false => format!("{}.{}", escape_ident(schema), escape_ident(name)),
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TableSchema {
+    /// Snapshots taken before schemas were recorded only hold relations of the default one.
+    #[serde(default = "default_schema")]
+    pub schema: String,
     pub name: String,
+    #[serde(default)]
+    pub kind: TableKind,
+    /// Query of a view or materialized view.
+    #[serde(default)]
+    pub definition: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
     pub columns: Vec<ColumnSchema>,
+    /// Columns of the primary key, empty when the table has none. `None` for relations that can
+    /// not have one such as views, or snapshots taken before constraints were recorded.
+    #[serde(default)]
+    pub primary_key: Option<Vec<String>>,
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKey>,
+    #[serde(default)]
+    pub unique_constraints: Vec<UniqueConstraint>,
+    #[serde(default)]
+    pub check_constraints: Vec<CheckConstraint>,
+    #[serde(default)]
+    pub indexes: Vec<Index>,
+    /// Row count estimated by the planner, `None` if the table was never analyzed.
+    #[serde(default)]
+    pub estimated_rows: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub name: String,
+    pub columns: Vec<String>,
+    #[serde(default = "default_schema")]
+    pub referenced_schema: String,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+}
+
+impl ForeignKey {
+    pub fn referenced_name(&self) -> String {
+        qualified_name(&self.referenced_schema, &self.referenced_table)
+    }
+
+    pub fn referenced_ident(&self) -> String {
+        qualified_ident(&self.referenced_schema, &self.referenced_table)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UniqueConstraint {
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckConstraint {
+    pub name: String,
+    /// Definition as printed by Postgres, such as `CHECK ((price > 0))`.
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Index {
+    pub name: String,
+    /// Key columns in index order, expressions are kept as their definition.
+    pub columns: Vec<String>,
+    pub unique: bool,
+    /// Access method such as `btree` or `gin`.
+    #[serde(default = "Index::btree")]
+    pub method: String,
+    /// Condition of a partial index.
+    #[serde(default)]
+    pub predicate: Option<String>,
+}
+
+impl Index {
+    fn btree() -> String {
+        "btree".to_string()
+    }
+
+    /// Whether lookups on `columns` can use this index, which requires them to be its leading
+    /// columns in any order. Partial indexes and methods without equality lookups never cover.
+    pub fn covers(&self, columns: &[String]) -> bool {
+        let equality = match self.method.as_str() {
+            "btree" => true,
+            "hash" => self.columns.len() == 1,
+            _ => false,
+        };
+        equality
+            && self.predicate.is_none()
+            && columns.len() <= self.columns.len()
+            && self.columns[..columns.len()]
+                .iter()
+                .all(|column| columns.contains(column))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ColumnSchema {
     pub name: String,
     pub data_type: SqlType,
     pub nullable: bool,
+    /// Default expression of the column.
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// The `[schema]` config table, restricting which relations are introspected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SchemaFilter {
+    /// Schemas to introspect, every user defined schema when empty.
+    #[serde(default = "Vec::default")]
+    pub schemas: Vec<String>,
+    /// Glob patterns of relations to introspect, every relation when empty. Patterns containing a
+    /// `.` are matched against `schema.name`, others against the name alone.
+    #[serde(default = "Vec::default")]
+    pub include: Vec<String>,
+    /// Glob patterns of relations to skip, taking precedence over `include`.
+    #[serde(default = "Vec::default")]
+    pub exclude: Vec<String>,
+    /// Skip partitions of partitioned tables, keeping the partitioned table itself.
+    #[serde(default = "bool::default")]
+    pub exclude_partitions: bool,
+}
+
+impl SchemaFilter {
+    /// Add the patterns of `other`, as given on the command line, to this filter.
+    pub fn extend(&mut self, other: SchemaFilter) {
+        self.schemas.extend(other.schemas);
+        self.include.extend(other.include);
+        self.exclude.extend(other.exclude);
+        self.exclude_partitions |= other.exclude_partitions;
+    }
+
+    /// Predicate deciding whether the relation `name` in `schema` is introspected.
+    fn matcher(&self) -> Result<impl Fn(&str, &str, bool) -> bool + '_, Box<dyn Error>> {
+        let compile = |globs: &[String]| {
+            globs
+                .iter()
+                .map(|glob| Pattern::new(glob))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let include = compile(&self.include)?;
+        let exclude = compile(&self.exclude)?;
+        Ok(move |schema: &str, name: &str, partition: bool| {
+            let qualified = format!("{schema}.{name}");
+            let matches = |pattern: &Pattern| match pattern.as_str().contains('.') {
+                true => pattern.matches(&qualified),
+                false => pattern.matches(name),
+            };
+            (self.schemas.is_empty() || self.schemas.iter().any(|allowed| allowed == schema))
+                && (include.is_empty() || include.iter().any(matches))
+                && !exclude.iter().any(matches)
+                && !(self.exclude_partitions && partition)
+        })
+    }
 }
 
+/// Version of the snapshots written by `schema export`, raised when the meaning of a snapshot
+/// changes. Snapshots before version 1 typed `jsonb` columns as `json`.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DbSchema {
+    /// Version of the snapshot the schema was read from, see [`SNAPSHOT_VERSION`].
+    #[serde(default)]
+    pub version: u32,
     pub tables: Vec<TableSchema>,
 }
 
+async fn column_defaults(
+    pool: &Pool<Postgres>,
+    schema: &str,
+    table: &str,
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let records = query!(
+        r#"SELECT
+    column_name AS "column_name!",
+    column_default AS "column_default!"
+FROM
+    information_schema.columns
+WHERE
+    table_schema = $1
+    AND table_name = $2
+    AND column_default IS NOT NULL"#,
+        schema,
+        table
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(records
+        .into_iter()
+        .map(|record| (record.column_name, record.column_default))
+        .collect())
+}
+
+async fn column_comments(
+    pool: &Pool<Postgres>,
+    schema: &str,
+    table: &str,
+) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let records = query!(
+        r#"SELECT
+    att.attname::text AS "column_name!",
+    col_description(rel.oid, att.attnum) AS "comment!"
+FROM
+    pg_attribute att
+    JOIN pg_class rel ON rel.oid = att.attrelid
+    JOIN pg_namespace nsp ON nsp.oid = rel.relnamespace
+WHERE
+    nsp.nspname = $1
+    AND rel.relname = $2
+    AND att.attnum > 0
+    AND NOT att.attisdropped
+    AND col_description(rel.oid, att.attnum) IS NOT NULL"#,
+        schema,
+        table
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(records
+        .into_iter()
+        .map(|record| (record.column_name, record.comment))
+        .collect())
+}
+
+/// Declared length of the `char`, `varchar`, `bit` and `varbit` columns that have one, read from
+/// the catalog as inference only reports it with `precise-output-datatypes`.
+async fn column_lengths(
+    pool: &Pool<Postgres>,
+    schema: &str,
+    table: &str,
+) -> Result<HashMap<String, u32>, Box<dyn Error>> {
+    let records = query!(
+        r#"SELECT
+    att.attname::text AS "column_name!",
+    CASE
+        WHEN typ.typname IN ('bpchar', 'varchar') THEN att.atttypmod - 4
+        ELSE att.atttypmod
+    END AS "length!"
+FROM
+    pg_attribute att
+    JOIN pg_class rel ON rel.oid = att.attrelid
+    JOIN pg_namespace nsp ON nsp.oid = rel.relnamespace
+    JOIN pg_type typ ON typ.oid = att.atttypid
+WHERE
+    nsp.nspname = $1
+    AND rel.relname = $2
+    AND att.attnum > 0
+    AND NOT att.attisdropped
+    AND typ.typname IN ('bpchar', 'varchar', 'bit', 'varbit')
+    AND att.atttypmod > 0"#,
+        schema,
+        table
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(records
+        .into_iter()
+        .filter_map(|record| Some((record.column_name, u32::try_from(record.length).ok()?)))
+        .collect())
+}
+
+async fn estimated_rows(
+    pool: &Pool<Postgres>,
+    schema: &str,
+    table: &str,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    let reltuples = query_scalar!(
+        r#"SELECT
+    c.reltuples::int8 AS "reltuples!"
+FROM
+    pg_class c
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE
+    n.nspname = $1
+    AND c.relname = $2"#,
+        schema,
+        table
+    )
+    .fetch_optional(pool)
+    .await?;
+    // Tables that were never analyzed have a negative estimate.
+    Ok(reltuples.and_then(|reltuples| u64::try_from(reltuples).ok()))
+}
+
+async fn primary_key(
+    pool: &Pool<Postgres>,
+    schema: &str,
+    table: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let columns = query!(
+        r#"SELECT
+    kcu.column_name AS "column_name!"
+FROM
+    information_schema.table_constraints tc
+    JOIN information_schema.key_column_usage kcu
+        ON kcu.constraint_schema = tc.constraint_schema
+        AND kcu.constraint_name = tc.constraint_name
+WHERE
+    tc.constraint_type = 'PRIMARY KEY'
+    AND tc.table_schema = $1
+    AND tc.table_name = $2
+ORDER BY
+    kcu.ordinal_position"#,
+        schema,
+        table
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(columns
+        .into_iter()
+        .map(|record| record.column_name)
+        .collect())
+}
+
+async fn foreign_keys(
+    pool: &Pool<Postgres>,
+    schema: &str,
+    table: &str,
+) -> Result<Vec<ForeignKey>, Box<dyn Error>> {
+    let records = query!(
+        r#"SELECT
+    con.conname::text AS "name!",
+    refnsp.nspname::text AS "referenced_schema!",
+    ref.relname::text AS "referenced_table!",
+    ARRAY(
+        SELECT att.attname::text
+        FROM unnest(con.conkey) WITH ORDINALITY AS k(attnum, n)
+            JOIN pg_attribute att ON att.attrelid = con.conrelid AND att.attnum = k.attnum
+        ORDER BY k.n
+    ) AS "columns!",
+    ARRAY(
+        SELECT att.attname::text
+        FROM unnest(con.confkey) WITH ORDINALITY AS k(attnum, n)
+            JOIN pg_attribute att ON att.attrelid = con.confrelid AND att.attnum = k.attnum
+        ORDER BY k.n
+    ) AS "referenced_columns!"
+FROM
+    pg_constraint con
+    JOIN pg_class rel ON rel.oid = con.conrelid
+    JOIN pg_namespace nsp ON nsp.oid = rel.relnamespace
+    JOIN pg_class ref ON ref.oid = con.confrelid
+    JOIN pg_namespace refnsp ON refnsp.oid = ref.relnamespace
+WHERE
+    con.contype = 'f'
+    AND nsp.nspname = $1
+    AND rel.relname = $2
+ORDER BY
+    con.conname"#,
+        schema,
+        table
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(records
+        .into_iter()
+        .map(|record| ForeignKey {
+            name: record.name,
+            columns: record.columns,
+            referenced_schema: record.referenced_schema,
+            referenced_table: record.referenced_table,
+            referenced_columns: record.referenced_columns,
+        })
+        .collect())
+}
+
+async fn constraints(
+    pool: &Pool<Postgres>,
+    schema: &str,
+    table: &str,
+) -> Result<(Vec<UniqueConstraint>, Vec<CheckConstraint>), Box<dyn Error>> {
+    let records = query!(
+        r#"SELECT
+    con.conname::text AS "name!",
+    con.contype = 'u' AS "unique!",
+    pg_get_constraintdef(con.oid, true) AS "definition!",
+    ARRAY(
+        SELECT att.attname::text
+        FROM unnest(con.conkey) WITH ORDINALITY AS k(attnum, n)
+            JOIN pg_attribute att ON att.attrelid = con.conrelid AND att.attnum = k.attnum
+        ORDER BY k.n
+    ) AS "columns!"
+FROM
+    pg_constraint con
+    JOIN pg_class rel ON rel.oid = con.conrelid
+    JOIN pg_namespace nsp ON nsp.oid = rel.relnamespace
+WHERE
+    con.contype IN ('u', 'c')
+    AND nsp.nspname = $1
+    AND rel.relname = $2
+ORDER BY
+    con.conname"#,
+        schema,
+        table
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut unique_constraints = vec![];
+    let mut check_constraints = vec![];
+    for record in records {
+        match record.unique {
+            true => unique_constraints.push(UniqueConstraint {
+                name: record.name,
+                columns: record.columns,
+            }),
+            false => check_constraints.push(CheckConstraint {
+                name: record.name,
+                definition: record.definition,
+            }),
+        }
+    }
+    Ok((unique_constraints, check_constraints))
+}
+
+async fn indexes(
+    pool: &Pool<Postgres>,
+    schema: &str,
+    table: &str,
+) -> Result<Vec<Index>, Box<dyn Error>> {
+    let records = query!(
+        r#"SELECT
+    idx.relname::text AS "name!",
+    ix.indisunique AS "unique!",
+    am.amname::text AS "method!",
+    pg_get_expr(ix.indpred, ix.indrelid, true) AS predicate,
+    ARRAY(
+        SELECT coalesce(att.attname::text, pg_get_indexdef(ix.indexrelid, k.n::int, true))
+        FROM unnest(ix.indkey::int2[]) WITH ORDINALITY AS k(attnum, n)
+            LEFT JOIN pg_attribute att ON att.attrelid = ix.indrelid AND att.attnum = k.attnum
+        WHERE k.n <= ix.indnkeyatts
+        ORDER BY k.n
+    ) AS "columns!"
+FROM
+    pg_index ix
+    JOIN pg_class idx ON idx.oid = ix.indexrelid
+    JOIN pg_am am ON am.oid = idx.relam
+    JOIN pg_class rel ON rel.oid = ix.indrelid
+    JOIN pg_namespace nsp ON nsp.oid = rel.relnamespace
+WHERE
+    nsp.nspname = $1
+    AND rel.relname = $2
+ORDER BY
+    idx.relname"#,
+        schema,
+        table
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(records
+        .into_iter()
+        .map(|record| Index {
+            name: record.name,
+            columns: record.columns,
+            unique: record.unique,
+            method: record.method,
+            predicate: record.predicate,
+        })
+        .collect())
+}
+
+impl DbSchema {
+    /// Introspect the database configured by `config`.
+    pub async fn from_database(config: &SqlInferConfig) -> Result<Self, Box<dyn Error>> {
+        let sql_infer = config.experimental_features.sql_infer();
+        Self::from_url(&config.db_url()?, &sql_infer, &config.schema).await
+    }
+
+    /// Introspect the database at `url`.
+    pub async fn from_url(
+        url: &str,
+        sql_infer: &SqlInfer,
+        filter: &SchemaFilter,
+    ) -> Result<Self, Box<dyn Error>> {
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(url)
+            .await
+            .map_err(Failure::Connection)?;
+        Self::introspect(&pool, sql_infer, filter).await
+    }
+
+    /// Read a JSON snapshot as written by `schema export`.
+    pub fn from_snapshot(snapshot: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = std::fs::read(snapshot).map_err(|error| {
+            format!(
+                "encountered '{error}' attempting to read {}",
+                snapshot.display()
+            )
+        })?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// Introspect every user defined table in the database that passes `filter`.
+    pub async fn introspect(
+        pool: &Pool<Postgres>,
+        sql_infer: &SqlInfer,
+        filter: &SchemaFilter,
+    ) -> Result<Self, Box<dyn Error>> {
+        let matches = filter.matcher()?;
+        // Ordered by creation so views come after the relations they select from.
+        let tables = query!(
+            r#"SELECT
+    nsp.nspname::text AS "schema!",
+    rel.relname::text AS "name!",
+    rel.relkind::text AS "relkind!",
+    rel.relispartition AS "partition!",
+    CASE WHEN rel.relkind IN ('v', 'm') THEN pg_get_viewdef(rel.oid, true) END AS definition,
+    obj_description(rel.oid, 'pg_class') AS comment
+FROM
+    pg_class rel
+    JOIN pg_namespace nsp ON nsp.oid = rel.relnamespace
+WHERE
+    rel.relkind IN ('r', 'p', 'v', 'm', 'f')
+    AND nsp.nspname NOT IN ('pg_catalog', 'information_schema')
+    AND nsp.nspname NOT LIKE 'pg_toast%'
+ORDER BY
+    rel.oid"#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut table_schemas = vec![];
+        for record in tables {
+            if !matches(&record.schema, &record.name, record.partition) {
+                continue;
+            }
+            let (schema, table) = (record.schema, record.name);
+            let kind = TableKind::from_relkind(&record.relkind);
+            let definition = record
+                .definition
+                .map(|definition| definition.trim().trim_end_matches(';').to_string());
+            let types = sql_infer.infer_table_types(pool, &schema, &table).await?;
+            let mut defaults = column_defaults(pool, &schema, &table).await?;
+            let mut comments = column_comments(pool, &schema, &table).await?;
+            let lengths = column_lengths(pool, &schema, &table).await?;
+            let mut columns = vec![];
+            for mut col in types.output {
+                if let SqlType::Char { length }
+                | SqlType::VarChar { length }
+                | SqlType::Bit { length }
+                | SqlType::VarBit { length } = &mut col.sql_type
+                    && length.is_none()
+                {
+                    *length = lengths.get(&col.name).copied();
+                }
+                columns.push(ColumnSchema {
+                    default: defaults.remove(&col.name),
+                    comment: comments.remove(&col.name),
+                    name: col.name,
+                    data_type: col.sql_type,
+                    nullable: col.nullable == Nullability::True,
+                });
+            }
+            let primary_key = match kind == TableKind::Table {
+                true => Some(primary_key(pool, &schema, &table).await?),
+                false => None,
+            };
+            let foreign_keys = foreign_keys(pool, &schema, &table).await?;
+            let (unique_constraints, check_constraints) =
+                constraints(pool, &schema, &table).await?;
+            let indexes = indexes(pool, &schema, &table).await?;
+            let estimated_rows = estimated_rows(pool, &schema, &table).await?;
+            table_schemas.push(TableSchema {
+                schema,
+                name: table,
+                kind,
+                definition,
+                comment: record.comment,
+                columns,
+                primary_key,
+                foreign_keys,
+                unique_constraints,
+                check_constraints,
+                indexes,
+                estimated_rows,
+            });
+        }
+        Ok(Self {
+            version: SNAPSHOT_VERSION,
+            tables: table_schemas,
+        })
+    }
+
+    pub fn table(&self, schema: &str, name: &str) -> Option<&TableSchema> {
+        self.tables
+            .iter()
+            .find(|table| table.schema == schema && table.name == name)
+    }
+
+    /// Table referenced as `reference` in a query, `schema.name` or a name alone. A name alone
+    /// is looked up in the default schema first, then in any other.
+    pub fn resolve(&self, reference: &str) -> Option<&TableSchema> {
+        match reference.split_once('.') {
+            Some((schema, name)) => self.table(schema, name),
+            None => self
+                .table(DEFAULT_SCHEMA, reference)
+                .or_else(|| self.tables.iter().find(|table| table.name == reference)),
+        }
+    }
+}
+
+impl TableSchema {
+    pub fn qualified_name(&self) -> String {
+        qualified_name(&self.schema, &self.name)
+    }
+
+    pub fn qualified_ident(&self) -> String {
+        qualified_ident(&self.schema, &self.name)
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnSchema> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
 impl Display for DbSchema {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for table in &self.tables {
-            writeln!(f, "{}", table.name)?;
+            match table.kind {
+                TableKind::Table => writeln!(f, "{}", table.qualified_name())?,
+                kind => writeln!(f, "{} ({kind})", table.qualified_name())?,
+            }
+            if let Some(comment) = &table.comment {
+                writeln!(f, "-- {comment}")?;
+            }
             let column_names = table
                 .columns
                 .iter()
@@ -59,8 +742,76 @@ impl Display for DbSchema {
                 .join("  |  ");
             writeln!(f, "{column_names}")?;
             writeln!(f, "{type_names}")?;
+            for column in &table.columns {
+                if let Some(comment) = &column.comment {
+                    writeln!(f, "{}: {comment}", column.name)?;
+                }
+            }
+            if let Some(primary_key) = &table.primary_key
+                && !primary_key.is_empty()
+            {
+                writeln!(f, "primary key ({})", primary_key.join(", "))?;
+            }
+            for foreign_key in &table.foreign_keys {
+                writeln!(
+                    f,
+                    "foreign key {} ({}) references {} ({})",
+                    foreign_key.name,
+                    foreign_key.columns.join(", "),
+                    foreign_key.referenced_name(),
+                    foreign_key.referenced_columns.join(", ")
+                )?;
+            }
+            for unique in &table.unique_constraints {
+                writeln!(f, "unique {} ({})", unique.name, unique.columns.join(", "))?;
+            }
+            for check in &table.check_constraints {
+                writeln!(f, "check {} {}", check.name, check.definition)?;
+            }
+            for index in &table.indexes {
+                write!(
+                    f,
+                    "{}index {} using {} ({})",
+                    match index.unique {
+                        true => "unique ",
+                        false => "",
+                    },
+                    index.name,
+                    index.method,
+                    index.columns.join(", ")
+                )?;
+                if let Some(predicate) = &index.predicate {
+                    write!(f, " where {predicate}")?;
+                }
+                writeln!(f)?;
+            }
             writeln!(f)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::Index;
+
+    fn index(columns: &[&str], method: &str, predicate: Option<&str>) -> Index {
+        Index {
+            name: "index".to_string(),
+            columns: columns.iter().map(ToString::to_string).collect(),
+            unique: false,
+            method: method.to_string(),
+            predicate: predicate.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn index_covers_leading_columns() {
+        let columns = ["b".to_string(), "a".to_string()];
+        assert!(index(&["a", "b", "c"], "btree", None).covers(&columns));
+        assert!(!index(&["a", "c", "b"], "btree", None).covers(&columns));
+        assert!(!index(&["a", "b"], "gin", None).covers(&columns));
+        assert!(!index(&["a", "b"], "btree", Some("a > 0")).covers(&columns));
+        assert!(index(&["a"], "hash", None).covers(&columns[1..]));
+    }
+}
@@ -0,0 +1,371 @@
+use sql_infer_core::inference::SqlType;
+
+use crate::{
+    manifest::sha256,
+    schema::{
+        ColumnSchema, DEFAULT_SCHEMA, DbSchema, TableKind, TableSchema, ddl::create_relation,
+        diff::Change,
+    },
+};
+
+/// Python string literal of `value`, JSON escapes being valid in Python.
+fn string(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+fn strings(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|value| string(value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// SQLAlchemy type of `sql_type`, as Alembic autogenerate writes it.
+fn sa_type(sql_type: &SqlType) -> String {
+    let length = |length: &Option<u32>| match length {
+        Some(length) => format!("length={length}"),
+        None => String::new(),
+    };
+    match sql_type {
+        SqlType::Bool => "sa.Boolean()".to_string(),
+        SqlType::Int2 | SqlType::SmallSerial => "sa.SmallInteger()".to_string(),
+        SqlType::Int4 | SqlType::Serial => "sa.Integer()".to_string(),
+        SqlType::Int8 | SqlType::BigSerial => "sa.BigInteger()".to_string(),
+        // The scale is not tracked, so the precision alone would drop it.
+        SqlType::Decimal { .. } => "sa.Numeric()".to_string(),
+        SqlType::Timestamp { tz } => format!("sa.DateTime(timezone={})", py_bool(*tz)),
+        SqlType::Date => "sa.Date()".to_string(),
+        SqlType::Time { tz } => format!("sa.Time(timezone={})", py_bool(*tz)),
+        SqlType::Interval => "postgresql.INTERVAL()".to_string(),
+        SqlType::Char { length: size } => format!("sa.CHAR({})", length(size)),
+        SqlType::VarChar { length: size } => format!("sa.String({})", length(size)),
+        SqlType::Bit { length: size } => format!("postgresql.BIT({})", length(size)),
+        SqlType::VarBit { length: size } => match size {
+            Some(size) => format!("postgresql.BIT(length={size}, varying=True)"),
+            None => "postgresql.BIT(varying=True)".to_string(),
+        },
+        SqlType::Text => "sa.Text()".to_string(),
+        SqlType::Json => "postgresql.JSON()".to_string(),
+        SqlType::Jsonb => "postgresql.JSONB()".to_string(),
+        SqlType::Float4 => "sa.REAL()".to_string(),
+        SqlType::Float8 => "postgresql.DOUBLE_PRECISION()".to_string(),
+        // The type is expected to exist already, Alembic would create it along with the table.
+        SqlType::Enum { name, tags } => format!(
+            "postgresql.ENUM({}, name={}, create_type=False)",
+            strings(tags),
+            string(name)
+        ),
+        SqlType::Array(inner) => format!("postgresql.ARRAY({})", sa_type(inner)),
+        SqlType::Record(_) | SqlType::Unknown => "sa.types.NullType()".to_string(),
+    }
+}
+
+fn py_bool(value: bool) -> &'static str {
+    match value {
+        true => "True",
+        false => "False",
+    }
+}
+
+/// Arguments of an operation on `table`, naming its `schema` unless it is the default one.
+fn arguments(schema: &str, table: &str, mut arguments: Vec<String>) -> String {
+    arguments.insert(0, string(table));
+    if schema != DEFAULT_SCHEMA {
+        arguments.push(format!("schema={}", string(schema)));
+    }
+    arguments.join(", ")
+}
+
+fn column(column: &ColumnSchema) -> String {
+    let mut arguments = vec![
+        string(&column.name),
+        sa_type(&column.data_type),
+        format!("nullable={}", py_bool(column.nullable)),
+    ];
+    // Sequence defaults come with the serial columns Alembic creates for integer primary keys.
+    if let Some(default) = &column.default
+        && !default.starts_with("nextval(")
+    {
+        arguments.push(format!("server_default=sa.text({})", string(default)));
+    }
+    format!("sa.Column({})", arguments.join(", "))
+}
+
+fn create(table: &TableSchema) -> String {
+    if table.kind != TableKind::Table {
+        return format!("op.execute({})", string(&create_relation(table)));
+    }
+    let mut arguments = table.columns.iter().map(column).collect::<Vec<_>>();
+    if let Some(primary_key) = &table.primary_key
+        && !primary_key.is_empty()
+    {
+        arguments.push(format!("sa.PrimaryKeyConstraint({})", strings(primary_key)));
+    }
+    for unique in &table.unique_constraints {
+        arguments.push(format!(
+            "sa.UniqueConstraint({}, name={})",
+            strings(&unique.columns),
+            string(&unique.name)
+        ));
+    }
+    if table.schema != DEFAULT_SCHEMA {
+        arguments.push(format!("schema={}", string(&table.schema)));
+    }
+    format!(
+        "op.create_table(\n        {},\n        {},\n    )",
+        string(&table.name),
+        arguments.join(",\n        ")
+    )
+}
+
+fn drop(table: &TableSchema) -> String {
+    match table.kind {
+        TableKind::Table => format!(
+            "op.drop_table({})",
+            arguments(&table.schema, &table.name, vec![])
+        ),
+        kind => format!(
+            "op.execute({})",
+            string(&format!("drop {kind} {}", table.qualified_ident()))
+        ),
+    }
+}
+
+fn alter_type(schema: &str, table: &str, column: &str, from: &SqlType, to: &SqlType) -> String {
+    let arguments = arguments(
+        schema,
+        table,
+        vec![
+            string(column),
+            format!("type_={}", sa_type(to)),
+            format!("existing_type={}", sa_type(from)),
+        ],
+    );
+    format!("op.alter_column({arguments})")
+}
+
+fn alter_nullable(schema: &str, table: &str, column: &str, nullable: bool) -> String {
+    let arguments = arguments(
+        schema,
+        table,
+        vec![string(column), format!("nullable={}", py_bool(nullable))],
+    );
+    format!("op.alter_column({arguments})")
+}
+
+fn add_column(schema: &str, table: &str, added: &ColumnSchema) -> String {
+    format!(
+        "op.add_column({})",
+        arguments(schema, table, vec![column(added)])
+    )
+}
+
+fn drop_column(schema: &str, table: &str, dropped: &ColumnSchema) -> String {
+    format!(
+        "op.drop_column({})",
+        arguments(schema, table, vec![string(&dropped.name)])
+    )
+}
+
+/// Operations of `upgrade` and `downgrade` applying `changes`, as produced by
+/// [`super::diff::diff`] from `old` to `new`, and reverting them.
+fn operations(changes: &[Change], old: &DbSchema, new: &DbSchema) -> (Vec<String>, Vec<String>) {
+    let mut upgrade = vec![];
+    let mut downgrade = vec![];
+    let mut created = vec![];
+    for change in changes {
+        let (up, down) = match change {
+            Change::TableAdded { schema, table } => {
+                let Some(table) = new.table(schema, table) else {
+                    continue;
+                };
+                created.push(table);
+                (create(table), drop(table))
+            }
+            Change::TableRemoved { schema, table } => {
+                let Some(table) = old.table(schema, table) else {
+                    continue;
+                };
+                (drop(table), create(table))
+            }
+            Change::ColumnAdded {
+                schema,
+                table,
+                column: added,
+            } => {
+                if created
+                    .iter()
+                    .any(|created| created.schema == *schema && created.name == *table)
+                {
+                    continue;
+                }
+                (
+                    add_column(schema, table, added),
+                    drop_column(schema, table, added),
+                )
+            }
+            Change::ColumnRemoved {
+                schema,
+                table,
+                column: dropped,
+            } => (
+                drop_column(schema, table, dropped),
+                add_column(schema, table, dropped),
+            ),
+            Change::ColumnRetyped {
+                schema,
+                table,
+                column,
+                from,
+                to,
+            } => (
+                alter_type(schema, table, column, from, to),
+                alter_type(schema, table, column, to, from),
+            ),
+            Change::NullabilityChanged {
+                schema,
+                table,
+                column,
+                nullable,
+            } => (
+                alter_nullable(schema, table, column, *nullable),
+                alter_nullable(schema, table, column, !nullable),
+            ),
+        };
+        upgrade.push(up);
+        downgrade.push(down);
+    }
+    downgrade.reverse();
+    (upgrade, downgrade)
+}
+
+fn body(operations: &[String]) -> String {
+    match operations.is_empty() {
+        true => "    pass\n".to_string(),
+        false => operations
+            .iter()
+            .map(|operation| format!("    {operation}\n"))
+            .collect(),
+    }
+}
+
+/// An Alembic revision file applying `changes` from the `old` to the `new` schema, following
+/// the `down_revision` revision.
+pub fn revision(
+    changes: &[Change],
+    old: &DbSchema,
+    new: &DbSchema,
+    message: &str,
+    down_revision: Option<&str>,
+) -> String {
+    let (upgrade, downgrade) = operations(changes, old, new);
+    let (upgrade, downgrade) = (body(&upgrade), body(&downgrade));
+    let down_revision = match down_revision {
+        Some(down_revision) => string(down_revision),
+        None => "None".to_string(),
+    };
+    // Derived from the content so the same changes keep the same identifier.
+    let revision = sha256(&format!("{message}\n{down_revision}\n{upgrade}{downgrade}"));
+    let revision = &revision[..12];
+    format!(
+        r#""""{message}
+
+Revision ID: {revision}
+Revises: {revises}
+
+Generated by sql-infer from the schema differences, review it before applying.
+"""
+
+from typing import Sequence, Union
+
+import sqlalchemy as sa
+from alembic import op
+from sqlalchemy.dialects import postgresql
+
+revision: str = "{revision}"
+down_revision: Union[str, None] = {down_revision}
+branch_labels: Union[str, Sequence[str], None] = None
+depends_on: Union[str, Sequence[str], None] = None
+
+
+def upgrade() -> None:
+{upgrade}
+
+def downgrade() -> None:
+{downgrade}"#,
+        message = message.replace("\"\"\"", "'''"),
+        revises = down_revision.trim_matches('"'),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use sql_infer_core::inference::SqlType;
+
+    use crate::schema::{
+        ColumnSchema, DbSchema, SNAPSHOT_VERSION, TableKind, TableSchema, alembic::operations,
+        diff::diff,
+    };
+
+    fn table(columns: Vec<ColumnSchema>) -> DbSchema {
+        DbSchema {
+            version: SNAPSHOT_VERSION,
+            tables: vec![TableSchema {
+                schema: "public".to_string(),
+                name: "users".to_string(),
+                kind: TableKind::Table,
+                definition: None,
+                comment: None,
+                columns,
+                primary_key: Some(vec![]),
+                foreign_keys: vec![],
+                unique_constraints: vec![],
+                check_constraints: vec![],
+                indexes: vec![],
+                estimated_rows: None,
+            }],
+        }
+    }
+
+    fn column(name: &str, data_type: SqlType, nullable: bool) -> ColumnSchema {
+        ColumnSchema {
+            name: name.to_string(),
+            data_type,
+            nullable,
+            default: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn changes_are_reverted_in_reverse() {
+        let old = table(vec![
+            column("name", SqlType::Text, true),
+            column("age", SqlType::Int4, true),
+        ]);
+        let new = table(vec![
+            column("name", SqlType::VarChar { length: Some(50) }, false),
+            column("email", SqlType::Text, true),
+        ]);
+        let (upgrade, downgrade) = operations(&diff(&old, &new), &old, &new);
+        assert_eq!(
+            upgrade,
+            [
+                r#"op.drop_column("users", "age")"#,
+                r#"op.alter_column("users", "name", type_=sa.String(length=50), existing_type=sa.Text())"#,
+                r#"op.alter_column("users", "name", nullable=False)"#,
+                r#"op.add_column("users", sa.Column("email", sa.Text(), nullable=True))"#,
+            ]
+        );
+        assert_eq!(
+            downgrade,
+            [
+                r#"op.drop_column("users", "email")"#,
+                r#"op.alter_column("users", "name", nullable=True)"#,
+                r#"op.alter_column("users", "name", type_=sa.Text(), existing_type=sa.String(length=50))"#,
+                r#"op.add_column("users", sa.Column("age", sa.Integer(), nullable=True))"#,
+            ]
+        );
+    }
+}
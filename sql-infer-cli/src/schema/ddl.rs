@@ -0,0 +1,433 @@
+use sql_infer_core::{escape_ident, inference::SqlType};
+
+use crate::schema::{
+    ColumnSchema, DEFAULT_SCHEMA, DbSchema, TableKind, TableSchema, diff::Change, qualified_ident,
+};
+
+/// PostgreSQL spelling of a datatype, suitable for use in DDL.
+pub fn sql_type_name(sql_type: &SqlType) -> String {
+    match sql_type {
+        SqlType::Bool => "boolean".to_string(),
+        SqlType::Int2 => "smallint".to_string(),
+        SqlType::Int4 => "integer".to_string(),
+        SqlType::Int8 => "bigint".to_string(),
+        SqlType::SmallSerial => "smallserial".to_string(),
+        SqlType::Serial => "serial".to_string(),
+        SqlType::BigSerial => "bigserial".to_string(),
+        // The scale is not tracked so only the unconstrained type can be reproduced faithfully.
+        SqlType::Decimal { .. } => "numeric".to_string(),
+        SqlType::Timestamp { tz: true } => "timestamptz".to_string(),
+        SqlType::Timestamp { tz: false } => "timestamp".to_string(),
+        SqlType::Date => "date".to_string(),
+        SqlType::Time { tz: true } => "timetz".to_string(),
+        SqlType::Time { tz: false } => "time".to_string(),
+        SqlType::Interval => "interval".to_string(),
+        SqlType::Char {
+            length: Some(length),
+        } => format!("char({length})"),
+        SqlType::Char { length: None } => "char".to_string(),
+        SqlType::VarChar {
+            length: Some(length),
+        } => format!("varchar({length})"),
+        SqlType::VarChar { length: None } => "varchar".to_string(),
+        SqlType::Bit {
+            length: Some(length),
+        } => format!("bit({length})"),
+        SqlType::Bit { length: None } => "bit".to_string(),
+        SqlType::VarBit {
+            length: Some(length),
+        } => format!("varbit({length})"),
+        SqlType::VarBit { length: None } => "varbit".to_string(),
+        SqlType::Text => "text".to_string(),
+        SqlType::Json => "json".to_string(),
+        SqlType::Jsonb => "jsonb".to_string(),
+        SqlType::Float4 => "real".to_string(),
+        SqlType::Float8 => "double precision".to_string(),
+        SqlType::Enum { name, .. } => escape_ident(name),
+        SqlType::Array(inner) => format!("{}[]", sql_type_name(inner)),
+        SqlType::Record(_) => "record".to_string(),
+        SqlType::Unknown => "unknown".to_string(),
+    }
+}
+
+fn column_definition(column: &ColumnSchema) -> String {
+    let sequence = column
+        .default
+        .as_ref()
+        .is_some_and(|default| default.starts_with("nextval("));
+    // Columns backed by a sequence are recreated as serial, which creates the sequence too.
+    let (data_type, default) = match (&column.data_type, sequence) {
+        (SqlType::Int2, true) => (SqlType::SmallSerial, None),
+        (SqlType::Int4, true) => (SqlType::Serial, None),
+        (SqlType::Int8, true) => (SqlType::BigSerial, None),
+        (SqlType::SmallSerial | SqlType::Serial | SqlType::BigSerial, _) => {
+            (column.data_type.clone(), None)
+        }
+        (data_type, _) => (data_type.clone(), column.default.as_ref()),
+    };
+    let mut definition = format!(
+        "{} {}",
+        escape_ident(&column.name),
+        sql_type_name(&data_type)
+    );
+    if let Some(default) = default {
+        definition.push_str(&format!(" default {default}"));
+    }
+    if !column.nullable {
+        definition.push_str(" not null");
+    }
+    definition
+}
+
+pub fn create_table(table: &TableSchema) -> String {
+    let mut definitions = table
+        .columns
+        .iter()
+        .map(|column| format!("    {}", column_definition(column)))
+        .collect::<Vec<_>>();
+    if let Some(primary_key) = &table.primary_key
+        && !primary_key.is_empty()
+    {
+        definitions.push(format!("    primary key ({})", column_list(primary_key)));
+    }
+    for unique in &table.unique_constraints {
+        definitions.push(format!(
+            "    constraint {} unique ({})",
+            escape_ident(&unique.name),
+            column_list(&unique.columns)
+        ));
+    }
+    for check in &table.check_constraints {
+        definitions.push(format!(
+            "    constraint {} {}",
+            escape_ident(&check.name),
+            check.definition
+        ));
+    }
+    let columns = definitions.join(",\n");
+    format!("create table {} (\n{columns}\n);", table.qualified_ident())
+}
+
+fn enum_types<'a>(sql_type: &'a SqlType, enums: &mut Vec<(&'a str, &'a [String])>) {
+    match sql_type {
+        SqlType::Enum { name, tags } if !enums.iter().any(|(existing, _)| existing == name) => {
+            enums.push((name, tags));
+        }
+        SqlType::Array(inner) => enum_types(inner, enums),
+        _ => {}
+    }
+}
+
+pub fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn column_list(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|column| escape_ident(column))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Statements recreating the whole schema: schemas and enum types, then tables and views with their indexes,
+/// then foreign keys so tables can reference each other in any order.
+pub fn schema(db: &DbSchema) -> Vec<String> {
+    let mut enums = vec![];
+    for table in &db.tables {
+        for column in &table.columns {
+            enum_types(&column.data_type, &mut enums);
+        }
+    }
+    let mut statements = vec![];
+    for table in &db.tables {
+        let create = format!(
+            "create schema if not exists {};",
+            escape_ident(&table.schema)
+        );
+        if table.schema != DEFAULT_SCHEMA && !statements.contains(&create) {
+            statements.push(create);
+        }
+    }
+    statements.extend(enums.into_iter().map(|(name, tags)| {
+        let tags = tags
+            .iter()
+            .map(|tag| literal(tag))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("create type {} as enum ({tags});", escape_ident(name))
+    }));
+    for table in &db.tables {
+        statements.push(create_relation(table));
+        for index in &table.indexes {
+            // Primary keys and unique constraints create their own index.
+            let constraint_index = table.primary_key.as_ref() == Some(&index.columns)
+                || table
+                    .unique_constraints
+                    .iter()
+                    .any(|unique| unique.name == index.name);
+            if index.unique && constraint_index {
+                continue;
+            }
+            // Expression columns are kept as their definition and can be used as is.
+            let columns = index
+                .columns
+                .iter()
+                .map(|column| match table.column(column) {
+                    Some(_) => escape_ident(column),
+                    None => column.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let predicate = index
+                .predicate
+                .as_ref()
+                .map(|predicate| format!(" where {predicate}"))
+                .unwrap_or_default();
+            statements.push(format!(
+                "create {}index {} on {} using {} ({columns}){predicate};",
+                match index.unique {
+                    true => "unique ",
+                    false => "",
+                },
+                escape_ident(&index.name),
+                table.qualified_ident(),
+                index.method
+            ));
+        }
+    }
+    for table in &db.tables {
+        if let Some(comment) = &table.comment {
+            statements.push(format!(
+                "comment on {} {} is {};",
+                table.kind,
+                table.qualified_ident(),
+                literal(comment)
+            ));
+        }
+        for column in &table.columns {
+            let Some(comment) = &column.comment else {
+                continue;
+            };
+            statements.push(format!(
+                "comment on column {}.{} is {};",
+                table.qualified_ident(),
+                escape_ident(&column.name),
+                literal(comment)
+            ));
+        }
+    }
+    for table in &db.tables {
+        for foreign_key in &table.foreign_keys {
+            statements.push(format!(
+                "alter table {} add constraint {} foreign key ({}) references {} ({});",
+                table.qualified_ident(),
+                escape_ident(&foreign_key.name),
+                column_list(&foreign_key.columns),
+                foreign_key.referenced_ident(),
+                column_list(&foreign_key.referenced_columns)
+            ));
+        }
+    }
+    statements
+}
+
+/// Statement creating `table`, views are recreated from their definition.
+pub fn create_relation(table: &TableSchema) -> String {
+    let view = match table.kind {
+        TableKind::View => "view",
+        TableKind::MaterializedView => "materialized view",
+        TableKind::Table | TableKind::ForeignTable => return create_table(table),
+    };
+    match &table.definition {
+        Some(definition) => format!(
+            "create {view} {} as\n{definition};",
+            table.qualified_ident()
+        ),
+        None => create_table(table),
+    }
+}
+
+/// Statements applying `changes` (as produced by [`super::diff::diff`]) to reach the `new` schema.
+pub fn migration(changes: &[Change], new: &DbSchema) -> Vec<String> {
+    let mut statements = vec![];
+    let mut created = vec![];
+    for change in changes {
+        let statement = match change {
+            Change::TableAdded { schema, table } => {
+                let Some(table) = new.table(schema, table) else {
+                    continue;
+                };
+                created.push(table);
+                create_relation(table)
+            }
+            Change::TableRemoved { schema, table } => {
+                format!("drop table {};", qualified_ident(schema, table))
+            }
+            Change::ColumnAdded {
+                schema,
+                table,
+                column,
+            } => {
+                if created
+                    .iter()
+                    .any(|created| created.schema == *schema && created.name == *table)
+                {
+                    continue;
+                }
+                format!(
+                    "alter table {} add column {};",
+                    qualified_ident(schema, table),
+                    column_definition(column)
+                )
+            }
+            Change::ColumnRemoved {
+                schema,
+                table,
+                column,
+            } => format!(
+                "alter table {} drop column {};",
+                qualified_ident(schema, table),
+                escape_ident(&column.name)
+            ),
+            Change::ColumnRetyped {
+                schema,
+                table,
+                column,
+                to,
+                ..
+            } => {
+                let to = match to {
+                    // Serial types are only valid in column definitions.
+                    SqlType::SmallSerial => &SqlType::Int2,
+                    SqlType::Serial => &SqlType::Int4,
+                    SqlType::BigSerial => &SqlType::Int8,
+                    to => to,
+                };
+                format!(
+                    "alter table {} alter column {} type {};",
+                    qualified_ident(schema, table),
+                    escape_ident(column),
+                    sql_type_name(to)
+                )
+            }
+            Change::NullabilityChanged {
+                schema,
+                table,
+                column,
+                nullable,
+            } => format!(
+                "alter table {} alter column {} {} not null;",
+                qualified_ident(schema, table),
+                escape_ident(column),
+                match nullable {
+                    true => "drop",
+                    false => "set",
+                }
+            ),
+        };
+        statements.push(statement);
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use sql_infer_core::inference::SqlType;
+
+    use crate::schema::{
+        ColumnSchema, DbSchema, ForeignKey, SNAPSHOT_VERSION, TableKind, TableSchema, ddl::schema,
+    };
+
+    fn column(
+        name: &str,
+        data_type: SqlType,
+        nullable: bool,
+        default: Option<&str>,
+    ) -> ColumnSchema {
+        ColumnSchema {
+            name: name.to_string(),
+            data_type,
+            nullable,
+            default: default.map(str::to_string),
+            comment: None,
+        }
+    }
+
+    fn table(schema: &str, name: &str, columns: Vec<ColumnSchema>) -> TableSchema {
+        TableSchema {
+            schema: schema.to_string(),
+            name: name.to_string(),
+            kind: TableKind::Table,
+            definition: None,
+            comment: None,
+            columns,
+            primary_key: Some(vec!["id".to_string()]),
+            foreign_keys: vec![],
+            unique_constraints: vec![],
+            check_constraints: vec![],
+            indexes: vec![],
+            estimated_rows: None,
+        }
+    }
+
+    #[test]
+    fn schema_is_recreated() {
+        let users = table(
+            "public",
+            "users",
+            vec![
+                column(
+                    "id",
+                    SqlType::Int4,
+                    false,
+                    Some("nextval('users_id_seq'::regclass)"),
+                ),
+                column("name", SqlType::VarChar { length: Some(20) }, false, None),
+                column(
+                    "Display \"Name\"",
+                    SqlType::Text,
+                    true,
+                    Some("'anonymous'::text"),
+                ),
+                column(
+                    "balance",
+                    SqlType::Decimal {
+                        precision: Some(10),
+                        precision_radix: Some(10),
+                    },
+                    false,
+                    Some("0"),
+                ),
+            ],
+        );
+        let mut posts = table(
+            "audit",
+            "Posts",
+            vec![
+                column("id", SqlType::Int8, false, None),
+                column("user_id", SqlType::Int4, false, None),
+            ],
+        );
+        posts.foreign_keys.push(ForeignKey {
+            name: "posts_user_id_fkey".to_string(),
+            columns: vec!["user_id".to_string()],
+            referenced_schema: "public".to_string(),
+            referenced_table: "users".to_string(),
+            referenced_columns: vec!["id".to_string()],
+        });
+        let db = DbSchema {
+            version: SNAPSHOT_VERSION,
+            tables: vec![users, posts],
+        };
+        assert_eq!(
+            schema(&db),
+            [
+                "create schema if not exists \"audit\";",
+                "create table \"users\" (\n    \"id\" serial not null,\n    \"name\" varchar(20) not null,\n    \"Display \"\"Name\"\"\" text default 'anonymous'::text,\n    \"balance\" numeric default 0 not null,\n    primary key (\"id\")\n);",
+                "create table \"audit\".\"Posts\" (\n    \"id\" bigint not null,\n    \"user_id\" integer not null,\n    primary key (\"id\")\n);",
+                "alter table \"audit\".\"Posts\" add constraint \"posts_user_id_fkey\" foreign key (\"user_id\") references \"users\" (\"id\");",
+            ]
+        );
+    }
+}
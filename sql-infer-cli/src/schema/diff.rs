@@ -0,0 +1,313 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+use sql_infer_core::inference::SqlType;
+
+use crate::schema::{ColumnSchema, DbSchema, qualified_name};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "kebab-case")]
+pub enum Change {
+    TableAdded {
+        schema: String,
+        table: String,
+    },
+    TableRemoved {
+        schema: String,
+        table: String,
+    },
+    ColumnAdded {
+        schema: String,
+        table: String,
+        column: ColumnSchema,
+    },
+    ColumnRemoved {
+        schema: String,
+        table: String,
+        column: ColumnSchema,
+    },
+    ColumnRetyped {
+        schema: String,
+        table: String,
+        column: String,
+        from: SqlType,
+        to: SqlType,
+    },
+    NullabilityChanged {
+        schema: String,
+        table: String,
+        column: String,
+        nullable: bool,
+    },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::TableAdded { schema, table } => {
+                write!(f, "+ [table] {}", qualified_name(schema, table))
+            }
+            Change::TableRemoved { schema, table } => {
+                write!(f, "- [table] {}", qualified_name(schema, table))
+            }
+            Change::ColumnAdded {
+                schema,
+                table,
+                column,
+            } => write!(
+                f,
+                "+ [column] {}.{}: {}",
+                qualified_name(schema, table),
+                column.name,
+                column.data_type
+            ),
+            Change::ColumnRemoved {
+                schema,
+                table,
+                column,
+            } => write!(
+                f,
+                "- [column] {}.{}: {}",
+                qualified_name(schema, table),
+                column.name,
+                column.data_type
+            ),
+            Change::ColumnRetyped {
+                schema,
+                table,
+                column,
+                from,
+                to,
+            } => write!(
+                f,
+                "~ [column] {}.{column}: {from} -> {to}",
+                qualified_name(schema, table)
+            ),
+            Change::NullabilityChanged {
+                schema,
+                table,
+                column,
+                nullable,
+            } => {
+                let table = qualified_name(schema, table);
+                match nullable {
+                    true => write!(f, "~ [column] {table}.{column}: not null -> nullable"),
+                    false => write!(f, "~ [column] {table}.{column}: nullable -> not null"),
+                }
+            }
+        }
+    }
+}
+
+/// Changes required to go from the `old` schema to the `new` schema.
+pub fn diff(old: &DbSchema, new: &DbSchema) -> Vec<Change> {
+    let mut changes = vec![];
+    for old_table in &old.tables {
+        if new.table(&old_table.schema, &old_table.name).is_none() {
+            changes.push(Change::TableRemoved {
+                schema: old_table.schema.clone(),
+                table: old_table.name.clone(),
+            });
+        }
+    }
+    for new_table in &new.tables {
+        let Some(old_table) = old.table(&new_table.schema, &new_table.name) else {
+            changes.push(Change::TableAdded {
+                schema: new_table.schema.clone(),
+                table: new_table.name.clone(),
+            });
+            for column in &new_table.columns {
+                changes.push(Change::ColumnAdded {
+                    schema: new_table.schema.clone(),
+                    table: new_table.name.clone(),
+                    column: column.clone(),
+                });
+            }
+            continue;
+        };
+        for old_column in &old_table.columns {
+            if new_table.column(&old_column.name).is_none() {
+                changes.push(Change::ColumnRemoved {
+                    schema: new_table.schema.clone(),
+                    table: new_table.name.clone(),
+                    column: old_column.clone(),
+                });
+            }
+        }
+        for new_column in &new_table.columns {
+            let Some(old_column) = old_table.column(&new_column.name) else {
+                changes.push(Change::ColumnAdded {
+                    schema: new_table.schema.clone(),
+                    table: new_table.name.clone(),
+                    column: new_column.clone(),
+                });
+                continue;
+            };
+            let legacy_json =
+                old.version == 0 && legacy_json(&old_column.data_type, &new_column.data_type);
+            if old_column.data_type != new_column.data_type && !legacy_json {
+                changes.push(Change::ColumnRetyped {
+                    schema: new_table.schema.clone(),
+                    table: new_table.name.clone(),
+                    column: new_column.name.clone(),
+                    from: old_column.data_type.clone(),
+                    to: new_column.data_type.clone(),
+                });
+            }
+            if old_column.nullable != new_column.nullable {
+                changes.push(Change::NullabilityChanged {
+                    schema: new_table.schema.clone(),
+                    table: new_table.name.clone(),
+                    column: new_column.name.clone(),
+                    nullable: new_column.nullable,
+                });
+            }
+        }
+    }
+    changes
+}
+
+/// Whether `old` is how a snapshot before version 1 typed `new`, which was `json` for `jsonb`.
+fn legacy_json(old: &SqlType, new: &SqlType) -> bool {
+    match (old, new) {
+        (SqlType::Json, SqlType::Jsonb) => true,
+        (SqlType::Array(old), SqlType::Array(new)) => legacy_json(old, new),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sql_infer_core::inference::SqlType;
+
+    use crate::schema::{
+        ColumnSchema, DbSchema, SNAPSHOT_VERSION, TableKind, TableSchema,
+        diff::{Change, diff},
+    };
+
+    fn column(name: &str, data_type: SqlType, nullable: bool) -> ColumnSchema {
+        ColumnSchema {
+            name: name.to_string(),
+            data_type,
+            nullable,
+            default: None,
+            comment: None,
+        }
+    }
+
+    fn db(columns: Vec<ColumnSchema>) -> DbSchema {
+        DbSchema {
+            version: SNAPSHOT_VERSION,
+            tables: vec![TableSchema {
+                schema: "public".to_string(),
+                name: "users".to_string(),
+                kind: TableKind::Table,
+                definition: None,
+                comment: None,
+                columns,
+                primary_key: None,
+                foreign_keys: vec![],
+                unique_constraints: vec![],
+                check_constraints: vec![],
+                indexes: vec![],
+                estimated_rows: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn identical_schemas_have_no_changes() {
+        let schema = db(vec![column("id", SqlType::Int4, false)]);
+        assert_eq!(diff(&schema, &schema), vec![]);
+    }
+
+    #[test]
+    fn column_changes() {
+        let old = db(vec![
+            column("id", SqlType::Int4, false),
+            column("name", SqlType::Text, false),
+        ]);
+        let new = db(vec![
+            column("id", SqlType::Int8, false),
+            column("email", SqlType::Text, true),
+        ]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change::ColumnRemoved {
+                    schema: "public".into(),
+                    table: "users".into(),
+                    column: column("name", SqlType::Text, false),
+                },
+                Change::ColumnRetyped {
+                    schema: "public".into(),
+                    table: "users".into(),
+                    column: "id".into(),
+                    from: SqlType::Int4,
+                    to: SqlType::Int8,
+                },
+                Change::ColumnAdded {
+                    schema: "public".into(),
+                    table: "users".into(),
+                    column: column("email", SqlType::Text, true),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn nullability_change() {
+        let old = db(vec![column("id", SqlType::Int4, true)]);
+        let new = db(vec![column("id", SqlType::Int4, false)]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![Change::NullabilityChanged {
+                schema: "public".into(),
+                table: "users".into(),
+                column: "id".into(),
+                nullable: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn jsonb_columns_of_old_snapshots_are_not_retyped() {
+        let mut old = db(vec![
+            column("data", SqlType::Json, false),
+            column("tags", SqlType::Array(Box::new(SqlType::Json)), false),
+        ]);
+        let new = db(vec![
+            column("data", SqlType::Jsonb, false),
+            column("tags", SqlType::Array(Box::new(SqlType::Jsonb)), false),
+        ]);
+        assert_eq!(diff(&old, &new).len(), 2);
+        old.version = 0;
+        assert_eq!(diff(&old, &new), vec![]);
+    }
+
+    #[test]
+    fn tables_are_told_apart_by_schema() {
+        let old = db(vec![column("id", SqlType::Int4, false)]);
+        let mut new = old.clone();
+        new.tables[0].schema = "audit".to_string();
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change::TableRemoved {
+                    schema: "public".into(),
+                    table: "users".into(),
+                },
+                Change::TableAdded {
+                    schema: "audit".into(),
+                    table: "users".into(),
+                },
+                Change::ColumnAdded {
+                    schema: "audit".into(),
+                    table: "users".into(),
+                    column: column("id", SqlType::Int4, false),
+                },
+            ]
+        );
+        assert_eq!(diff(&old, &new)[1].to_string(), "+ [table] audit.users");
+    }
+}
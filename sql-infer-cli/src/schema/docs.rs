@@ -0,0 +1,146 @@
+use crate::schema::{DbSchema, TableKind, TableSchema, ddl::sql_type_name};
+
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn code(text: &str) -> String {
+    format!("`{}`", cell(text))
+}
+
+/// File name of the page documenting `table`.
+pub fn page_name(table: &TableSchema) -> String {
+    format!("{}.md", table.qualified_name())
+}
+
+pub fn index(db: &DbSchema) -> String {
+    let mut lines = vec!["# Schema".to_string(), String::new()];
+    for table in &db.tables {
+        match table.kind {
+            TableKind::Table => lines.push(format!(
+                "- [{}]({})",
+                table.qualified_name(),
+                page_name(table)
+            )),
+            kind => lines.push(format!(
+                "- [{}]({}) ({kind})",
+                table.qualified_name(),
+                page_name(table)
+            )),
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Page describing the columns and keys of `table` along with the `queries` that use it.
+pub fn table_page(table: &TableSchema, queries: &[&str]) -> String {
+    let mut lines = vec![format!("# {}", table.qualified_name()), String::new()];
+    if let Some(comment) = &table.comment {
+        lines.push(comment.clone());
+        lines.push(String::new());
+    }
+    if table.kind != TableKind::Table {
+        lines.push(format!("Kind: {}", table.kind));
+        lines.push(String::new());
+    }
+    if let Some(rows) = table.estimated_rows {
+        lines.push(format!("Estimated rows: {rows}"));
+        lines.push(String::new());
+    }
+    lines.push("| Column | Type | Nullable | Default | Comment |".to_string());
+    lines.push("| --- | --- | --- | --- | --- |".to_string());
+    for column in &table.columns {
+        let nullable = match column.nullable {
+            true => "yes",
+            false => "no",
+        };
+        let default = column.default.as_deref().map(code).unwrap_or_default();
+        let comment = column.comment.as_deref().map(cell).unwrap_or_default();
+        lines.push(format!(
+            "| {} | {} | {nullable} | {default} | {comment} |",
+            cell(&column.name),
+            code(&sql_type_name(&column.data_type)),
+        ));
+    }
+    lines.push(String::new());
+
+    if let Some(primary_key) = &table.primary_key
+        && !primary_key.is_empty()
+    {
+        lines.push("## Primary key".to_string());
+        lines.push(String::new());
+        lines.push(primary_key.join(", "));
+        lines.push(String::new());
+    }
+    if !table.foreign_keys.is_empty() {
+        lines.push("## Foreign keys".to_string());
+        lines.push(String::new());
+        for foreign_key in &table.foreign_keys {
+            lines.push(format!(
+                "- {}: ({}) references [{}]({}.md) ({})",
+                foreign_key.name,
+                foreign_key.columns.join(", "),
+                foreign_key.referenced_name(),
+                foreign_key.referenced_name(),
+                foreign_key.referenced_columns.join(", ")
+            ));
+        }
+        lines.push(String::new());
+    }
+    if !table.unique_constraints.is_empty() {
+        lines.push("## Unique constraints".to_string());
+        lines.push(String::new());
+        for unique in &table.unique_constraints {
+            lines.push(format!("- {}: {}", unique.name, unique.columns.join(", ")));
+        }
+        lines.push(String::new());
+    }
+    if !table.check_constraints.is_empty() {
+        lines.push("## Check constraints".to_string());
+        lines.push(String::new());
+        for check in &table.check_constraints {
+            lines.push(format!("- {}: {}", check.name, code(&check.definition)));
+        }
+        lines.push(String::new());
+    }
+    if !table.indexes.is_empty() {
+        lines.push("## Indexes".to_string());
+        lines.push(String::new());
+        for index in &table.indexes {
+            let unique = match index.unique {
+                true => " (unique)",
+                false => "",
+            };
+            let predicate = index
+                .predicate
+                .as_deref()
+                .map(|predicate| format!(" where {}", code(predicate)))
+                .unwrap_or_default();
+            lines.push(format!(
+                "- {}: {} using {}{unique}{predicate}",
+                index.name,
+                index.columns.join(", "),
+                index.method
+            ));
+        }
+        lines.push(String::new());
+    }
+    if let Some(definition) = &table.definition {
+        lines.push("## Definition".to_string());
+        lines.push(String::new());
+        lines.push("```sql".to_string());
+        lines.push(definition.clone());
+        lines.push("```".to_string());
+        lines.push(String::new());
+    }
+    if !queries.is_empty() {
+        lines.push("## Queries".to_string());
+        lines.push(String::new());
+        for query in queries {
+            lines.push(format!("- {query}"));
+        }
+        lines.push(String::new());
+    }
+    lines.join("\n")
+}
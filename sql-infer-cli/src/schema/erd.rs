@@ -0,0 +1,248 @@
+use clap::ValueEnum;
+
+use crate::schema::{DbSchema, TableSchema, ddl::sql_type_name};
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErdFormat {
+    Mermaid,
+    Dot,
+}
+
+pub fn render(db: &DbSchema, format: ErdFormat) -> String {
+    match format {
+        ErdFormat::Mermaid => mermaid(db),
+        ErdFormat::Dot => dot(db),
+    }
+}
+
+/// Mermaid and DOT identifiers are only left unquoted when they are plain words.
+fn is_plain(name: &str) -> bool {
+    name.starts_with(|char: char| char.is_ascii_alphabetic() || char == '_')
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+fn mermaid_entity(name: &str) -> String {
+    match is_plain(name) {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('"', "'")),
+    }
+}
+
+/// Mermaid attribute names and types can not contain spaces or most punctuation.
+fn mermaid_word(word: &str) -> String {
+    word.chars()
+        .filter(|char| *char != '"')
+        .map(|char| match char {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' | '(' | ')' | '[' | ']' => char,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn keys(table: &TableSchema, column: &str) -> Vec<&'static str> {
+    let mut keys = vec![];
+    if table
+        .primary_key
+        .as_ref()
+        .is_some_and(|primary_key| primary_key.iter().any(|key| key == column))
+    {
+        keys.push("PK");
+    }
+    if table
+        .unique_constraints
+        .iter()
+        .any(|unique| unique.columns.iter().any(|key| key == column))
+    {
+        keys.push("UK");
+    }
+    if table
+        .foreign_keys
+        .iter()
+        .any(|foreign_key| foreign_key.columns.iter().any(|key| key == column))
+    {
+        keys.push("FK");
+    }
+    keys
+}
+
+fn mermaid(db: &DbSchema) -> String {
+    let mut lines = vec!["erDiagram".to_string()];
+    for table in &db.tables {
+        lines.push(format!(
+            "    {} {{",
+            mermaid_entity(&table.qualified_name())
+        ));
+        for column in &table.columns {
+            let mut attribute = format!(
+                "        {} {}",
+                mermaid_word(&sql_type_name(&column.data_type)),
+                mermaid_word(&column.name)
+            );
+            let keys = keys(table, &column.name);
+            if !keys.is_empty() {
+                attribute.push_str(&format!(" {}", keys.join(", ")));
+            }
+            if column.nullable {
+                attribute.push_str(" \"nullable\"");
+            }
+            lines.push(attribute);
+        }
+        lines.push("    }".to_string());
+    }
+    for table in &db.tables {
+        for foreign_key in &table.foreign_keys {
+            lines.push(format!(
+                "    {} ||--o{{ {} : \"{}\"",
+                mermaid_entity(&foreign_key.referenced_name()),
+                mermaid_entity(&table.qualified_name()),
+                foreign_key.columns.join(", ").replace('"', "'")
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn dot(db: &DbSchema) -> String {
+    let mut lines = vec![
+        "digraph schema {".to_string(),
+        "    rankdir=LR;".to_string(),
+        "    node [shape=plaintext];".to_string(),
+    ];
+    for table in &db.tables {
+        let mut rows = vec![format!(
+            "<tr><td><b>{}</b></td></tr>",
+            html_escape(&table.qualified_name())
+        )];
+        for column in &table.columns {
+            let keys = keys(table, &column.name);
+            let keys = match keys.is_empty() {
+                true => String::new(),
+                false => format!(" [{}]", keys.join(", ")),
+            };
+            let nullable = match column.nullable {
+                true => "?",
+                false => "",
+            };
+            let name = html_escape(&column.name);
+            rows.push(format!(
+                "<tr><td port=\"{name}\" align=\"left\">{name}{nullable}: {}{keys}</td></tr>",
+                html_escape(&sql_type_name(&column.data_type)),
+            ));
+        }
+        lines.push(format!(
+            "    {} [label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\">{}</table>>];",
+            dot_id(&table.qualified_name()),
+            rows.join("")
+        ));
+    }
+    for table in &db.tables {
+        for foreign_key in &table.foreign_keys {
+            // Single column keys point at the column, composite keys at the table.
+            let (from, to) = match (
+                foreign_key.columns.as_slice(),
+                foreign_key.referenced_columns.as_slice(),
+            ) {
+                ([from], [to]) => (
+                    format!("{}:{}", dot_id(&table.qualified_name()), dot_id(from)),
+                    format!("{}:{}", dot_id(&foreign_key.referenced_name()), dot_id(to)),
+                ),
+                _ => (
+                    dot_id(&table.qualified_name()),
+                    dot_id(&foreign_key.referenced_name()),
+                ),
+            };
+            lines.push(format!(
+                "    {from} -> {to} [label={}];",
+                dot_id(&foreign_key.name)
+            ));
+        }
+    }
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use sql_infer_core::inference::SqlType;
+
+    use crate::schema::{
+        ColumnSchema, DbSchema, ForeignKey, SNAPSHOT_VERSION, TableKind, TableSchema,
+        erd::{ErdFormat, render},
+    };
+
+    fn table(
+        name: &str,
+        columns: &[(&str, SqlType)],
+        foreign_keys: Vec<ForeignKey>,
+    ) -> TableSchema {
+        TableSchema {
+            schema: "public".to_string(),
+            name: name.to_string(),
+            kind: TableKind::Table,
+            definition: None,
+            comment: None,
+            columns: columns
+                .iter()
+                .map(|(name, data_type)| ColumnSchema {
+                    name: name.to_string(),
+                    data_type: data_type.clone(),
+                    nullable: false,
+                    default: None,
+                    comment: None,
+                })
+                .collect(),
+            primary_key: Some(vec!["id".to_string()]),
+            foreign_keys,
+            unique_constraints: vec![],
+            check_constraints: vec![],
+            indexes: vec![],
+            estimated_rows: None,
+        }
+    }
+
+    #[test]
+    fn mermaid_diagram() {
+        let db = DbSchema {
+            version: SNAPSHOT_VERSION,
+            tables: vec![
+                table("users", &[("id", SqlType::Int4)], vec![]),
+                table(
+                    "posts",
+                    &[("id", SqlType::Int4), ("user_id", SqlType::Float8)],
+                    vec![ForeignKey {
+                        name: "posts_user_id_fkey".to_string(),
+                        columns: vec!["user_id".to_string()],
+                        referenced_schema: "public".to_string(),
+                        referenced_table: "users".to_string(),
+                        referenced_columns: vec!["id".to_string()],
+                    }],
+                ),
+            ],
+        };
+        assert_eq!(
+            render(&db, ErdFormat::Mermaid),
+            "erDiagram
+    users {
+        integer id PK
+    }
+    posts {
+        integer id PK
+        double_precision user_id FK
+    }
+    users ||--o{ posts : \"user_id\""
+        );
+    }
+}
@@ -1,9 +1,23 @@
-use std::{borrow::Cow, fmt::Display};
+use std::{borrow::Cow, error::Error, fmt::Display, path::Path};
 
 use serde::{Deserialize, Serialize};
 use sql_infer_core::inference::SqlType;
 
-use crate::schema::DbSchema;
+use crate::{
+    diagnostics::{Diagnostic, DiagnosticsFormat, Severity, report},
+    schema::{
+        DbSchema,
+        lint::{
+            naming::{
+                ForbiddenPrefix, IdentifierLength, NamingOptions, SnakeCase, TableNamePlurality,
+            },
+            plugin::LintPlugin,
+        },
+    },
+};
+
+pub mod naming;
+pub mod plugin;
 
 #[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -14,10 +28,168 @@ pub enum LintSetting {
     Deny,
 }
 
+impl LintSetting {
+    pub fn warn() -> Self {
+        Self::Warn
+    }
+
+    /// Print a finding of the lint `name` at this level, returns whether it is denied.
+    pub fn report(self, name: &str, error: &LintError) -> bool {
+        match self {
+            LintSetting::Allow => false,
+            LintSetting::Warn => {
+                println!("warning({name}): {error}");
+                false
+            }
+            LintSetting::Deny => {
+                println!("error({name}): {error}");
+                true
+            }
+        }
+    }
+}
+
+/// The `[lints]` config table, setting the level of each lint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Lints {
+    #[serde(default = "LintSetting::warn")]
+    pub time_with_timezone: LintSetting,
+    #[serde(default = "LintSetting::warn")]
+    pub timestamp_without_timezone: LintSetting,
+    #[serde(default = "LintSetting::warn")]
+    pub table_column_name_clash: LintSetting,
+    #[serde(default = "LintSetting::warn")]
+    pub missing_primary_key: LintSetting,
+    #[serde(default = "LintSetting::warn")]
+    pub unindexed_foreign_key: LintSetting,
+    #[serde(default = "LintSetting::default")]
+    pub snake_case: LintSetting,
+    #[serde(default = "LintSetting::default")]
+    pub forbidden_prefix: LintSetting,
+    #[serde(default = "LintSetting::default")]
+    pub table_name_plurality: LintSetting,
+    #[serde(default = "LintSetting::default")]
+    pub identifier_length: LintSetting,
+    #[serde(default = "LintSetting::default")]
+    pub string_type: LintSetting,
+    #[serde(default = "LintSetting::default")]
+    pub nullable_without_default: LintSetting,
+    #[serde(default = "LintSetting::warn")]
+    pub select_star: LintSetting,
+    #[serde(default = "LintSetting::warn")]
+    pub unbounded_select: LintSetting,
+    #[serde(default = "LintSetting::warn")]
+    pub implicit_cross_join: LintSetting,
+    #[serde(default = "LintSetting::warn")]
+    pub mismatched_comparison: LintSetting,
+    #[serde(default = "LintSetting::default")]
+    pub ddl: LintSetting,
+    #[serde(default = "NamingOptions::default")]
+    pub naming: NamingOptions,
+    #[serde(default = "StringPolicy::default")]
+    pub string_policy: StringPolicy,
+    /// Estimated number of rows from which tables are checked by `nullable-without-default`.
+    #[serde(default = "u64::default")]
+    pub min_table_rows: u64,
+    #[serde(default = "Vec::default")]
+    pub plugins: Vec<LintPlugin>,
+}
+
+/// Which string columns the `string-type` lint accepts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StringPolicy {
+    /// Flag `varchar` without a length.
+    #[default]
+    BoundedVarchar,
+    /// Flag `varchar(n)` in favour of `text`.
+    Text,
+}
+
+impl Default for Lints {
+    fn default() -> Self {
+        Self {
+            time_with_timezone: LintSetting::Warn,
+            timestamp_without_timezone: LintSetting::Warn,
+            table_column_name_clash: LintSetting::Warn,
+            missing_primary_key: LintSetting::Warn,
+            unindexed_foreign_key: LintSetting::Warn,
+            snake_case: LintSetting::Allow,
+            forbidden_prefix: LintSetting::Allow,
+            table_name_plurality: LintSetting::Allow,
+            identifier_length: LintSetting::Allow,
+            string_type: LintSetting::Allow,
+            nullable_without_default: LintSetting::Allow,
+            select_star: LintSetting::Warn,
+            unbounded_select: LintSetting::Warn,
+            implicit_cross_join: LintSetting::Warn,
+            mismatched_comparison: LintSetting::Warn,
+            ddl: LintSetting::Allow,
+            naming: NamingOptions::default(),
+            string_policy: StringPolicy::default(),
+            min_table_rows: 0,
+            plugins: vec![],
+        }
+    }
+}
+
+impl Lints {
+    /// Every schema lint that is not allowed along with its level.
+    pub fn enabled(&self) -> Vec<(LintSetting, Box<dyn Lint>)> {
+        let lints: [(LintSetting, Box<dyn Lint>); 11] = [
+            (self.time_with_timezone, Box::new(TimeWithTimezone)),
+            (
+                self.timestamp_without_timezone,
+                Box::new(TimestampWithoutTimezone),
+            ),
+            (self.table_column_name_clash, Box::new(TableColumnNameClash)),
+            (self.missing_primary_key, Box::new(MissingPrimaryKey)),
+            (self.unindexed_foreign_key, Box::new(UnindexedForeignKey)),
+            (self.snake_case, Box::new(SnakeCase)),
+            (
+                self.forbidden_prefix,
+                Box::new(ForbiddenPrefix {
+                    prefixes: self.naming.forbidden_prefixes.clone(),
+                }),
+            ),
+            (
+                self.table_name_plurality,
+                Box::new(TableNamePlurality {
+                    plurality: self.naming.table_names,
+                }),
+            ),
+            (
+                self.identifier_length,
+                Box::new(IdentifierLength {
+                    max: self.naming.max_identifier_length,
+                }),
+            ),
+            (
+                self.string_type,
+                Box::new(StringType {
+                    policy: self.string_policy,
+                }),
+            ),
+            (
+                self.nullable_without_default,
+                Box::new(NullableWithoutDefault {
+                    min_rows: self.min_table_rows,
+                }),
+            ),
+        ];
+        lints
+            .into_iter()
+            .filter(|(setting, _)| !matches!(setting, LintSetting::Allow))
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Source {
     Table(String),
     Column { table: String, column: String },
+    Query(String),
 }
 
 #[derive(Debug, Clone)]
@@ -26,32 +198,105 @@ pub struct LintError {
     msg: Cow<'static, str>,
 }
 
+impl LintError {
+    pub fn new(source: Source, msg: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            source,
+            msg: msg.into(),
+        }
+    }
+}
+
+/// Findings of a lint run, printed as they are found unless they are reported in a diagnostics
+/// format at the end.
+pub struct LintReport {
+    format: Option<DiagnosticsFormat>,
+    diagnostics: Vec<Diagnostic>,
+    denied: usize,
+}
+
+impl LintReport {
+    pub fn new(format: Option<DiagnosticsFormat>) -> Self {
+        Self {
+            format,
+            diagnostics: vec![],
+            denied: 0,
+        }
+    }
+
+    /// Record a finding of the lint `name`, `file` being the query file it was found in.
+    pub fn push(&mut self, setting: LintSetting, name: &str, error: &LintError, file: &Path) {
+        if self.format.is_none() {
+            self.denied += usize::from(setting.report(name, error));
+            return;
+        }
+        let severity = match setting {
+            LintSetting::Allow => return,
+            LintSetting::Warn => Severity::Warning,
+            LintSetting::Deny => Severity::Error,
+        };
+        self.denied += usize::from(severity == Severity::Error);
+        self.diagnostics.push(Diagnostic {
+            file: file.to_path_buf(),
+            query: match &error.source {
+                Source::Query(query) => query.clone(),
+                Source::Table(_) | Source::Column { .. } => String::new(),
+            },
+            span: None,
+            severity,
+            message: format!("{error} ({name})"),
+        });
+    }
+
+    /// Report the findings in the diagnostics format, failing if any lint is denied.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        if let Some(format) = self.format {
+            report(&self.diagnostics, format)?;
+        }
+        if self.denied > 0 {
+            Err(format!("{} lint error(s) found", self.denied))?;
+        }
+        Ok(())
+    }
+}
+
 impl Display for LintError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.source {
             Source::Table(table) => write!(f, "[table] {table}: "),
             Source::Column { table, column } => write!(f, "[column] {table}.{column}: "),
+            Source::Query(query) => write!(f, "[query] {query}: "),
         }?;
         write!(f, "{}", self.msg)
     }
 }
 
 pub trait Lint {
+    /// Key of the lint in the `[lints]` config table.
+    fn name(&self) -> &'static str;
     fn lint(&self, db: &DbSchema) -> Vec<LintError>;
 }
 pub struct TimestampWithoutTimezone;
 
 impl Lint for TimestampWithoutTimezone {
+    fn name(&self) -> &'static str {
+        "timestamp-without-timezone"
+    }
+
     fn lint(&self, db: &DbSchema) -> Vec<LintError> {
         let mut errors = vec![];
         for table in &db.tables {
+            // Views inherit their column types, which are reported on the source table.
+            if table.kind.is_view() {
+                continue;
+            }
             for column in &table.columns {
                 let SqlType::Timestamp { tz: false } = column.data_type else {
                     continue;
                 };
                 errors.push(LintError {
                     source: Source::Column {
-                        table: table.name.clone(),
+                        table: table.qualified_name(),
                         column: column.name.clone(),
                     },
                     msg: Cow::Borrowed("timestamp has no timezone"),
@@ -65,16 +310,24 @@ impl Lint for TimestampWithoutTimezone {
 pub struct TimeWithTimezone;
 
 impl Lint for TimeWithTimezone {
+    fn name(&self) -> &'static str {
+        "time-with-timezone"
+    }
+
     fn lint(&self, db: &DbSchema) -> Vec<LintError> {
         let mut errors = vec![];
         for table in &db.tables {
+            // Views inherit their column types, which are reported on the source table.
+            if table.kind.is_view() {
+                continue;
+            }
             for column in &table.columns {
                 let SqlType::Time { tz: true } = column.data_type else {
                     continue;
                 };
                 errors.push(LintError {
                     source: Source::Column {
-                        table: table.name.clone(),
+                        table: table.qualified_name(),
                         column: column.name.clone(),
                     },
                     msg: Cow::Borrowed("time has timezone"),
@@ -88,6 +341,10 @@ impl Lint for TimeWithTimezone {
 pub struct TableColumnNameClash;
 
 impl Lint for TableColumnNameClash {
+    fn name(&self) -> &'static str {
+        "table-column-name-clash"
+    }
+
     fn lint(&self, db: &DbSchema) -> Vec<LintError> {
         let mut errors = vec![];
         for table in &db.tables {
@@ -97,7 +354,7 @@ impl Lint for TableColumnNameClash {
                 };
                 errors.push(LintError {
                     source: Source::Column {
-                        table: table.name.clone(),
+                        table: table.qualified_name(),
                         column: column.name.clone(),
                     },
                     msg: Cow::Borrowed("column name is the same as table name"),
@@ -107,3 +364,130 @@ impl Lint for TableColumnNameClash {
         errors
     }
 }
+
+pub struct MissingPrimaryKey;
+
+impl Lint for MissingPrimaryKey {
+    fn name(&self) -> &'static str {
+        "missing-primary-key"
+    }
+
+    fn lint(&self, db: &DbSchema) -> Vec<LintError> {
+        let mut errors = vec![];
+        for table in &db.tables {
+            if !table.primary_key.as_ref().is_some_and(Vec::is_empty) {
+                continue;
+            }
+            errors.push(LintError {
+                source: Source::Table(table.qualified_name()),
+                msg: Cow::Borrowed("table has no primary key"),
+            });
+        }
+        errors
+    }
+}
+
+pub struct UnindexedForeignKey;
+
+impl Lint for UnindexedForeignKey {
+    fn name(&self) -> &'static str {
+        "unindexed-foreign-key"
+    }
+
+    fn lint(&self, db: &DbSchema) -> Vec<LintError> {
+        let mut errors = vec![];
+        for table in &db.tables {
+            for foreign_key in &table.foreign_keys {
+                if table
+                    .indexes
+                    .iter()
+                    .any(|index| index.covers(&foreign_key.columns))
+                {
+                    continue;
+                }
+                errors.push(LintError {
+                    source: Source::Table(table.qualified_name()),
+                    msg: Cow::Owned(format!(
+                        "foreign key {} ({}) has no covering index",
+                        foreign_key.name,
+                        foreign_key.columns.join(", ")
+                    )),
+                });
+            }
+        }
+        errors
+    }
+}
+
+pub struct StringType {
+    pub policy: StringPolicy,
+}
+
+impl Lint for StringType {
+    fn name(&self) -> &'static str {
+        "string-type"
+    }
+
+    fn lint(&self, db: &DbSchema) -> Vec<LintError> {
+        let mut errors = vec![];
+        for table in &db.tables {
+            // Views inherit their column types, which are reported on the source table.
+            if table.kind.is_view() {
+                continue;
+            }
+            for column in &table.columns {
+                let SqlType::VarChar { length } = column.data_type else {
+                    continue;
+                };
+                let msg = match (self.policy, length) {
+                    (StringPolicy::BoundedVarchar, None) => "varchar has no length",
+                    (StringPolicy::Text, Some(_)) => "varchar has a length, use text instead",
+                    _ => continue,
+                };
+                errors.push(LintError {
+                    source: Source::Column {
+                        table: table.qualified_name(),
+                        column: column.name.clone(),
+                    },
+                    msg: Cow::Borrowed(msg),
+                });
+            }
+        }
+        errors
+    }
+}
+
+pub struct NullableWithoutDefault {
+    pub min_rows: u64,
+}
+
+impl Lint for NullableWithoutDefault {
+    fn name(&self) -> &'static str {
+        "nullable-without-default"
+    }
+
+    fn lint(&self, db: &DbSchema) -> Vec<LintError> {
+        let mut errors = vec![];
+        for table in &db.tables {
+            if table.kind.is_view() {
+                continue;
+            }
+            if self.min_rows > 0 && table.estimated_rows.is_none_or(|rows| rows < self.min_rows) {
+                continue;
+            }
+            for column in &table.columns {
+                if !column.nullable || column.default.is_some() {
+                    continue;
+                }
+                errors.push(LintError {
+                    source: Source::Column {
+                        table: table.qualified_name(),
+                        column: column.name.clone(),
+                    },
+                    msg: Cow::Borrowed("nullable column has no default"),
+                });
+            }
+        }
+        errors
+    }
+}
@@ -0,0 +1,196 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{
+    DbSchema,
+    lint::{Lint, LintError, Source},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Plurality {
+    Singular,
+    #[default]
+    Plural,
+}
+
+/// The `[lints.naming]` config table, used by the naming lints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NamingOptions {
+    #[serde(default = "Vec::default")]
+    pub forbidden_prefixes: Vec<String>,
+    #[serde(default = "Plurality::default")]
+    pub table_names: Plurality,
+    #[serde(default = "NamingOptions::max_identifier_length")]
+    pub max_identifier_length: usize,
+}
+
+impl NamingOptions {
+    /// Longest identifier Postgres keeps without truncating.
+    fn max_identifier_length() -> usize {
+        63
+    }
+}
+
+impl Default for NamingOptions {
+    fn default() -> Self {
+        Self {
+            forbidden_prefixes: vec![],
+            table_names: Plurality::default(),
+            max_identifier_length: Self::max_identifier_length(),
+        }
+    }
+}
+
+/// Every table and column name along with where it came from.
+fn identifiers(db: &DbSchema) -> Vec<(Source, &str)> {
+    let mut identifiers = vec![];
+    for table in &db.tables {
+        identifiers.push((Source::Table(table.qualified_name()), table.name.as_str()));
+        for column in &table.columns {
+            identifiers.push((
+                Source::Column {
+                    table: table.qualified_name(),
+                    column: column.name.clone(),
+                },
+                column.name.as_str(),
+            ));
+        }
+    }
+    identifiers
+}
+
+pub struct SnakeCase;
+
+impl Lint for SnakeCase {
+    fn name(&self) -> &'static str {
+        "snake-case"
+    }
+
+    fn lint(&self, db: &DbSchema) -> Vec<LintError> {
+        let mut errors = vec![];
+        for (source, name) in identifiers(db) {
+            let snake_case = name.starts_with(|char: char| char.is_ascii_lowercase())
+                && name
+                    .chars()
+                    .all(|char| char.is_ascii_lowercase() || char.is_ascii_digit() || char == '_');
+            if snake_case {
+                continue;
+            }
+            errors.push(LintError {
+                source,
+                msg: Cow::Borrowed("name is not snake_case"),
+            });
+        }
+        errors
+    }
+}
+
+pub struct ForbiddenPrefix {
+    pub prefixes: Vec<String>,
+}
+
+impl Lint for ForbiddenPrefix {
+    fn name(&self) -> &'static str {
+        "forbidden-prefix"
+    }
+
+    fn lint(&self, db: &DbSchema) -> Vec<LintError> {
+        let mut errors = vec![];
+        for (source, name) in identifiers(db) {
+            let Some(prefix) = self
+                .prefixes
+                .iter()
+                .find(|prefix| name.starts_with(prefix.as_str()))
+            else {
+                continue;
+            };
+            errors.push(LintError {
+                source,
+                msg: Cow::Owned(format!("name starts with forbidden prefix '{prefix}'")),
+            });
+        }
+        errors
+    }
+}
+
+/// Guess whether the last word of a name is plural, this is only a heuristic for English.
+fn is_plural(name: &str) -> bool {
+    let word = name.rsplit('_').next().unwrap_or(name).to_lowercase();
+    word.ends_with('s') && !["ss", "us", "is"].iter().any(|end| word.ends_with(end))
+}
+
+pub struct TableNamePlurality {
+    pub plurality: Plurality,
+}
+
+impl Lint for TableNamePlurality {
+    fn name(&self) -> &'static str {
+        "table-name-plurality"
+    }
+
+    fn lint(&self, db: &DbSchema) -> Vec<LintError> {
+        let mut errors = vec![];
+        for table in &db.tables {
+            let plurality = match is_plural(&table.name) {
+                true => Plurality::Plural,
+                false => Plurality::Singular,
+            };
+            if plurality == self.plurality {
+                continue;
+            }
+            errors.push(LintError {
+                source: Source::Table(table.qualified_name()),
+                msg: Cow::Borrowed(match self.plurality {
+                    Plurality::Singular => "table name is not singular",
+                    Plurality::Plural => "table name is not plural",
+                }),
+            });
+        }
+        errors
+    }
+}
+
+pub struct IdentifierLength {
+    pub max: usize,
+}
+
+impl Lint for IdentifierLength {
+    fn name(&self) -> &'static str {
+        "identifier-length"
+    }
+
+    fn lint(&self, db: &DbSchema) -> Vec<LintError> {
+        let mut errors = vec![];
+        for (source, name) in identifiers(db) {
+            if name.len() <= self.max {
+                continue;
+            }
+            errors.push(LintError {
+                source,
+                msg: Cow::Owned(format!(
+                    "name is {} characters long, longer than {}",
+                    name.len(),
+                    self.max
+                )),
+            });
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::lint::naming::is_plural;
+
+    #[test]
+    fn plurality_of_last_word() {
+        assert!(is_plural("users"));
+        assert!(is_plural("user_accounts"));
+        assert!(!is_plural("user"));
+        assert!(!is_plural("accounts_status"));
+        assert!(!is_plural("address"));
+    }
+}
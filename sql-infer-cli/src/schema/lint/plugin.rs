@@ -0,0 +1,74 @@
+use std::{
+    error::Error,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{
+    DbSchema,
+    lint::{LintError, LintSetting, Source},
+};
+
+/// An external executable that receives the JSON `DbSchema` on stdin and prints a JSON array of
+/// findings on stdout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LintPlugin {
+    pub name: String,
+    /// Program followed by its arguments.
+    pub command: Vec<String>,
+    #[serde(default = "LintSetting::warn")]
+    pub level: LintSetting,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Finding {
+    table: String,
+    column: Option<String>,
+    message: String,
+}
+
+impl LintPlugin {
+    pub fn run(&self, db: &DbSchema) -> Result<Vec<LintError>, Box<dyn Error>> {
+        let Some((program, args)) = self.command.split_first() else {
+            Err(format!("lint plugin {} has an empty command", self.name))?
+        };
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| format!("failed to run lint plugin {}: {error}", self.name))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&serde_json::to_vec(db)?)?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            Err(format!(
+                "lint plugin {} exited with {}",
+                self.name, output.status
+            ))?;
+        }
+        let findings: Vec<Finding> = serde_json::from_slice(&output.stdout).map_err(|error| {
+            format!(
+                "lint plugin {} printed invalid findings: {error}",
+                self.name
+            )
+        })?;
+        Ok(findings
+            .into_iter()
+            .map(|finding| {
+                let source = match finding.column {
+                    Some(column) => Source::Column {
+                        table: finding.table,
+                        column,
+                    },
+                    None => Source::Table(finding.table),
+                };
+                LintError::new(source, finding.message)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,206 @@
+use sql_infer_core::inference::SqlType;
+
+use crate::schema::{DEFAULT_SCHEMA, DbSchema, TableKind, TableSchema, ddl::literal};
+
+/// Type name as `format_type` prints it, which `col_type_is` compares against. `None` when the
+/// type can not be told exactly, e.g. the scale of `numeric` which is not tracked, or lengths
+/// when `precise-output-datatypes` is off.
+fn format_type(sql_type: &SqlType) -> Option<String> {
+    let name = match sql_type {
+        SqlType::Bool => "boolean".to_string(),
+        SqlType::Int2 | SqlType::SmallSerial => "smallint".to_string(),
+        SqlType::Int4 | SqlType::Serial => "integer".to_string(),
+        SqlType::Int8 | SqlType::BigSerial => "bigint".to_string(),
+        SqlType::Timestamp { tz: true } => "timestamp with time zone".to_string(),
+        SqlType::Timestamp { tz: false } => "timestamp without time zone".to_string(),
+        SqlType::Date => "date".to_string(),
+        SqlType::Time { tz: true } => "time with time zone".to_string(),
+        SqlType::Time { tz: false } => "time without time zone".to_string(),
+        SqlType::Interval => "interval".to_string(),
+        SqlType::Char { length } => format!("character({})", length.as_ref()?),
+        SqlType::VarChar { length } => format!("character varying({})", length.as_ref()?),
+        SqlType::Bit { length } => format!("bit({})", length.as_ref()?),
+        SqlType::VarBit { length } => format!("bit varying({})", length.as_ref()?),
+        SqlType::Text => "text".to_string(),
+        SqlType::Json => "json".to_string(),
+        SqlType::Jsonb => "jsonb".to_string(),
+        SqlType::Float4 => "real".to_string(),
+        SqlType::Float8 => "double precision".to_string(),
+        SqlType::Enum { name, .. } => name.clone(),
+        SqlType::Array(inner) => format!("{}[]", format_type(inner)?),
+        SqlType::Decimal { .. } | SqlType::Record(_) | SqlType::Unknown => {
+            return None;
+        }
+    };
+    Some(name)
+}
+
+/// Call of `function` on the relation `table`. Relations outside the default schema are named
+/// along with their schema, whose variants of the pgTAP functions also take a description.
+fn call(table: &TableSchema, function: &str, arguments: &[String], description: &str) -> String {
+    let mut all = match table.schema == DEFAULT_SCHEMA {
+        true => vec![literal(&table.name)],
+        false => vec![
+            format!("{}::name", literal(&table.schema)),
+            format!("{}::name", literal(&table.name)),
+        ],
+    };
+    all.extend_from_slice(arguments);
+    if table.schema != DEFAULT_SCHEMA {
+        all.push(literal(description));
+    }
+    format!("select {function}({});", all.join(", "))
+}
+
+fn has_relation(table: &TableSchema) -> String {
+    let (function, kind) = match table.kind {
+        TableKind::Table => ("has_table", "Table"),
+        TableKind::View => ("has_view", "View"),
+        TableKind::MaterializedView => ("has_materialized_view", "Materialized view"),
+        TableKind::ForeignTable => ("has_foreign_table", "Foreign table"),
+    };
+    let description = format!("{kind} {} should exist", table.qualified_name());
+    call(table, function, &[], &description)
+}
+
+/// Assertions checking `table` and its columns exist with their type and nullability.
+pub fn assertions(table: &TableSchema) -> Vec<String> {
+    let mut assertions = vec![has_relation(table)];
+    for column in &table.columns {
+        let name = format!("Column {}.{}", table.qualified_name(), column.name);
+        let column_name = literal(&column.name);
+        assertions.push(call(
+            table,
+            "has_column",
+            std::slice::from_ref(&column_name),
+            &format!("{name} should exist"),
+        ));
+        if let Some(type_name) = format_type(&column.data_type) {
+            assertions.push(call(
+                table,
+                "col_type_is",
+                &[column_name.clone(), literal(&type_name)],
+                &format!("{name} should be type {type_name}"),
+            ));
+        }
+        // Nullability of view columns is inferred rather than declared, so it is not asserted.
+        if table.kind.is_view() {
+            continue;
+        }
+        let (function, description) = match column.nullable {
+            true => ("col_is_null", "allow NULL"),
+            false => ("col_not_null", "be NOT NULL"),
+        };
+        assertions.push(call(
+            table,
+            function,
+            &[column_name],
+            &format!("{name} should {description}"),
+        ));
+    }
+    assertions
+}
+
+/// A pgTAP test file asserting the database matches `db`, run in a rolled back transaction.
+pub fn test_file(db: &DbSchema) -> String {
+    let assertions = db
+        .tables
+        .iter()
+        .map(|table| assertions(table).join("\n"))
+        .collect::<Vec<_>>();
+    let count = assertions
+        .iter()
+        .map(|assertions| assertions.lines().count())
+        .sum::<usize>();
+    format!(
+        "-- Generated by sql-infer from the introspected schema.\nbegin;\nselect plan({count});\n\n{}\n\nselect * from finish();\nrollback;\n",
+        assertions.join("\n\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use sql_infer_core::inference::SqlType;
+
+    use crate::schema::{ColumnSchema, TableKind, TableSchema, pgtap::assertions};
+
+    fn column(name: &str, data_type: SqlType, nullable: bool) -> ColumnSchema {
+        ColumnSchema {
+            name: name.to_string(),
+            data_type,
+            nullable,
+            default: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn columns_are_asserted() {
+        let table = TableSchema {
+            schema: "public".to_string(),
+            name: "users".to_string(),
+            kind: TableKind::Table,
+            definition: None,
+            comment: None,
+            columns: vec![
+                column("id", SqlType::Int4, false),
+                column("name", SqlType::VarChar { length: Some(50) }, true),
+                column(
+                    "price",
+                    SqlType::Decimal {
+                        precision: Some(10),
+                        precision_radix: Some(10),
+                    },
+                    true,
+                ),
+            ],
+            primary_key: Some(vec!["id".to_string()]),
+            foreign_keys: vec![],
+            unique_constraints: vec![],
+            check_constraints: vec![],
+            indexes: vec![],
+            estimated_rows: None,
+        };
+        assert_eq!(
+            assertions(&table),
+            [
+                "select has_table('users');",
+                "select has_column('users', 'id');",
+                "select col_type_is('users', 'id', 'integer');",
+                "select col_not_null('users', 'id');",
+                "select has_column('users', 'name');",
+                "select col_type_is('users', 'name', 'character varying(50)');",
+                "select col_is_null('users', 'name');",
+                "select has_column('users', 'price');",
+                "select col_is_null('users', 'price');",
+            ]
+        );
+    }
+
+    #[test]
+    fn other_schemas_are_named() {
+        let table = TableSchema {
+            schema: "audit".to_string(),
+            name: "events".to_string(),
+            kind: TableKind::Table,
+            definition: None,
+            comment: None,
+            columns: vec![column("id", SqlType::Int4, false)],
+            primary_key: Some(vec!["id".to_string()]),
+            foreign_keys: vec![],
+            unique_constraints: vec![],
+            check_constraints: vec![],
+            indexes: vec![],
+            estimated_rows: None,
+        };
+        assert_eq!(
+            assertions(&table),
+            [
+                "select has_table('audit'::name, 'events'::name, 'Table audit.events should exist');",
+                "select has_column('audit'::name, 'events'::name, 'id', 'Column audit.events.id should exist');",
+                "select col_type_is('audit'::name, 'events'::name, 'id', 'integer', 'Column audit.events.id should be type integer');",
+                "select col_not_null('audit'::name, 'events'::name, 'id', 'Column audit.events.id should be NOT NULL');",
+            ]
+        );
+    }
+}
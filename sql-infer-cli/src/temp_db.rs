@@ -0,0 +1,271 @@
+use std::{
+    error::Error,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, PgConnection};
+
+/// Password of the superuser of containers, which only listen on localhost.
+const PASSWORD: &str = "sql-infer";
+
+/// How long a started database gets to accept connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What runs the temporary database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Runtime {
+    /// A container of `image`, removed once stopped.
+    #[default]
+    Docker,
+    /// A cluster created with the `initdb` and `pg_ctl` found in `PATH`.
+    Local,
+}
+
+/// The `[temp-db]` config table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TempDbConfig {
+    #[serde(default = "Runtime::default")]
+    pub runtime: Runtime,
+    /// Image of the container, ignored by the local runtime.
+    #[serde(default = "TempDbConfig::image")]
+    pub image: String,
+}
+
+impl TempDbConfig {
+    fn image() -> String {
+        "postgres:17".to_string()
+    }
+}
+
+impl Default for TempDbConfig {
+    fn default() -> Self {
+        Self {
+            runtime: Runtime::default(),
+            image: Self::image(),
+        }
+    }
+}
+
+enum Instance {
+    Container(String),
+    Cluster(PathBuf),
+}
+
+impl Instance {
+    /// Command stopping and removing a container, or stopping the server of a cluster whose
+    /// directory is removed afterwards.
+    fn stop(&self) -> Command {
+        match self {
+            Instance::Container(id) => {
+                let mut command = Command::new("docker");
+                command.args(["rm", "--force", id]);
+                command
+            }
+            Instance::Cluster(directory) => {
+                let mut command = Command::new("pg_ctl");
+                command
+                    .arg("--pgdata")
+                    .arg(directory)
+                    .args(["--mode", "immediate", "stop"]);
+                command
+            }
+        }
+    }
+}
+
+/// Command starting a container of `image` in the background, listening on a random port of
+/// localhost.
+fn docker_run(image: &str) -> Command {
+    let mut command = Command::new("docker");
+    command.args([
+        "run",
+        "--detach",
+        "--rm",
+        "--env",
+        &format!("POSTGRES_PASSWORD={PASSWORD}"),
+        "--publish",
+        "127.0.0.1::5432",
+        image,
+    ]);
+    command
+}
+
+fn initdb(directory: &Path) -> Command {
+    let mut command = Command::new("initdb");
+    command
+        .args(["--auth=trust", "--username=postgres", "--no-sync"])
+        .arg("--pgdata")
+        .arg(directory);
+    command
+}
+
+/// Command starting the server of the cluster in `directory` on `port` of localhost, with its
+/// socket in the cluster directory.
+fn pg_ctl_start(directory: &Path, port: u16) -> Command {
+    let options = format!(
+        "-k {} -p {port} -c listen_addresses=127.0.0.1 -F",
+        directory.display()
+    );
+    let mut command = Command::new("pg_ctl");
+    command
+        .arg("--pgdata")
+        .arg(directory)
+        .arg("--log")
+        .arg(directory.join("postgres.log"))
+        .args(["--options", &options, "--wait", "start"]);
+    command
+}
+
+/// A disposable database, stopped and removed when dropped.
+pub struct TempDb {
+    url: String,
+    instance: Instance,
+}
+
+fn run(command: &mut Command) -> Result<String, Box<dyn Error>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| format!("could not run {program}: {error}"))?;
+    if !output.status.success() {
+        Err(format!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))?;
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn free_port() -> Result<u16, Box<dyn Error>> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+impl TempDb {
+    /// Start a database and wait for it to accept connections. Migrations are applied by
+    /// [`crate::migrate`] like for any other database.
+    pub async fn start(config: &TempDbConfig) -> Result<Self, Box<dyn Error>> {
+        let temp_db = match config.runtime {
+            Runtime::Docker => Self::container(&config.image)?,
+            Runtime::Local => Self::cluster()?,
+        };
+        tracing::info!("Started a temporary database at {}", temp_db.url);
+        temp_db.connect().await?.close().await?;
+        Ok(temp_db)
+    }
+
+    fn container(image: &str) -> Result<Self, Box<dyn Error>> {
+        let id = run(&mut docker_run(image))?;
+        // Dropped from here on, so the container is removed if anything below fails.
+        let mut temp_db = Self {
+            url: String::new(),
+            instance: Instance::Container(id.clone()),
+        };
+        let port = run(Command::new("docker").args(["port", &id, "5432/tcp"]))?;
+        let address = port.lines().next().ok_or("the container exposes no port")?;
+        temp_db.url = format!("postgres://postgres:{PASSWORD}@{address}/postgres");
+        Ok(temp_db)
+    }
+
+    fn cluster() -> Result<Self, Box<dyn Error>> {
+        let directory = std::env::temp_dir().join(format!("sql-infer-{}", std::process::id()));
+        run(&mut initdb(&directory))?;
+        let port = free_port()?;
+        let temp_db = Self {
+            url: format!("postgres://postgres@127.0.0.1:{port}/postgres"),
+            instance: Instance::Cluster(directory.clone()),
+        };
+        run(&mut pg_ctl_start(&directory, port))?;
+        Ok(temp_db)
+    }
+
+    /// Connect once the database accepts connections, containers take a few seconds to
+    /// initialize.
+    async fn connect(&self) -> Result<PgConnection, Box<dyn Error>> {
+        let start = Instant::now();
+        loop {
+            match PgConnection::connect(&self.url).await {
+                Ok(conn) => return Ok(conn),
+                Err(error) if start.elapsed() > STARTUP_TIMEOUT => {
+                    Err(format!("the temporary database did not start: {error}"))?
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(250)).await,
+            }
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let stopped = run(&mut self.instance.stop());
+        let stopped = match &self.instance {
+            Instance::Container(_) => stopped,
+            Instance::Cluster(directory) => {
+                // Stopping fails when the server never started, the cluster is removed anyway.
+                match std::fs::remove_dir_all(directory) {
+                    Ok(()) => stopped,
+                    Err(error) => Err(error.into()),
+                }
+            }
+        };
+        match stopped {
+            Ok(_) => tracing::info!("Removed the temporary database"),
+            Err(error) => tracing::warn!("Could not remove the temporary database: {error}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, process::Command};
+
+    use crate::temp_db::{Instance, TempDb, docker_run, pg_ctl_start};
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn databases_only_listen_on_localhost() {
+        let run = docker_run("postgres:17");
+        let run = args(&run);
+        assert!(run.contains(&"--rm".to_string()));
+        assert_eq!(run[run.len() - 2..], ["127.0.0.1::5432", "postgres:17"]);
+
+        let start = pg_ctl_start(Path::new("/tmp/cluster"), 5433);
+        assert_eq!(start.get_program(), "pg_ctl");
+        assert!(
+            args(&start)
+                .contains(&"-k /tmp/cluster -p 5433 -c listen_addresses=127.0.0.1 -F".to_string())
+        );
+        let stop = Instance::Container("abc".to_string()).stop();
+        assert_eq!(args(&stop), ["rm", "--force", "abc"]);
+    }
+
+    #[test]
+    fn clusters_are_removed_when_starting_them_failed() {
+        let directory =
+            std::env::temp_dir().join(format!("sql-infer-{}-cluster", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("PG_VERSION"), "17").unwrap();
+        // No server runs in the directory, so stopping it fails.
+        drop(TempDb {
+            url: String::new(),
+            instance: Instance::Cluster(directory.clone()),
+        });
+        assert!(!directory.exists());
+    }
+}
@@ -0,0 +1,99 @@
+use std::{fmt::Write, time::Duration};
+
+use sql_infer_core::inference::InferenceTimings;
+
+/// Number of queries listed in the report.
+const SLOWEST_QUERIES: usize = 10;
+
+/// Time spent in each phase of a `generate` run.
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    /// Discovering and parsing the query files.
+    pub parse: Duration,
+    /// Connecting to the database, hashing queries for the manifest and cache and prefetching
+    /// metadata.
+    pub setup: Duration,
+    /// Preparing statements, summed over queries so it exceeds the wall time with `--jobs`.
+    pub prepare: Duration,
+    /// Running the metadata passes, summed over queries like `prepare`.
+    pub passes: Duration,
+    /// Generating the code of every target.
+    pub codegen: Duration,
+    /// Writing the targets and expanding `SELECT *` in query files, or printing their diff.
+    pub write: Duration,
+    /// Inference time of each query that was not reused from a previous run.
+    pub queries: Vec<(String, Duration)>,
+}
+
+impl Timings {
+    pub fn add_query(&mut self, name: &str, timings: &InferenceTimings) {
+        self.prepare += timings.prepare;
+        self.passes += timings.passes;
+        self.queries
+            .push((name.to_string(), timings.prepare + timings.passes));
+    }
+
+    /// Human readable report of the phases, the `total` wall time and the slowest queries.
+    pub fn report(&self, total: Duration) -> String {
+        let mut report = String::from("Timings:\n");
+        let phases = [
+            ("parse", self.parse),
+            ("setup", self.setup),
+            ("prepare", self.prepare),
+            ("passes", self.passes),
+            ("codegen", self.codegen),
+            ("write", self.write),
+            ("total", total),
+        ];
+        for (phase, duration) in phases {
+            let _ = writeln!(report, "  {phase:<10}{duration:>12.2?}");
+        }
+        if self.queries.is_empty() {
+            return report;
+        }
+        let mut queries = self.queries.iter().collect::<Vec<_>>();
+        queries.sort_by(|(_, left), (_, right)| right.cmp(left));
+        report.push_str("Slowest queries:\n");
+        let width = queries
+            .iter()
+            .take(SLOWEST_QUERIES)
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        for (name, duration) in queries.into_iter().take(SLOWEST_QUERIES) {
+            let _ = writeln!(report, "  {name:<width$}  {duration:>12.2?}");
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sql_infer_core::inference::InferenceTimings;
+
+    use crate::timings::Timings;
+
+    #[test]
+    fn report_lists_slowest_queries_first() {
+        let mut timings = Timings::default();
+        for (name, millis) in [("fast", 1), ("slow", 30), ("medium", 10)] {
+            timings.add_query(
+                name,
+                &InferenceTimings {
+                    prepare: Duration::from_millis(millis),
+                    passes: Duration::ZERO,
+                },
+            );
+        }
+        let report = timings.report(Duration::from_millis(50));
+        let slowest = report.split("Slowest queries:\n").nth(1).unwrap();
+        let names = slowest
+            .lines()
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["slow", "medium", "fast"]);
+        assert_eq!(timings.prepare, Duration::from_millis(41));
+    }
+}
@@ -1,90 +1,948 @@
-use regex::Regex;
-use std::error::Error;
+use clap::ValueEnum;
+use sqlparser::{
+    ast::{Statement, visit_relations},
+    dialect::PostgreSqlDialect,
+    tokenizer::{Location, Token, Tokenizer, Whitespace},
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    error::Error,
+    iter::once,
+    ops::{ControlFlow, Range},
+};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use crate::{
+    codegen::{OptionalBlock, Returns},
+    config::{Pass, PlaceholderStyle},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ParametrizedQuery {
+    /// The query with `:name` placeholders, as executed by generated code.
+    pub query: String,
+    /// The query with `$n` placeholders, as prepared by Postgres.
     pub raw_query: String,
     pub params: Vec<String>,
+    /// Types declared by `-- param name: type` comments, by parameter.
+    pub param_types: Vec<Option<String>>,
+    /// SQL literals declared by `-- param name: type = default` comments, by parameter.
+    pub param_defaults: Vec<Option<String>>,
+    /// Set when a parameter is used several times.
+    pub repeated: Option<RepeatedParams>,
+    /// Parameters written as `IN :name*`, bound as a list of values.
+    pub expanding: BTreeSet<String>,
+    /// `/*? :name */ ... /*?*/` blocks of `query`.
+    pub blocks: Vec<OptionalBlock>,
+    /// The query with every optional block left out, checked along with the full query.
+    pub without_blocks: Option<Box<ParametrizedQuery>>,
+}
+
+/// The query with its own placeholder at each use of a parameter, so the type Postgres infers
+/// at every use can be compared.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepeatedParams {
+    pub raw_query: String,
+    /// Index into `params` of the parameter used at each placeholder.
+    pub uses: Vec<usize>,
+}
+
+/// A token along with its byte range within the query.
+type SpannedToken = (Token, Range<usize>);
+
+/// Tokens of `query` along with their byte range within it.
+fn tokenize(query: &str) -> Result<Vec<SpannedToken>, Box<dyn Error>> {
+    let dialect = PostgreSqlDialect {};
+    let tokens = Tokenizer::new(&dialect, query)
+        .with_unescape(false)
+        .tokenize_with_location()?;
+    let line_starts = once(0)
+        .chain(query.match_indices('\n').map(|(offset, _)| offset + 1))
+        .collect::<Vec<_>>();
+    // Locations are 1-based lines and columns counted in characters.
+    let offset = |location: Location| {
+        let Some(&start) = line_starts.get((location.line as usize).saturating_sub(1)) else {
+            return query.len();
+        };
+        query[start..]
+            .char_indices()
+            .nth((location.column as usize).saturating_sub(1))
+            .map_or(query.len(), |(offset, _)| start + offset)
+    };
+    Ok(tokens
+        .into_iter()
+        .map(|token| {
+            let range = offset(token.span.start)..offset(token.span.end);
+            (token.token, range)
+        })
+        .collect())
 }
 
-fn split_query(mut query: &str) -> Vec<&str> {
-    let mut split_query = vec![];
-    if query.starts_with('\'') {
-        split_query.push(&query[..1]);
-        query = &query[1..];
-    }
-    let mut in_quotes = false;
-    let mut in_double_quotes = false;
-    let mut last = 0;
-    for (idx, char) in query.char_indices() {
-        // TODO: clean up duplicate
-        // SQL Quotes are escaped by doubling up so we do not check for backslashes.
-        if char == '\'' {
-            in_quotes = !in_quotes;
-            let end = match in_quotes {
-                true => idx,
-                false => idx + 1,
-            };
-            split_query.push(&query[last..end]);
-            last = end;
+/// Argument standing in for the standard input.
+pub const STDIN: &str = "-";
+
+/// Read a query from stdin if `query` is `-`, from the file at `query` if it exists, otherwise
+/// treat `query` as SQL.
+pub fn read_query(query: String) -> Result<String, Box<dyn Error>> {
+    if query == STDIN {
+        return Ok(std::io::read_to_string(std::io::stdin())?);
+    }
+    Ok(match std::fs::exists(&query)? {
+        true => std::fs::read_to_string(query)?,
+        false => query,
+    })
+}
+
+/// Map a character offset into `raw_query`, as produced by [`parse_into_postgres`], back to the
+/// character offset into the original `query`.
+pub fn original_offset(query: &str, raw_query: &str, offset: usize) -> usize {
+    let mut original = query.chars().peekable();
+    let mut raw = raw_query.chars().peekable();
+    let mut original_offset = 0;
+    let mut raw_offset = 0;
+    while raw_offset < offset {
+        let (Some(&original_char), Some(&raw_char)) = (original.peek(), raw.peek()) else {
+            break;
+        };
+        if matches!(original_char, ':' | '?') && raw_char == '$' {
+            // Skip over a placeholder and its replacement.
+            original.next();
+            original_offset += 1;
+            while original
+                .next_if(|char| char.is_ascii_alphanumeric() || *char == '_')
+                .is_some()
+            {
+                original_offset += 1;
+            }
+            raw.next();
+            raw_offset += 1;
+            while raw.next_if(char::is_ascii_digit).is_some() {
+                raw_offset += 1;
+            }
+            continue;
         }
-        // SQL Double quotes are escaped by doubling up so we do not check for backslashes.
-        if char == '\"' {
-            in_double_quotes = !in_double_quotes;
-            let end = match in_double_quotes {
-                true => idx,
-                false => idx + 1,
-            };
-            split_query.push(&query[last..end]);
-            last = end;
+        original.next();
+        raw.next();
+        original_offset += 1;
+        raw_offset += 1;
+    }
+    original_offset
+}
+
+/// `query` with whitespace outside of quotes collapsed and any trailing semicolon removed, so
+/// queries differing only in formatting compare equal. Line breaks are kept as they end comments.
+pub fn normalize_query(query: &str) -> String {
+    let Ok(tokens) = tokenize(query) else {
+        return query.trim().to_string();
+    };
+    let mut normalized = String::new();
+    let mut whitespace = None;
+    for (token, range) in tokens {
+        match token {
+            Token::Whitespace(Whitespace::Space | Whitespace::Tab) => {
+                whitespace = whitespace.or(Some(" "));
+            }
+            Token::Whitespace(Whitespace::Newline) => whitespace = Some("\n"),
+            _ => {
+                normalized += whitespace.take().unwrap_or_default();
+                normalized += &query[range];
+            }
         }
     }
-    split_query.push(&query[last..]);
-    split_query
+    normalized
+        .trim()
+        .trim_end_matches(';')
+        .trim_end()
+        .to_string()
 }
 
-pub fn parse_into_postgres(query: &str) -> Result<ParametrizedQuery, Box<dyn Error>> {
-    /*
-    TODO: Using regex really is not the proper way to parse SQL query identifiers, write a proper tokenizer or use sqlparse.
-     */
-    let mut params = Vec::new();
-    let placeholder_pattern = Regex::new(r":([a-z]|[A-Z]|_)([a-z]|[A-Z]|_|[0-9])*")?;
-    let split_query = split_query(query);
+/// Rewrite the placeholders of `query` to the `$n` placeholders Postgres expects. Strings, quoted
+/// identifiers, comments and casts are left untouched.
+pub fn parse_into_postgres(
+    query: &str,
+    style: PlaceholderStyle,
+) -> Result<ParametrizedQuery, Box<dyn Error>> {
+    let tokens = tokenize(query)?;
+    let mut parsed = match style {
+        PlaceholderStyle::Named => rewrite_named(query, &tokens)?,
+        PlaceholderStyle::QuestionMark => number_question_marks(query, &tokens)?,
+    };
+    annotate(&mut parsed, &tokens)?;
+    let blocks = optional_blocks(&tokens)?;
+    if blocks.is_empty() {
+        return Ok(parsed);
+    }
+    if style != PlaceholderStyle::Named || !native_params(&tokens).is_empty() {
+        Err("optional blocks can only be used with named (:name) placeholders")?;
+    }
+    if let Some(block) = blocks
+        .iter()
+        .find(|block| !parsed.params.contains(&block.param))
+    {
+        Err(format!(
+            "optional block of :{} does not match any parameter",
+            block.param
+        ))?;
+    }
+    let mut without_blocks = String::new();
+    let mut head = 0;
+    for block in &blocks {
+        without_blocks += &query[head..block.start];
+        head = block.end;
+    }
+    without_blocks += &query[head..];
+    let mut without_blocks = rewrite_named(&without_blocks, &tokenize(&without_blocks)?)?;
+    if let Some(param) = parsed
+        .expanding
+        .difference(&without_blocks.expanding)
+        .next()
+    {
+        Err(format!(
+            "list parameter :{param} is only used in optional blocks, which is not supported"
+        ))?;
+    }
+    // Parameters keep their annotations, whichever of them are left.
+    for name in &without_blocks.params {
+        let index = parsed.params.iter().position(|param| param == name);
+        let annotation =
+            |annotations: &[Option<String>]| index.and_then(|i| annotations[i].clone());
+        without_blocks
+            .param_types
+            .push(annotation(&parsed.param_types));
+        without_blocks
+            .param_defaults
+            .push(annotation(&parsed.param_defaults));
+    }
+    parsed.blocks = optional_blocks(&tokenize(&parsed.query)?)?;
+    parsed.without_blocks = Some(Box::new(without_blocks));
+    Ok(parsed)
+}
+
+/// `/*? :name */ ... /*?*/` blocks of a query, each only included when its parameter is given.
+fn optional_blocks(tokens: &[SpannedToken]) -> Result<Vec<OptionalBlock>, Box<dyn Error>> {
+    let mut blocks = vec![];
+    let mut open: Option<(String, usize)> = None;
+    for (token, range) in tokens {
+        let Token::Whitespace(Whitespace::MultiLineComment(comment)) = token else {
+            continue;
+        };
+        let Some(marker) = comment.strip_prefix('?').map(str::trim) else {
+            continue;
+        };
+        if marker.is_empty() {
+            let (param, start) = open
+                .take()
+                .ok_or("`/*?*/` does not close an optional block")?;
+            blocks.push(OptionalBlock {
+                param,
+                start,
+                end: range.end,
+            });
+            continue;
+        }
+        let param = marker
+            .strip_prefix(':')
+            .ok_or_else(|| format!("expected `/*? :<name> */`, found `/*{comment}*/`"))?;
+        if open.is_some() {
+            Err("optional blocks can not be nested")?;
+        }
+        open = Some((param.to_string(), range.start));
+    }
+    if let Some((param, _)) = open {
+        Err(format!(
+            "optional block of :{param} is not closed by `/*?*/`"
+        ))?;
+    }
+    Ok(blocks)
+}
 
+/// Apply the `-- param name: type = default` comments of a query to its parameters, the type
+/// and default are each optional.
+fn annotate(parsed: &mut ParametrizedQuery, tokens: &[SpannedToken]) -> Result<(), Box<dyn Error>> {
+    parsed.param_types = vec![None; parsed.params.len()];
+    parsed.param_defaults = vec![None; parsed.params.len()];
+    for (token, _) in tokens {
+        let Token::Whitespace(Whitespace::SingleLineComment { comment, .. }) = token else {
+            continue;
+        };
+        let Some(annotation) = comment.trim().strip_prefix("param ") else {
+            continue;
+        };
+        let (declaration, default) = match annotation.split_once('=') {
+            Some((declaration, default)) => (declaration, Some(default.trim().to_string())),
+            None => (annotation, None),
+        };
+        let (name, param_type) = match declaration.split_once(':') {
+            Some((name, param_type)) => (name.trim(), Some(param_type.trim().to_string())),
+            None if default.is_some() => (declaration.trim(), None),
+            None => Err(format!(
+                "expected `-- param <name>: <type> = <default>`, found `--{}`",
+                comment.trim_end()
+            ))?,
+        };
+        let index = parsed
+            .params
+            .iter()
+            .position(|param| param == name)
+            .ok_or_else(|| format!("`-- param {name}` does not match any parameter"))?;
+        if param_type.is_some() {
+            parsed.param_types[index] = param_type;
+        }
+        if default.is_some() {
+            parsed.param_defaults[index] = default;
+        }
+    }
+    Ok(())
+}
+
+/// Start of the `IN` or `NOT IN` before the placeholder starting at token `position`, along with
+/// the array comparison replacing it.
+fn in_operator(tokens: &[SpannedToken], position: usize) -> Option<(usize, &'static str)> {
+    let mut words = tokens[..position]
+        .iter()
+        .rev()
+        .filter(|(token, _)| !matches!(token, Token::Whitespace(_)));
+    let (Token::Word(word), range) = words.next()? else {
+        return None;
+    };
+    if !word.value.eq_ignore_ascii_case("in") || word.quote_style.is_some() {
+        return None;
+    }
+    match words.next() {
+        Some((Token::Word(word), not)) if word.value.eq_ignore_ascii_case("not") => {
+            Some((not.start, "<> all"))
+        }
+        _ => Some((range.start, "= any")),
+    }
+}
+
+/// Rewrite `:name` placeholders, a name used several times maps to the same placeholder.
+/// Positional `$n` placeholders are kept as they are.
+fn rewrite_named(
+    query: &str,
+    tokens: &[SpannedToken],
+) -> Result<ParametrizedQuery, Box<dyn Error>> {
+    let mut params = Vec::new();
     let mut postgres_query = String::new();
-    for (id, query) in split_query.into_iter().enumerate() {
-        if id % 2 == 1 {
-            postgres_query += query;
+    let mut named_query = String::new();
+    let mut repeated = RepeatedParams::default();
+    let mut expanding = BTreeSet::new();
+    let mut head = 0;
+    let mut named_head = 0;
+    for (position, window) in tokens.windows(2).enumerate() {
+        let [(Token::Colon, colon), (Token::Word(word), name)] = window else {
+            continue;
+        };
+        if word.quote_style.is_some() || colon.end != name.start {
             continue;
         }
-        let mut head = 0;
-        for matches in placeholder_pattern.captures_iter(query) {
-            let placeholder = matches.get(0).unwrap();
-            let start = placeholder.start();
-            if query
-                .get(..start)
-                .is_some_and(|slice| slice.trim().ends_with(":"))
-            {
-                // Two colons is indicative of casting
-                // We do not handle this inside of the regex as the match would include the character prior
-                continue;
-            }
-            postgres_query += &query[head..start];
-            let param_name = &placeholder.as_str()[1..];
-            let param_index = 1 + params
-                .iter()
-                .position(|param| param == param_name)
-                .unwrap_or_else(|| {
-                    params.push(param_name.to_string());
-                    params.len() - 1
-                });
-            postgres_query += &format!("${param_index}");
-            head = start + placeholder.len();
+        let star = match tokens.get(position + 2) {
+            Some((Token::Mul, star)) if star.start == name.end => Some(star),
+            _ => None,
+        };
+        let param_index = params
+            .iter()
+            .position(|param| *param == word.value)
+            .unwrap_or_else(|| {
+                params.push(word.value.clone());
+                params.len() - 1
+            });
+        // `IN :name*` compares against an array instead, `NOT IN :name*` against all of it.
+        let (start, comparison) = match star {
+            Some(_) => in_operator(tokens, position)
+                .ok_or_else(|| format!("expected IN before the list parameter :{}*", word.value))?,
+            None => (colon.start, ""),
+        };
+        let placeholder = |index: usize| match star {
+            Some(_) => format!("{comparison}(${index})"),
+            None => format!("${index}"),
+        };
+        postgres_query += &query[head..start];
+        postgres_query += &placeholder(param_index + 1);
+        repeated.raw_query += &query[head..start];
+        repeated.uses.push(param_index);
+        repeated.raw_query += &placeholder(repeated.uses.len());
+        head = star.unwrap_or(name).end;
+        if let Some(star) = star {
+            // Generated code binds lists with an expanding `IN :name`.
+            named_query += &query[named_head..star.start];
+            named_head = star.end;
+            expanding.insert(word.value.clone());
         }
-        postgres_query += &query[head..];
     }
+    postgres_query += &query[head..];
+    repeated.raw_query += &query[head..];
+    named_query += &query[named_head..];
+    let native = native_params(tokens);
+    if native.is_empty() {
+        return Ok(ParametrizedQuery {
+            query: named_query,
+            raw_query: postgres_query,
+            repeated: (repeated.uses.len() > params.len()).then_some(repeated),
+            params,
+            expanding,
+            ..Default::default()
+        });
+    }
+    if !params.is_empty() {
+        Err("queries can not mix named (:name) and positional ($1) placeholders")?;
+    }
+    // Generated code binds parameters by name, so positional placeholders are named there.
+    let mut named_query = String::new();
+    let mut head = 0;
+    for (token, range) in tokens {
+        let Token::Placeholder(placeholder) = token else {
+            continue;
+        };
+        let Some(name) = placeholder
+            .strip_prefix('$')
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| native.get(index.wrapping_sub(1)))
+        else {
+            continue;
+        };
+        named_query += &query[head..range.start];
+        named_query += &format!(":{name}");
+        head = range.end;
+    }
+    named_query += &query[head..];
     Ok(ParametrizedQuery {
+        query: named_query,
         raw_query: postgres_query,
+        params: native,
+        ..Default::default()
+    })
+}
+
+/// Replace each `?` placeholder of `query` with the next `$n`, see
+/// [`PlaceholderStyle::QuestionMark`]. Parameters are named like positional placeholders.
+fn number_question_marks(
+    query: &str,
+    tokens: &[SpannedToken],
+) -> Result<ParametrizedQuery, Box<dyn Error>> {
+    if !native_params(tokens).is_empty() {
+        Err("queries can not mix positional ($1) and question mark (?) placeholders")?;
+    }
+    let mut names = param_names(tokens);
+    let mut raw_query = String::new();
+    let mut named_query = String::new();
+    let mut params = vec![];
+    let mut head = 0;
+    for (token, range) in tokens {
+        if *token != Token::Question {
+            continue;
+        }
+        let index = params.len() + 1;
+        let name = names
+            .remove(&index)
+            .unwrap_or_else(|| format!("param{index}"));
+        raw_query += &query[head..range.start];
+        raw_query += &format!("${index}");
+        named_query += &query[head..range.start];
+        named_query += &format!(":{name}");
+        params.push(name);
+        head = range.end;
+    }
+    raw_query += &query[head..];
+    named_query += &query[head..];
+    Ok(ParametrizedQuery {
+        query: named_query,
+        raw_query,
         params,
+        ..Default::default()
     })
 }
+
+/// Parameter names given by `-- $n: name` comments.
+fn param_names(tokens: &[SpannedToken]) -> HashMap<usize, String> {
+    let mut names = HashMap::new();
+    for (token, _) in tokens {
+        let Token::Whitespace(Whitespace::SingleLineComment { comment, .. }) = token else {
+            continue;
+        };
+        let Some((index, name)) = comment.trim().split_once(':') else {
+            continue;
+        };
+        if let Some(index) = index
+            .trim()
+            .strip_prefix('$')
+            .and_then(|index| index.parse::<usize>().ok())
+        {
+            names.insert(index, name.trim().to_string());
+        }
+    }
+    names
+}
+
+/// One of several queries in a file, each following a `-- name: <name>` comment.
+pub struct NamedQuery {
+    pub name: String,
+    pub returns: Option<Returns>,
+    pub query: String,
+    /// Byte offset of `query` within the file.
+    pub offset: usize,
+}
+
+/// Split a file on its `-- name: <name> [:one | :many | :exec]` comments, empty when it has
+/// none and so holds a single query.
+pub fn named_queries(query: &str) -> Result<Vec<NamedQuery>, Box<dyn Error>> {
+    let tokens = tokenize(query)?;
+    let mut markers = vec![];
+    for (token, range) in &tokens {
+        let Token::Whitespace(Whitespace::SingleLineComment { comment, .. }) = token else {
+            continue;
+        };
+        let Some(marker) = comment.trim().strip_prefix("name:") else {
+            continue;
+        };
+        let mut words = marker.split_whitespace();
+        let name = words.next().ok_or("expected a name after `-- name:`")?;
+        let returns = match words.next() {
+            None => None,
+            Some(":one") => Some(Returns::One),
+            Some(":many") => Some(Returns::Many),
+            Some(":exec") => Some(Returns::Exec),
+            Some(other) => Err(format!(
+                "expected :one, :many or :exec after `-- name: {name}`, found {other}"
+            ))?,
+        };
+        markers.push((name.to_string(), returns, range.clone()));
+    }
+    let Some((_, _, first)) = markers.first() else {
+        return Ok(vec![]);
+    };
+    let header = tokens
+        .iter()
+        .take_while(|(_, range)| range.start < first.start)
+        .find(|(token, _)| !matches!(token, Token::Whitespace(_)));
+    if let Some((token, _)) = header {
+        Err(format!(
+            "`{token}` comes before the first `-- name:` comment"
+        ))?;
+    }
+    let ends = markers
+        .iter()
+        .skip(1)
+        .map(|(_, _, range)| range.start)
+        .chain(once(query.len()))
+        .collect::<Vec<_>>();
+    Ok(markers
+        .into_iter()
+        .zip(ends)
+        .map(|((name, returns, range), end)| NamedQuery {
+            name,
+            returns,
+            query: query[range.end..end].to_string(),
+            offset: range.end,
+        })
+        .collect())
+}
+
+/// Settings of a query given by `-- sql-infer: key=value, ...` comments before it, replacing
+/// those of the config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pragma {
+    /// Name of the generated function, instead of the file or `-- name:` comment.
+    pub name: Option<String>,
+    /// `fetch=one`, `many` or `exec`, like the suffix of a `-- name:` comment.
+    pub fetch: Option<Returns>,
+    /// Whether the generated function is async, instead of the mode of the target.
+    pub r#async: Option<bool>,
+    /// Passes turned off by `no-<pass>` settings, e.g. `no-nullability`.
+    pub disabled_passes: BTreeSet<Pass>,
+}
+
+/// Read the `-- sql-infer:` comments leading `query`, later settings replace earlier ones.
+pub fn pragma(query: &str) -> Result<Pragma, Box<dyn Error>> {
+    let mut pragma = Pragma::default();
+    for (token, _) in tokenize(query)? {
+        let comment = match token {
+            Token::Whitespace(Whitespace::SingleLineComment { comment, .. }) => comment,
+            Token::Whitespace(_) => continue,
+            _ => break,
+        };
+        let Some(settings) = comment.trim().strip_prefix("sql-infer:") else {
+            continue;
+        };
+        for setting in settings.split(',').map(str::trim) {
+            if let Some(pass) = setting.strip_prefix("no-") {
+                let pass = Pass::from_str(pass, false)
+                    .map_err(|_| format!("unknown pass `{pass}` in `-- sql-infer: {setting}`"))?;
+                pragma.disabled_passes.insert(pass);
+                continue;
+            }
+            let (key, value) = setting
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| {
+                    format!("expected `key=value` in `-- sql-infer:`, found `{setting}`")
+                })?;
+            match key {
+                "name" if !value.is_empty() => pragma.name = Some(value.to_string()),
+                "fetch" => {
+                    pragma.fetch = Some(match value {
+                        "one" => Returns::One,
+                        "many" => Returns::Many,
+                        "exec" => Returns::Exec,
+                        _ => Err(format!(
+                            "expected one, many or exec for `fetch`, found `{value}`"
+                        ))?,
+                    })
+                }
+                "async" => {
+                    pragma.r#async = Some(value.parse().map_err(|_| {
+                        format!("expected true or false for `async`, found `{value}`")
+                    })?)
+                }
+                _ => Err(format!("unknown setting `{setting}` in `-- sql-infer:`"))?,
+            }
+        }
+    }
+    Ok(pragma)
+}
+
+/// Byte range of the `*` in a query of the form `SELECT * FROM ...`, `None` for any other
+/// query, including those selecting `*` along with other columns.
+pub fn select_star(query: &str) -> Result<Option<Range<usize>>, Box<dyn Error>> {
+    let tokens = tokenize(query)?;
+    let mut significant = tokens
+        .iter()
+        .filter(|(token, _)| !matches!(token, Token::Whitespace(_)));
+    let is_keyword = |token: Option<&SpannedToken>, keyword: &str| matches!(token, Some((Token::Word(word), _)) if word.quote_style.is_none() && word.value.eq_ignore_ascii_case(keyword));
+    if !is_keyword(significant.next(), "select") {
+        return Ok(None);
+    }
+    let Some((Token::Mul, star)) = significant.next() else {
+        return Ok(None);
+    };
+    match is_keyword(significant.next(), "from") {
+        true => Ok(Some(star.clone())),
+        false => Ok(None),
+    }
+}
+
+/// A `-- include: <path>` comment.
+pub struct Include {
+    /// Byte range of the comment within the query.
+    pub range: Range<usize>,
+    pub path: String,
+}
+
+/// `-- include: <path>` directives of `query`.
+pub fn include_directives(query: &str) -> Result<Vec<Include>, Box<dyn Error>> {
+    let mut directives = vec![];
+    for (token, range) in tokenize(query)? {
+        let Token::Whitespace(Whitespace::SingleLineComment { comment, .. }) = token else {
+            continue;
+        };
+        if let Some(path) = comment.trim().strip_prefix("include:") {
+            directives.push(Include {
+                range,
+                path: path.trim().to_string(),
+            });
+        }
+    }
+    Ok(directives)
+}
+
+/// Names of the `$n` placeholders already in a query, taken from `-- $n: name` comments or
+/// defaulting to `paramn`.
+fn native_params(tokens: &[SpannedToken]) -> Vec<String> {
+    let count = tokens
+        .iter()
+        .filter_map(|(token, _)| match token {
+            Token::Placeholder(placeholder) => placeholder.strip_prefix('$')?.parse::<usize>().ok(),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    let mut names = param_names(tokens);
+    (1..=count)
+        .map(|index| {
+            names
+                .remove(&index)
+                .unwrap_or_else(|| format!("param{index}"))
+        })
+        .collect()
+}
+
+/// Names of every relation read or written by the statements, without their schema.
+pub fn referenced_tables(statements: &[Statement]) -> BTreeSet<String> {
+    let mut tables = BTreeSet::new();
+    for statement in statements {
+        let _ = visit_relations(statement, |relation| {
+            if let Some(ident) = relation.0.last().and_then(|part| part.as_ident()) {
+                tables.insert(ident.value.clone());
+            }
+            ControlFlow::<()>::Continue(())
+        });
+    }
+    tables
+}
+
+/// Every relation read or written by the statements as it is written, `schema.name` or a name
+/// alone.
+pub fn referenced_relations(statements: &[Statement]) -> BTreeSet<String> {
+    let mut relations = BTreeSet::new();
+    for statement in statements {
+        let _ = visit_relations(statement, |relation| {
+            let parts = relation
+                .0
+                .iter()
+                .filter_map(|part| part.as_ident())
+                .map(|ident| ident.value.as_str())
+                .collect::<Vec<_>>();
+            relations.insert(parts.join("."));
+            ControlFlow::<()>::Continue(())
+        });
+    }
+    relations
+}
+
+/// Keywords Postgres does not accept as bare column names, those that are not unreserved.
+const KEYWORDS: &str = "\
+    all analyse analyze and any array as asc asymmetric authorization between bigint binary \
+    bit boolean both case cast char character check coalesce collate collation column \
+    concurrently constraint create cross current_catalog current_date current_role \
+    current_schema current_time current_timestamp current_user dec decimal default deferrable \
+    desc distinct do else end except exists extract false fetch float for foreign freeze from \
+    full grant greatest group grouping having ilike in initially inner inout int integer \
+    intersect interval into is isnull join json json_array json_arrayagg json_exists \
+    json_object json_objectagg json_query json_scalar json_serialize json_table json_value \
+    lateral leading least left like limit localtime localtimestamp merge_action national \
+    natural nchar none normalize not notnull null nullif numeric offset on only or order out \
+    outer overlaps overlay placing position precision primary real references returning right \
+    row select session_user setof similar smallint some substring symmetric system_user table \
+    tablesample then time timestamp to trailing treat trim true union unique user using values \
+    varchar variadic verbose when where window with xmlattributes xmlconcat xmlelement \
+    xmlexists xmlforest xmlnamespaces xmlparse xmlpi xmlroot xmlserialize xmltable";
+
+/// `name` as an identifier, quoted the way `quote_ident` does: only when it is not lowercase,
+/// starts with a digit, holds other characters than letters, digits, `_` and `$`, or is a keyword.
+pub fn quote_ident(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_ascii_lowercase() || first == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$')
+        && !KEYWORDS.split_whitespace().any(|keyword| keyword == name);
+    match plain {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sql_infer_core::parser::to_ast;
+
+    use crate::codegen::Returns;
+    use crate::config::{Pass, PlaceholderStyle};
+    use crate::utils::{
+        Pragma, named_queries, normalize_query, original_offset, parse_into_postgres, pragma,
+        quote_ident, referenced_relations, referenced_tables, select_star,
+    };
+
+    #[test]
+    fn normalize_query_ignores_formatting() {
+        assert_eq!(
+            normalize_query("select  id\n\n  from users\twhere name = 'a  b';\n"),
+            "select id\nfrom users where name = 'a  b'"
+        );
+    }
+
+    #[test]
+    fn placeholders_outside_of_strings_comments_and_casts() {
+        let query = "select :id::int8, ':skip', \"col:skip\", $$ :skip $$, $tag$ ' :skip $tag$
+-- ' :skip
+from users /* :skip */ where id = :id and name = :name";
+        let parsed = parse_into_postgres(query, PlaceholderStyle::Named).unwrap();
+        assert_eq!(parsed.params, ["id", "name"]);
+        assert_eq!(
+            parsed.raw_query,
+            "select $1::int8, ':skip', \"col:skip\", $$ :skip $$, $tag$ ' :skip $tag$
+-- ' :skip
+from users /* :skip */ where id = $1 and name = $2"
+        );
+    }
+
+    #[test]
+    fn positional_placeholders_are_kept() {
+        let query = "-- $2: user_name\nselect * from users where id = $1 and name = $2";
+        let parsed = parse_into_postgres(query, PlaceholderStyle::Named).unwrap();
+        assert_eq!(parsed.raw_query, query);
+        assert_eq!(parsed.params, ["param1", "user_name"]);
+        assert_eq!(
+            parsed.query,
+            "-- $2: user_name\nselect * from users where id = :param1 and name = :user_name"
+        );
+        assert!(parse_into_postgres("select $1, :id", PlaceholderStyle::Named).is_err());
+    }
+
+    #[test]
+    fn question_mark_placeholders() {
+        let query = "select * from users where id = ? and name = '?' and email = ?";
+        let parsed = parse_into_postgres(query, PlaceholderStyle::QuestionMark).unwrap();
+        assert_eq!(
+            parsed.raw_query,
+            "select * from users where id = $1 and name = '?' and email = $2"
+        );
+        assert_eq!(parsed.params, ["param1", "param2"]);
+    }
+
+    #[test]
+    fn declared_parameter_types() {
+        let query =
+            "-- param mood: mood\nselect * from users where mood = :mood and id = :id::int8";
+        let parsed = parse_into_postgres(query, PlaceholderStyle::Named).unwrap();
+        assert_eq!(
+            parsed.raw_query.lines().last(),
+            Some("select * from users where mood = $1 and id = $2::int8")
+        );
+        assert_eq!(parsed.param_types, [Some("mood".to_string()), None]);
+        let query = "-- param limit: int4 = 50\n-- param name = 'a = b'\nselect :name limit :limit";
+        let parsed = parse_into_postgres(query, PlaceholderStyle::Named).unwrap();
+        assert_eq!(parsed.param_types, [None, Some("int4".to_string())]);
+        assert_eq!(
+            parsed.param_defaults,
+            [Some("'a = b'".to_string()), Some("50".to_string())]
+        );
+        let query = "-- param missing: int8\nselect :id";
+        assert!(parse_into_postgres(query, PlaceholderStyle::Named).is_err());
+    }
+
+    #[test]
+    fn repeated_parameters_use_their_own_placeholders() {
+        let query = "select * from users where id = :id and price > :id and name = :name";
+        let repeated = parse_into_postgres(query, PlaceholderStyle::Named)
+            .unwrap()
+            .repeated
+            .unwrap();
+        assert_eq!(
+            repeated.raw_query,
+            "select * from users where id = $1 and price > $2 and name = $3"
+        );
+        assert_eq!(repeated.uses, [0, 0, 1]);
+    }
+
+    #[test]
+    fn list_parameters() {
+        let query = "select * from users where id in :ids* and name not in :names*";
+        let parsed = parse_into_postgres(query, PlaceholderStyle::Named).unwrap();
+        assert_eq!(
+            parsed.raw_query,
+            "select * from users where id = any($1) and name <> all($2)"
+        );
+        assert_eq!(
+            parsed.query,
+            "select * from users where id in :ids and name not in :names"
+        );
+        assert_eq!(parsed.expanding.len(), 2);
+        assert!(parse_into_postgres("select :ids*", PlaceholderStyle::Named).is_err());
+    }
+
+    #[test]
+    fn optional_blocks_are_left_out() {
+        let query = "select id from users where true /*? :name */ and name = :name /*?*/ limit :n";
+        let parsed = parse_into_postgres(query, PlaceholderStyle::Named).unwrap();
+        assert_eq!(parsed.params, ["name", "n"]);
+        let block = &parsed.blocks[0];
+        assert_eq!(
+            &parsed.query[block.start..block.end],
+            "/*? :name */ and name = :name /*?*/"
+        );
+        let without_blocks = parsed.without_blocks.unwrap();
+        assert_eq!(
+            without_blocks.raw_query,
+            "select id from users where true  limit $1"
+        );
+        assert_eq!(without_blocks.params, ["n"]);
+        assert!(parse_into_postgres("select 1 /*? :x */", PlaceholderStyle::Named).is_err());
+    }
+
+    #[test]
+    fn several_named_queries() {
+        let file = "-- queries of users\n-- name: get_user :one\nselect 1;\n\n-- name: list_users\nselect 2;\n";
+        let queries = named_queries(file).unwrap();
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].name, "get_user");
+        assert_eq!(queries[0].returns, Some(Returns::One));
+        assert_eq!(queries[0].query, "select 1;\n\n");
+        assert_eq!(queries[1].returns, None);
+        assert!(named_queries("select 1;").unwrap().is_empty());
+        assert!(named_queries("select 1;\n-- name: a\nselect 2;").is_err());
+    }
+
+    #[test]
+    fn pragma_settings() {
+        let query = "-- sql-infer: fetch=one, async=false\n-- sql-infer: name = get_user, no-nullability\nselect 1;\n-- sql-infer: fetch=many";
+        assert_eq!(
+            pragma(query).unwrap(),
+            Pragma {
+                name: Some("get_user".to_string()),
+                fetch: Some(Returns::One),
+                r#async: Some(false),
+                disabled_passes: [Pass::Nullability].into(),
+            }
+        );
+        assert!(pragma("-- sql-infer: no-lineage\nselect 1").is_err());
+        assert!(pragma("-- sql-infer: fetch=all\nselect 1").is_err());
+        assert!(pragma("-- sql-infer: timeout=1\nselect 1").is_err());
+    }
+
+    #[test]
+    fn select_star_position() {
+        let query = "-- all users\nSELECT  * FROM users";
+        assert_eq!(select_star(query).unwrap(), Some(21..22));
+        assert_eq!(select_star("select *, 1 as one from users").unwrap(), None);
+        assert_eq!(select_star("select count(*) from users").unwrap(), None);
+    }
+
+    #[test]
+    fn original_offset_skips_placeholders() {
+        let query = "select * from users where id = :user_id and name = missing";
+        let raw_query = parse_into_postgres(query, PlaceholderStyle::Named)
+            .unwrap()
+            .raw_query;
+        let raw_offset = raw_query.find("missing").unwrap();
+        assert_eq!(
+            original_offset(query, &raw_query, raw_offset),
+            query.find("missing").unwrap()
+        );
+    }
+
+    #[test]
+    fn tables_of_subqueries_and_joins() {
+        let statements = to_ast(
+            "SELECT * FROM public.users u JOIN posts p ON p.user_id = u.id \
+             WHERE u.id IN (SELECT user_id FROM comments)",
+        )
+        .unwrap();
+        assert_eq!(
+            referenced_tables(&statements)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["comments", "posts", "users"]
+        );
+        assert_eq!(
+            referenced_relations(&statements)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["comments", "posts", "public.users"]
+        );
+    }
+
+    #[test]
+    fn identifiers_are_quoted_like_postgres_does() {
+        assert_eq!(quote_ident("user_id"), "user_id");
+        assert_eq!(quote_ident("_x$1"), "_x$1");
+        assert_eq!(quote_ident("name"), "name");
+        assert_eq!(quote_ident("userId"), "\"userId\"");
+        assert_eq!(quote_ident("first name"), "\"first name\"");
+        assert_eq!(quote_ident("1st"), "\"1st\"");
+        assert_eq!(quote_ident("order"), "\"order\"");
+        assert_eq!(quote_ident("?column?"), "\"?column?\"");
+        assert_eq!(quote_ident("a\"b"), "\"a\"\"b\"");
+    }
+}
@@ -1,17 +1,25 @@
 pub mod datatypes;
+pub mod explain;
 pub mod nullability;
 
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::{PgTypeInfo, PgTypeKind};
+use sqlx::postgres::types::Oid;
+use sqlx::postgres::{PgStatement, PgTypeInfo, PgTypeKind};
 use sqlx::{Either, Pool, Postgres, Statement, TypeInfo, query};
-use sqlx::{Executor, query_as};
+use sqlx::{Executor, query_as, query_scalar};
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{error::Error, fmt};
 
-use crate::parser::{Column, find_fields, to_ast};
+use crate::escape_ident;
+use crate::parser::{
+    CallArgument, Column, CopyInto, FunctionCall, Lineage, SplitStatement, StatementKind,
+    called_procedure, copy_from_stdin, find_named_fields, modifies_rows, projection_functions,
+    split_statements, statement_kind, to_ast,
+};
 use tracing::warn;
 
 pub trait UseInformationSchema {
@@ -21,10 +29,23 @@ pub trait UseInformationSchema {
         source: &Column,
         column: &mut QueryItem,
     );
+
+    /// Why the pass could not tell something about `column`, if it could not.
+    fn explain(
+        &self,
+        _schemas: &HashMap<Column, InformationSchema>,
+        _source: &Column,
+        _column: &QueryItem,
+    ) -> Option<String> {
+        None
+    }
 }
 
 pub struct Passes {
     pub information_schema: Vec<Box<dyn UseInformationSchema>>,
+    /// Resolve the source of output columns from the query plan, falling back to the parser
+    /// for columns the plan does not tell.
+    pub explain_origins: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -43,7 +64,7 @@ pub struct QueryItem {
 #[derive(Debug, Clone)]
 pub enum CheckerError {
     UnrecognizedType { sql_type: String },
-    RepeatingParameterName { name: String },
+    DuplicateColumnName { name: String },
 }
 
 impl fmt::Display for CheckerError {
@@ -52,7 +73,10 @@ impl fmt::Display for CheckerError {
             Self::UnrecognizedType { sql_type } => {
                 write!(f, "Unrecognized SQL Type {sql_type}")
             }
-            Self::RepeatingParameterName { name } => write!(f, "repeating parameter name {name}"),
+            Self::DuplicateColumnName { name } => write!(
+                f,
+                "several columns are named {name}, give them distinct names with AS"
+            ),
         }
     }
 }
@@ -66,10 +90,44 @@ pub enum Nullability {
     Unknown,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct QueryTypes {
     pub input: Box<[QueryItem]>,
     pub output: Box<[QueryItem]>,
+    /// Problems that did not prevent inference but may have made it less precise.
+    pub warnings: Box<[String]>,
+    /// Why some output columns are less precise than they could be, e.g. of unknown
+    /// nullability.
+    #[serde(default)]
+    pub notes: Box<[String]>,
+    /// The query writes rows without returning any, its result is the number of rows affected.
+    #[serde(default)]
+    pub rows_affected: bool,
+    /// The most disruptive kind among the statements of the query.
+    #[serde(default)]
+    pub kind: StatementKind,
+    /// What each output column is computed from, in the order of `output`, when it is known.
+    #[serde(default)]
+    pub lineage: Box<[Option<Lineage>]>,
+    /// Set for `COPY ... FROM STDIN`, whose rows are passed instead of parameters.
+    #[serde(default)]
+    pub copy_from: Option<CopyFrom>,
+}
+
+/// Table loaded by a `COPY ... FROM STDIN` statement along with the columns of each row.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CopyFrom {
+    pub schema: Option<String>,
+    pub table: String,
+    pub columns: Box<[QueryItem]>,
+}
+
+/// Result of resolving the source of every output column.
+pub(crate) struct Resolved {
+    /// Problems that made inference less precise.
+    pub warnings: Vec<String>,
+    pub notes: Vec<String>,
+    pub lineage: Vec<Option<Lineage>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -124,6 +182,8 @@ pub enum SqlType {
     },
     // Array
     Array(Box<SqlType>),
+    // Composite types and records returned by functions
+    Record(Box<[QueryItem]>),
     // Unknown types
     Unknown,
 }
@@ -193,6 +253,15 @@ impl Display for SqlType {
             SqlType::VarBit { length: None } => write!(f, "varbit"),
             SqlType::Unknown => write!(f, "unknown"),
             SqlType::Array(inner) => write!(f, "[{inner}; N]"),
+            SqlType::Record(fields) => write!(
+                f,
+                "record({})",
+                fields
+                    .iter()
+                    .map(|field| format!("{}: {}", field.name, field.sql_type))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
             SqlType::Enum { name, tags } => write!(f, "{name}: {}", tags.join(", ")),
         }
     }
@@ -222,6 +291,19 @@ impl SqlType {
         )
     }
 
+    /// Type of `arr[i]`, the element type, or with `slice` of `arr[i:j]`, the array type.
+    pub fn subscript(&self, slice: bool) -> Option<SqlType> {
+        match (self, slice) {
+            (SqlType::Array(_), true) => Some(self.clone()),
+            // Postgres arrays have no nesting in their type, `int[][]` is `int[]`.
+            (SqlType::Array(inner), false) => match &**inner {
+                SqlType::Array(_) => inner.subscript(false),
+                inner => Some(inner.clone()),
+            },
+            _ => None,
+        }
+    }
+
     fn numeric_rank(&self) -> Option<u8> {
         // https://www.postgresql.org/docs/current/functions-math.html
         Some(match self {
@@ -250,6 +332,18 @@ impl SqlType {
             PgTypeKind::Array(inner) => {
                 SqlType::Array(Box::new(SqlType::from_pg_type_info(inner)?))
             }
+            PgTypeKind::Composite(fields) => SqlType::Record(
+                fields
+                    .iter()
+                    .map(|(name, type_info)| {
+                        Ok::<_, Box<dyn Error>>(QueryItem {
+                            name: name.clone(),
+                            sql_type: SqlType::from_pg_type_info(type_info)?,
+                            nullable: Nullability::Unknown,
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
             _ => SqlType::from_str(type_info.name())?,
         })
     }
@@ -278,7 +372,7 @@ impl SqlType {
             "VARBIT" => Self::VarChar { length: None },
             "TEXT" => Self::Text,
             "JSON" => Self::Json,
-            "JSONB" => Self::Json,
+            "JSONB" => Self::Jsonb,
             "DOUBLE PRECISION" | "FLOAT8" => Self::Float8,
             "REAL" | "FLOAT4" => Self::Float4,
             "INTERVAL" => Self::Interval,
@@ -301,11 +395,94 @@ pub struct TableSchema {
     pub columns: HashMap<String, InformationSchema>,
 }
 
+/// Information schema of every column, loaded in a single query so inference does not look up
+/// each column separately.
+#[derive(Debug, Clone, Default)]
+pub struct PrefetchedColumns {
+    /// Columns by table and column name, the table being qualified by its schema or, for the
+    /// table found first on the `search_path`, left unqualified like in queries.
+    columns: HashMap<(String, String), InformationSchema>,
+    /// Columns by table OID and attribute number, as reported for plain column references.
+    origins: HashMap<ColumnOrigin, InformationSchema>,
+}
+
+impl PrefetchedColumns {
+    /// Load the columns of the tables in `schemas`, or of every table when `schemas` is empty.
+    pub async fn load(pool: &Pool<Postgres>, schemas: &[String]) -> Result<Self, Box<dyn Error>> {
+        let records = query!(
+            r#"select
+    a.attrelid as "table_oid!: Oid",
+    a.attnum as "attnum!",
+    n.nspname::text as "table_schema!",
+    c.relname::text as "table_name!",
+    a.attname::text as "column_name!",
+    pg_table_is_visible(c.oid) as "visible!",
+    not a.attnotnull as "is_nullable",
+    case when a.atttypid in (1042, 1043) and a.atttypmod > 0 then a.atttypmod - 4 end as "character_maximum_length",
+    case when a.atttypid = 1700 and a.atttypmod > 0 then ((a.atttypmod - 4) >> 16) & 65535 end as "numeric_precision",
+    case when a.atttypid = 1700 and a.atttypmod > 0 then 10 end as "numeric_precision_radix",
+    case when a.atttypid = 1700 and a.atttypmod > 0 then (a.atttypmod - 4) & 65535 end as "numeric_scale",
+    pg_get_expr(d.adbin, d.adrelid) as "column_default"
+from
+    pg_attribute a
+    join pg_class c on c.oid = a.attrelid
+    join pg_namespace n on n.oid = c.relnamespace
+    left join pg_attrdef d on d.adrelid = a.attrelid and d.adnum = a.attnum
+where
+    c.relkind in ('r', 'p', 'v', 'm', 'f')
+    and a.attnum > 0
+    and not a.attisdropped
+    and (cardinality($1::text[]) = 0 or n.nspname = any($1));"#,
+            schemas
+        )
+        .fetch_all(pool)
+        .await?;
+        let mut prefetched = Self::default();
+        for record in records {
+            let schema = InformationSchema {
+                is_nullable: record.is_nullable,
+                character_maximum_length: record.character_maximum_length,
+                numeric_precision: record.numeric_precision,
+                numeric_precision_radix: record.numeric_precision_radix,
+                numeric_scale: record.numeric_scale,
+                column_default: record.column_default,
+            };
+            let qualified = format!("{}.{}", record.table_schema, record.table_name);
+            if record.visible {
+                prefetched.columns.insert(
+                    (record.table_name, record.column_name.clone()),
+                    schema.clone(),
+                );
+            }
+            prefetched
+                .columns
+                .insert((qualified, record.column_name), schema.clone());
+            prefetched
+                .origins
+                .insert((record.table_oid, record.attnum), schema);
+        }
+        Ok(prefetched)
+    }
+
+    pub fn get(&self, table: &str, column: &str) -> Option<&InformationSchema> {
+        self.columns.get(&(table.to_string(), column.to_string()))
+    }
+
+    /// Information schema of the column Postgres reported as `origin`.
+    pub fn origin(&self, origin: ColumnOrigin) -> Option<&InformationSchema> {
+        self.origins.get(&origin)
+    }
+}
+
 async fn get_information_schema(
     pool: &Pool<Postgres>,
+    prefetched: Option<&PrefetchedColumns>,
     table: &str,
     column: &str,
 ) -> Result<Option<InformationSchema>, Box<dyn Error>> {
+    if let Some(prefetched) = prefetched {
+        return Ok(prefetched.get(table, column).cloned());
+    }
     let query = query_as!(
         InformationSchema,
         "select
@@ -326,18 +503,69 @@ where
     Ok(query.fetch_optional(pool).await?)
 }
 
+/// Table and attribute number Postgres reports as the origin of a result column, given for
+/// columns that are a plain reference to a table column.
+pub type ColumnOrigin = (Oid, i16);
+
+/// Information schema of the column `attnum` of the table `table`, looked up by OID so
+/// neither aliases nor tables of the same name in other schemas get in the way.
+async fn get_origin_schema(
+    pool: &Pool<Postgres>,
+    prefetched: Option<&PrefetchedColumns>,
+    origin: ColumnOrigin,
+) -> Result<Option<InformationSchema>, Box<dyn Error>> {
+    if let Some(prefetched) = prefetched {
+        return Ok(prefetched.origin(origin).cloned());
+    }
+    let (table, attnum) = origin;
+    let query = query_as!(
+        InformationSchema,
+        r#"select
+    not a.attnotnull as "is_nullable",
+    case when a.atttypid in (1042, 1043) and a.atttypmod > 0 then a.atttypmod - 4 end as "character_maximum_length",
+    case when a.atttypid = 1700 and a.atttypmod > 0 then ((a.atttypmod - 4) >> 16) & 65535 end as "numeric_precision",
+    case when a.atttypid = 1700 and a.atttypmod > 0 then 10 end as "numeric_precision_radix",
+    case when a.atttypid = 1700 and a.atttypmod > 0 then (a.atttypmod - 4) & 65535 end as "numeric_scale",
+    pg_get_expr(d.adbin, d.adrelid) as "column_default"
+from
+    pg_attribute a
+    left join pg_attrdef d on d.adrelid = a.attrelid and d.adnum = a.attnum
+where
+    a.attrelid = $1
+    and a.attnum = $2;"#,
+        table,
+        attnum,
+    );
+    Ok(query.fetch_optional(pool).await?)
+}
+
+/// Table column `source` refers to, possibly through casts or as the nullable side of a join.
+fn column_reference(source: &Column) -> Option<&Column> {
+    match source {
+        Column::DependsOn { .. } => Some(source),
+        Column::Maybe { column } => column_reference(column),
+        Column::Cast { source, .. } => column_reference(source),
+        _ => None,
+    }
+}
+
 pub async fn get_all_info_schema(
     pool: &Pool<Postgres>,
+    prefetched: Option<&PrefetchedColumns>,
     source: &Column,
     map: &mut HashMap<Column, InformationSchema>,
 ) -> Result<Option<InformationSchema>, Box<dyn Error>> {
     let schema = match source {
-        Column::DependsOn { table, column } => get_information_schema(pool, table, column).await?,
-        Column::Maybe { column } => Box::pin(get_all_info_schema(pool, column, map)).await?,
+        Column::DependsOn { table, column } => {
+            get_information_schema(pool, prefetched, table, column).await?
+        }
+        Column::Maybe { column } => {
+            Box::pin(get_all_info_schema(pool, prefetched, column, map)).await?
+        }
         Column::Either { left, right } => {
             let future = Box::pin(async {
-                let left = get_all_info_schema(pool, left, map).await?;
-                let right = get_all_info_schema(pool, right, map).await?;
+                let left = get_all_info_schema(pool, prefetched, left, map).await?;
+                let right = get_all_info_schema(pool, prefetched, right, map).await?;
                 Ok::<_, Box<dyn Error>>((left, right))
             });
             let (left, right) = future.await?;
@@ -349,10 +577,23 @@ pub async fn get_all_info_schema(
             }
         }
         Column::Unknown { .. } => None,
-        Column::Cast { source, .. } => Box::pin(get_all_info_schema(pool, source, map)).await?,
+        Column::Cast { source, .. } => {
+            Box::pin(get_all_info_schema(pool, prefetched, source, map)).await?
+        }
         Column::BinaryOp { left, right, .. } => {
-            Box::pin(get_all_info_schema(pool, left, map)).await?;
-            Box::pin(get_all_info_schema(pool, right, map)).await?;
+            Box::pin(get_all_info_schema(pool, prefetched, left, map)).await?;
+            Box::pin(get_all_info_schema(pool, prefetched, right, map)).await?;
+            None
+        }
+        Column::Function { args, .. } => {
+            for arg in args {
+                Box::pin(get_all_info_schema(pool, prefetched, arg, map)).await?;
+            }
+            None
+        }
+        // The schema describes the array rather than its elements.
+        Column::Subscript { source, .. } => {
+            Box::pin(get_all_info_schema(pool, prefetched, source, map)).await?;
             None
         }
         Column::Value(_) => None,
@@ -365,21 +606,23 @@ pub async fn get_all_info_schema(
 
 pub async fn get_column_information_schema(
     pool: &Pool<Postgres>,
+    prefetched: Option<&PrefetchedColumns>,
     source: &Column,
 ) -> Result<(Column, Option<InformationSchema>), Box<dyn Error>> {
     match source {
         Column::DependsOn { table, column } => Ok((
             source.clone(),
-            get_information_schema(pool, table, column).await?,
+            get_information_schema(pool, prefetched, table, column).await?,
         )),
         Column::Maybe { column } => {
-            let (column, schema) = Box::pin(get_column_information_schema(pool, column)).await?;
+            let (column, schema) =
+                Box::pin(get_column_information_schema(pool, prefetched, column)).await?;
             Ok((column.maybe(), schema))
         }
         Column::Either { left, right } => {
             let future = Box::pin(async {
-                let left = get_column_information_schema(pool, left).await?;
-                let right = get_column_information_schema(pool, right).await?;
+                let left = get_column_information_schema(pool, prefetched, left).await?;
+                let right = get_column_information_schema(pool, prefetched, right).await?;
                 Ok::<_, Box<dyn Error>>((left, right))
             });
             let ((left_col, left), (right_col, right)) = future.await?;
@@ -392,98 +635,554 @@ pub async fn get_column_information_schema(
         }
         Column::Unknown { .. } => Ok((source.clone(), None)),
         Column::Cast { source, data_type } => {
-            let (column, schema) = Box::pin(get_column_information_schema(pool, source)).await?;
+            let (column, schema) =
+                Box::pin(get_column_information_schema(pool, prefetched, source)).await?;
             Ok((column.cast(data_type.clone()), schema))
         }
-        Column::BinaryOp { .. } => Ok((source.clone(), None)),
+        Column::BinaryOp { .. } | Column::Function { .. } | Column::Subscript { .. } => {
+            Ok((source.clone(), None))
+        }
         Column::Value(_) => Ok((source.clone(), None)),
     }
 }
 
 pub(crate) async fn update_with_info(
     pool: &Pool<Postgres>,
+    prefetched: Option<&PrefetchedColumns>,
     source: &Column,
+    origin: Option<ColumnOrigin>,
     item: &mut QueryItem,
     passes: &Passes,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<Vec<String>, Box<dyn Error>> {
     let mut map = HashMap::new();
-    get_all_info_schema(pool, source, &mut map).await?;
+    get_all_info_schema(pool, prefetched, source, &mut map).await?;
+    // The origin Postgres reports is more reliable than the table name the parser resolved.
+    if let Some(origin) = origin
+        && let Some(reference) = column_reference(source)
+        && let Some(schema) = get_origin_schema(pool, prefetched, origin).await?
+    {
+        map.insert(reference.clone(), schema);
+    }
+    let mut notes = vec![];
     for pass in &passes.information_schema {
         pass.apply(&map, source, item);
+        notes.extend(pass.explain(&map, source, item));
     }
-    Ok(())
+    Ok(notes)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn apply_passes(
     pool: &Pool<Postgres>,
+    prefetched: Option<&PrefetchedColumns>,
     query: &str,
+    statement: &sqlparser::ast::Statement,
+    params: &[PgTypeInfo],
+    origins: &[Option<ColumnOrigin>],
     output_types: &mut [QueryItem],
     passes: &Passes,
-) -> Result<(), Box<dyn Error>> {
-    let statement = to_ast(query)?;
-    let statement = statement.first().ok_or("Empty query")?;
+) -> Result<Resolved, Box<dyn Error>> {
     let mut errors: Vec<String> = vec![];
+    let mut notes = vec![];
+    let mut lineage = vec![];
 
-    let fields = find_fields(statement)?;
-    for output in output_types.iter_mut() {
-        match fields.get(&output.name) {
+    let explain = passes.explain_origins && !passes.information_schema.is_empty();
+    let fields = match find_named_fields(statement) {
+        Ok(fields) => fields,
+        // The plan may still tell the source of the columns.
+        Err(err) if explain => {
+            tracing::debug!("Could not resolve the columns from the query\n {err}");
+            vec![]
+        }
+        Err(err) => Err(err)?,
+    };
+    // Outputs of procedures come from their `INOUT` parameters, which are not followed.
+    let procedure_output =
+        matches!(statement, sqlparser::ast::Statement::Call(_)).then(|| Column::Unknown {
+            sql: statement.to_string(),
+        });
+    let explained = match explain {
+        true => explain::explain_fields(pool, query, params)
+            .await
+            .unwrap_or_else(|err| {
+                tracing::debug!("Could not resolve the columns from the plan\n {err}");
+                vec![]
+            }),
+        false => vec![],
+    };
+    let names = output_types
+        .iter()
+        .map(|output| output.name.clone())
+        .collect::<Vec<_>>();
+    for (index, output) in output_types.iter_mut().enumerate() {
+        let origin = origins.get(index).copied().flatten();
+        let explained = explained
+            .get(index)
+            .filter(|column| !matches!(column, Column::Unknown { .. }));
+        // Columns sharing a name, kept when duplicates are renamed, are matched in order.
+        let occurrence = names[..index]
+            .iter()
+            .filter(|name| **name == output.name)
+            .count();
+        let field = fields
+            .iter()
+            .filter(|(name, _)| *name == output.name)
+            .nth(occurrence)
+            .and_then(|(_, column)| column.as_ref());
+        let column = explained.or(field).or(procedure_output.as_ref());
+        lineage.push(column.map(Lineage::from));
+        match column {
             Some(column) => {
-                update_with_info(pool, column, output, passes).await?;
+                notes.extend(
+                    update_with_info(pool, prefetched, column, origin, output, passes).await?,
+                );
             }
             None => errors.push(format!("not provided with info for {}", output.name)),
         }
     }
-    for error in errors {
+    for error in &errors {
         warn!("{error}");
     }
 
-    Ok(())
+    Ok(Resolved {
+        warnings: errors,
+        notes,
+        lineage,
+    })
+}
+
+/// Suffix every name equal to an earlier one with `_2`, `_3` and so on, skipping names already
+/// in `names`, so `a, a, a_2` becomes `a, a_3, a_2`.
+pub fn deduplicate_names(names: &mut [String]) {
+    let mut taken = names.iter().cloned().collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    for name in names.iter_mut() {
+        if seen.insert(name.clone()) {
+            continue;
+        }
+        let renamed = (2..)
+            .map(|suffix| format!("{name}_{suffix}"))
+            .find(|renamed| !taken.contains(renamed))
+            .expect("a suffix is always free");
+        taken.insert(renamed.clone());
+        *name = renamed;
+    }
+}
+
+/// Suffix every column named like an earlier one, see [`deduplicate_names`].
+fn rename_duplicate_columns(columns: &mut [QueryItem]) {
+    let mut names = columns
+        .iter()
+        .map(|column| column.name.clone())
+        .collect::<Vec<_>>();
+    deduplicate_names(&mut names);
+    for (column, name) in columns.iter_mut().zip(names) {
+        column.name = name;
+    }
+}
+
+/// Time spent on each step of inferring a query.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InferenceTimings {
+    /// Preparing the statement on the database.
+    pub prepare: Duration,
+    /// Running the information schema passes, including their metadata lookups.
+    pub passes: Duration,
+}
+
+/// Parameter types to prepare a statement with, `None` leaves the type of a parameter to
+/// Postgres.
+async fn declared_param_types(
+    pool: &Pool<Postgres>,
+    param_types: &[Option<String>],
+) -> Result<Vec<PgTypeInfo>, Box<dyn Error>> {
+    let mut types = Vec::with_capacity(param_types.len());
+    for param_type in param_types {
+        let oid = match param_type {
+            Some(param_type) => {
+                query_scalar::<_, Oid>("select $1::regtype::oid")
+                    .bind(param_type)
+                    .fetch_one(pool)
+                    .await?
+            }
+            None => Oid(0),
+        };
+        types.push(PgTypeInfo::with_oid(oid));
+    }
+    Ok(types)
+}
+
+/// Parameter types of a prepared statement.
+fn prepared_params<'a>(prepared: &'a PgStatement<'_>) -> &'a [PgTypeInfo] {
+    match prepared.parameters() {
+        Some(Either::Left(parameters)) => parameters,
+        /*
+        PgStatement::<'_>::parameters is defined as following:
+        Some(Either::Left(&self.metadata.parameters))
+        */
+        _ => unreachable!(),
+    }
+}
+
+/// Prepare each statement of a query holding several, e.g. a `SET LOCAL` before a `SELECT`.
+/// The result is the one of the last statement, along with its SQL, and each parameter is
+/// typed by the first statement using it. Statements are only prepared, so one
+/// can not depend on objects created by an earlier one.
+async fn prepare_statements<'q>(
+    pool: &Pool<Postgres>,
+    statements: &'q [SplitStatement],
+    param_types: &[Option<String>],
+) -> Result<(PgStatement<'q>, &'q str, Vec<PgTypeInfo>), Box<dyn Error>> {
+    let count = statements
+        .iter()
+        .filter_map(|statement| statement.placeholders.last().copied())
+        .max()
+        .unwrap_or(0)
+        .max(param_types.len());
+    let mut known = declared_param_types(pool, param_types)
+        .await?
+        .into_iter()
+        .map(|param| (param.oid() != Some(Oid(0))).then_some(param))
+        .collect::<Vec<_>>();
+    known.resize(count, None);
+    let mut last = None;
+    for (position, statement) in statements.iter().enumerate() {
+        let max = statement.placeholders.last().copied().unwrap_or(0);
+        let types = (1..=max)
+            .map(|index| match &known[index - 1] {
+                Some(param) => param.clone(),
+                None if statement.placeholders.contains(&index) => PgTypeInfo::with_oid(Oid(0)),
+                // Not used by this statement nor typed by an earlier one, any type does.
+                None => PgTypeInfo::with_name("text"),
+            })
+            .collect::<Vec<_>>();
+        // Error positions of later statements would not match the query.
+        let prepared = match position {
+            0 => pool.prepare_with(&statement.sql, &types).await?,
+            _ => pool
+                .prepare_with(&statement.sql, &types)
+                .await
+                .map_err(|err| format!("statement {}: {err}", position + 1))?,
+        };
+        for (index, param) in prepared_params(&prepared).iter().enumerate() {
+            if statement.placeholders.contains(&(index + 1)) && known[index].is_none() {
+                known[index] = Some(param.clone());
+            }
+        }
+        last = Some((prepared, statement.sql.as_str()));
+    }
+    let (prepared, sql) = last.ok_or("Empty query")?;
+    let params = known
+        .into_iter()
+        .enumerate()
+        .map(|(index, param)| param.ok_or_else(|| format!("parameter ${} is not used", index + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((prepared, sql, params))
 }
 
 pub(crate) async fn check_statement(
     pool: &Pool<Postgres>,
+    prefetched: Option<&PrefetchedColumns>,
     query: &str,
+    param_types: &[Option<String>],
+    param_names: &[String],
     passes: &Passes,
-) -> Result<QueryTypes, Box<dyn Error>> {
+    rename_duplicates: bool,
+) -> Result<(QueryTypes, InferenceTimings), Box<dyn Error>> {
     use sqlx::Column;
-    let prepared = pool.prepare(query).await?;
+    // Parse errors only surface once Postgres prepared the query, as its errors tell where
+    // the query is wrong.
+    let ast = to_ast(query);
+    if let Some(copy) = ast
+        .as_deref()
+        .ok()
+        .and_then(<[_]>::first)
+        .and_then(copy_from_stdin)
+    {
+        return infer_copy(pool, prefetched, copy, passes).await;
+    }
+    let start = Instant::now();
+    let statements = split_statements(query)?;
+    let (prepared, query, parameters) = match statements.len() {
+        0 | 1 => {
+            let prepared = match param_types.iter().any(Option::is_some) {
+                true => {
+                    let param_types = declared_param_types(pool, param_types).await?;
+                    pool.prepare_with(query, &param_types).await?
+                }
+                false => pool.prepare(query).await?,
+            };
+            let parameters = prepared_params(&prepared).to_vec();
+            (prepared, query, parameters)
+        }
+        _ => prepare_statements(pool, &statements, param_types).await?,
+    };
+    let prepare = start.elapsed();
+    let ast = ast?;
+    let kind = ast.iter().map(statement_kind).max().unwrap_or_default();
+    // The result is the one of the last statement.
+    let statement = ast.last().ok_or("Empty query")?;
     let mut result_types = Vec::with_capacity(prepared.columns().len());
+    let origins = prepared
+        .columns()
+        .iter()
+        .map(|column| column.relation_id().zip(column.relation_attribute_no()))
+        .collect::<Vec<_>>();
+    let calls = projection_functions(statement);
     let mut names = HashSet::new();
-    for column in prepared.columns() {
-        if !names.insert(column.name()) {
-            Err(CheckerError::RepeatingParameterName {
+    for (index, column) in prepared.columns().iter().enumerate() {
+        if !names.insert(column.name()) && !rename_duplicates {
+            Err(CheckerError::DuplicateColumnName {
                 name: column.name().to_string(),
             })?;
         }
+        let call = calls.get(index).and_then(Option::as_ref);
+        let sql_type = match call {
+            // Postgres only describes the columns of records returned by functions to callers
+            // selecting from the function.
+            Some(call) if column.type_info().name() == "RECORD" => get_function_result(pool, call)
+                .await?
+                .unwrap_or(SqlType::Unknown),
+            _ => SqlType::from_pg_type_info(column.type_info())?,
+        };
         result_types.push(QueryItem {
             name: column.name().to_string(),
-            sql_type: SqlType::from_pg_type_info(column.type_info())?,
+            sql_type,
             nullable: Nullability::Unknown,
         });
     }
+    let procedure_params = match called_procedure(statement) {
+        Some((call, args)) => procedure_param_names(pool, &call, &args).await?,
+        None => HashMap::new(),
+    };
     let mut input_types = vec![];
-    match prepared.parameters() {
-        Some(Either::Left(parameters)) => {
-            for (param, name) in parameters.iter().zip(parameters.iter()) {
-                input_types.push(QueryItem {
-                    name: name.to_string(),
-                    sql_type: SqlType::from_pg_type_info(param)?,
-                    nullable: Nullability::Unknown,
-                });
-            }
+    for (index, param) in parameters.iter().enumerate() {
+        // Parameters without a name are named after the procedure parameter they are passed
+        // as, or else after their placeholder.
+        let name = match param_names
+            .get(index)
+            .or_else(|| procedure_params.get(&(index + 1)))
+        {
+            Some(name) => name.clone(),
+            None => format!("${}", index + 1),
+        };
+        input_types.push(QueryItem {
+            name,
+            sql_type: SqlType::from_pg_type_info(param)?,
+            nullable: Nullability::Unknown,
+        });
+    }
+    let start = Instant::now();
+    let utility = statement_kind(statement) == StatementKind::Utility;
+    let Resolved {
+        warnings,
+        notes,
+        lineage,
+    } = match utility {
+        // Outputs of utility statements, e.g. `SHOW`, do not come from tables.
+        true => Resolved {
+            warnings: vec![],
+            notes: vec![],
+            lineage: vec![None; result_types.len()],
+        },
+        false => {
+            apply_passes(
+                pool,
+                prefetched,
+                query,
+                statement,
+                &parameters,
+                &origins,
+                &mut result_types,
+                passes,
+            )
+            .await?
         }
-        /*
-        PgStatement::<'_>::parameters is defined as following:
-        Some(Either::Left(&self.metadata.parameters))
-        */
-        _ => unreachable!(),
     };
-    apply_passes(pool, query, &mut result_types, passes).await?;
+    if rename_duplicates {
+        rename_duplicate_columns(&mut result_types);
+    }
+    let rows_affected = result_types.is_empty() && modifies_rows(statement);
+    let timings = InferenceTimings {
+        prepare,
+        passes: start.elapsed(),
+    };
 
-    Ok(QueryTypes {
-        input: input_types.into_boxed_slice(),
-        output: result_types.into_boxed_slice(),
-    })
+    Ok((
+        QueryTypes {
+            input: input_types.into_boxed_slice(),
+            output: result_types.into_boxed_slice(),
+            warnings: warnings.into_boxed_slice(),
+            notes: notes.into_boxed_slice(),
+            rows_affected,
+            kind,
+            lineage: lineage.into_boxed_slice(),
+            copy_from: None,
+        },
+        timings,
+    ))
+}
+
+/// Types of the rows of a `COPY ... FROM STDIN`, those of the columns selected from the table.
+async fn infer_copy(
+    pool: &Pool<Postgres>,
+    prefetched: Option<&PrefetchedColumns>,
+    copy: CopyInto,
+    passes: &Passes,
+) -> Result<(QueryTypes, InferenceTimings), Box<dyn Error>> {
+    let table = match &copy.schema {
+        Some(schema) => format!("{}.{}", escape_ident(schema), escape_ident(&copy.table)),
+        None => escape_ident(&copy.table),
+    };
+    let mut columns = copy.columns.clone();
+    if columns.is_empty() {
+        // Columns are listed as the parser does not follow `*`.
+        let select = format!("select * from {table}");
+        let prepared = pool.prepare(&select).await?;
+        columns = prepared
+            .columns()
+            .iter()
+            .map(|column| sqlx::Column::name(column).to_string())
+            .collect();
+    }
+    let columns = columns
+        .iter()
+        .map(|column| escape_ident(column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let select = format!("select {columns} from {table}");
+    let (selected, timings) = Box::pin(check_statement(
+        pool,
+        prefetched,
+        &select,
+        &[],
+        &[],
+        passes,
+        false,
+    ))
+    .await?;
+    Ok((
+        QueryTypes {
+            input: Box::new([]),
+            output: Box::new([]),
+            warnings: selected.warnings,
+            notes: selected.notes,
+            rows_affected: false,
+            kind: StatementKind::Write,
+            lineage: Box::new([]),
+            copy_from: Some(CopyFrom {
+                schema: copy.schema,
+                table: copy.table,
+                columns: selected.output,
+            }),
+        },
+        timings,
+    ))
+}
+
+/// Name of the procedure parameter each placeholder passed to the procedure `call` is given
+/// for, by placeholder number.
+async fn procedure_param_names(
+    pool: &Pool<Postgres>,
+    call: &FunctionCall,
+    args: &[CallArgument],
+) -> Result<HashMap<usize, String>, Box<dyn Error>> {
+    if args.iter().all(|arg| arg.placeholder.is_none()) {
+        return Ok(HashMap::new());
+    }
+    let records = query!(
+        r#"
+        select p.oid as "oid!", coalesce(p.proargnames, '{}') as "names!"
+        from pg_proc p
+        where p.proname = $1
+            and case
+                when $2::text is null then pg_function_is_visible(p.oid)
+                else p.pronamespace = to_regnamespace($2)::oid
+            end
+            and p.prokind = 'p'
+            and $3::int4 between coalesce(cardinality(p.proallargtypes), p.pronargs)
+                - p.pronargdefaults and coalesce(cardinality(p.proallargtypes), p.pronargs)
+        "#,
+        call.name,
+        call.schema,
+        call.args as i32,
+    )
+    .fetch_all(pool)
+    .await?;
+    // Overloads of the procedure may name their parameters differently.
+    let names = match records.as_slice() {
+        [record] => record.names.as_slice(),
+        _ => &[],
+    };
+    let mut params = HashMap::new();
+    for (position, arg) in args.iter().enumerate() {
+        let name = arg
+            .name
+            .as_ref()
+            .or_else(|| names.get(position).filter(|name| !name.is_empty()));
+        if let Some((placeholder, name)) = arg.placeholder.zip(name) {
+            params.entry(placeholder).or_insert_with(|| name.clone());
+        }
+    }
+    Ok(params)
+}
+
+/// Columns of the record returned by `call`, the `OUT` parameters or `RETURNS TABLE` columns of
+/// the function. `None` when no function or several overloads match the call.
+pub async fn get_function_result(
+    pool: &Pool<Postgres>,
+    call: &FunctionCall,
+) -> Result<Option<SqlType>, Box<dyn Error>> {
+    let records = query!(
+        r#"
+        select p.oid as "oid!", a.name as "name?", format_type(a.type, null) as "data_type!"
+        from pg_proc p
+        cross join lateral unnest(p.proallargtypes, p.proargmodes, p.proargnames)
+            with ordinality as a(type, mode, name, position)
+        where p.proname = $1
+            and case
+                when $2::text is null then pg_function_is_visible(p.oid)
+                else p.pronamespace = to_regnamespace($2)::oid
+            end
+            and $3::int2 between p.pronargs - p.pronargdefaults and p.pronargs
+            and p.prorettype = 'record'::regtype
+            and a.mode in ('o', 'b', 't')
+        order by p.oid, a.position
+        "#,
+        call.name,
+        call.schema,
+        call.args as i16,
+    )
+    .fetch_all(pool)
+    .await?;
+    let Some(first) = records.first() else {
+        return Ok(None);
+    };
+    if records.iter().any(|record| record.oid != first.oid) {
+        return Ok(None);
+    }
+    // Preparing casts has Postgres resolve the types, including enums and arrays.
+    let casts = records
+        .iter()
+        .map(|record| format!("null::{}", record.data_type))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let query = format!("select {casts}");
+    let prepared = pool.prepare(&query).await?;
+    let mut fields = vec![];
+    for (index, (record, column)) in records.iter().zip(prepared.columns()).enumerate() {
+        // Like Postgres, unnamed parameters are named after their position.
+        let name = match record.name.as_deref() {
+            None | Some("") => format!("column{}", index + 1),
+            Some(name) => name.to_string(),
+        };
+        fields.push(QueryItem {
+            name,
+            sql_type: SqlType::from_pg_type_info(sqlx::Column::type_info(column))?,
+            nullable: Nullability::Unknown,
+        });
+    }
+    Ok(Some(SqlType::Record(fields.into_boxed_slice())))
 }
 
 pub async fn get_table_columns(
@@ -492,20 +1191,28 @@ pub async fn get_table_columns(
     table: &str,
 ) -> Result<Box<[String]>, Box<dyn Error>> {
     let records = query!(
-        "select
-    column_name
+        r#"select
+    att.attname::text as "column_name!"
 from
-    INFORMATION_SCHEMA.COLUMNS
+    pg_attribute att
+    join pg_class rel on rel.oid = att.attrelid
+    join pg_namespace nsp on nsp.oid = rel.relnamespace
 where
-    table_name = $1 and table_schema = $2;",
+    rel.relname = $1
+    and nsp.nspname = $2
+    and att.attnum > 0
+    and not att.attisdropped
+order by
+    att.attnum;"#,
         table,
         schema
     )
     .fetch_all(pool)
     .await?;
 
+    // Unlike INFORMATION_SCHEMA.COLUMNS, pg_attribute also lists columns of materialized views.
     Ok(records
         .into_iter()
-        .flat_map(|record| record.column_name)
+        .map(|record| record.column_name)
         .collect())
 }
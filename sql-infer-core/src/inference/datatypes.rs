@@ -18,7 +18,8 @@ impl UseInformationSchema for TextLength {
         let Some(schema) = schema else {
             return;
         };
-        if includes_cast(column) != Some(true) {
+        // A cast replaces the datatype of the source column.
+        if includes_cast(column) != Some(false) {
             return;
         }
         if let SqlType::Char { length } | SqlType::VarChar { length } = &mut item.sql_type
@@ -42,7 +43,8 @@ impl UseInformationSchema for DecimalPrecision {
         let Some(schema) = schema else {
             return;
         };
-        if includes_cast(column) != Some(true) {
+        // A cast replaces the datatype of the source column.
+        if includes_cast(column) != Some(false) {
             return;
         }
         if let SqlType::Decimal {
@@ -65,8 +67,52 @@ fn includes_cast(column: &Column) -> Option<bool> {
         Column::Either { left, right } => Option::zip(includes_cast(left), includes_cast(right))
             .map(|(left, right)| left || right)?,
         Column::Cast { .. } => true,
-        Column::BinaryOp { .. } => return None,
+        Column::BinaryOp { .. } | Column::Function { .. } | Column::Subscript { .. } => {
+            return None;
+        }
         Column::Unknown { .. } => return None,
         Column::Value { .. } => return None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use sqlparser::ast::DataType;
+
+    use crate::{
+        inference::{
+            InformationSchema, Nullability, QueryItem, SqlType, UseInformationSchema,
+            datatypes::TextLength,
+        },
+        parser::Column,
+    };
+
+    #[test]
+    fn lengths_apply_to_plain_columns_only() {
+        let name = Column::depends_on("users", "name");
+        let cast = name.clone().cast(DataType::Varchar(None));
+        let schema = InformationSchema {
+            is_nullable: Some(false),
+            character_maximum_length: Some(20),
+            numeric_precision: None,
+            numeric_precision_radix: None,
+            numeric_scale: None,
+            column_default: None,
+        };
+        // The pass is given the schema of the source column for casts as well.
+        let schemas = HashMap::from([(name.clone(), schema.clone()), (cast.clone(), schema)]);
+        let length = |column: &Column| {
+            let mut item = QueryItem {
+                name: "name".to_string(),
+                sql_type: SqlType::VarChar { length: None },
+                nullable: Nullability::Unknown,
+            };
+            TextLength.apply(&schemas, column, &mut item);
+            item.sql_type
+        };
+        assert_eq!(length(&name), SqlType::VarChar { length: Some(20) });
+        assert_eq!(length(&cast), SqlType::VarChar { length: None });
+    }
+}
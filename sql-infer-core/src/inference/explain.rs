@@ -0,0 +1,315 @@
+use std::{collections::HashMap, error::Error};
+
+use serde_json::Value;
+use sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+use sqlx::{Executor, Pool, Postgres, TypeInfo, postgres::PgTypeInfo, query_scalar, raw_sql};
+
+use crate::parser::{Column, ValueType};
+
+const STATEMENT: &str = "sql_infer_explain";
+
+/// Relation scanned under an alias in a plan.
+struct Relation {
+    name: String,
+    /// Rows of the relation may be missing, e.g. on the inner side of a `LEFT JOIN`.
+    nullable: bool,
+}
+
+/// Source of each output column in order according to the generic plan of `query`, whose
+/// parameters have the types `params`.
+pub async fn explain_fields(
+    pool: &Pool<Postgres>,
+    query: &str,
+    params: &[PgTypeInfo],
+) -> Result<Vec<Column>, Box<dyn Error>> {
+    let plan = explain(pool, query, params).await?;
+    let plan = plan
+        .get(0)
+        .and_then(|explained| explained.get("Plan"))
+        .ok_or("the plan is missing from the EXPLAIN output")?;
+    let mut relations = HashMap::new();
+    collect_relations(plan, false, &mut relations);
+    plan_outputs(plan, &relations).ok_or_else(|| "the plan does not list its output".into())
+}
+
+/// `EXPLAIN (VERBOSE, FORMAT JSON)` output of the generic plan of `query`, prepared on its own
+/// so the plan keeps its parameters.
+async fn explain(
+    pool: &Pool<Postgres>,
+    query: &str,
+    params: &[PgTypeInfo],
+) -> Result<Value, Box<dyn Error>> {
+    let mut conn = pool.acquire().await?;
+    let types = params
+        .iter()
+        .map(|param| param.name())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let types = match params.is_empty() {
+        true => String::new(),
+        false => format!("({types})"),
+    };
+    let args = match params.is_empty() {
+        true => String::new(),
+        false => format!("({})", vec!["null"; params.len()].join(", ")),
+    };
+    conn.execute(raw_sql(
+        "begin; set local plan_cache_mode = force_generic_plan",
+    ))
+    .await?;
+    let explained = async {
+        conn.execute(raw_sql(&format!("prepare {STATEMENT}{types} as {query}")))
+            .await?;
+        let result = query_scalar::<_, Value>(&format!(
+            "explain (verbose, format json) execute {STATEMENT}{args}"
+        ))
+        .persistent(false)
+        .fetch_one(&mut *conn)
+        .await;
+        conn.execute(raw_sql(&format!("deallocate {STATEMENT}")))
+            .await?;
+        Ok::<_, Box<dyn Error>>(result?)
+    }
+    .await;
+    conn.execute(raw_sql("rollback")).await?;
+    if explained.is_err() {
+        // The statement outlives the transaction, and is left behind if `EXPLAIN` failed.
+        let _ = conn
+            .execute(raw_sql(&format!("deallocate {STATEMENT}")))
+            .await;
+    }
+    explained
+}
+
+/// Record every relation scanned in `plan` by its alias, an alias scanning different
+/// relations in different parts of the plan is left out.
+fn collect_relations(
+    plan: &Value,
+    nullable: bool,
+    relations: &mut HashMap<String, Option<Relation>>,
+) {
+    if let (Some(alias), Some(name)) = (
+        plan.get("Alias").and_then(Value::as_str),
+        plan.get("Relation Name").and_then(Value::as_str),
+    ) {
+        let relation = Relation {
+            name: name.to_string(),
+            nullable,
+        };
+        relations
+            .entry(alias.to_string())
+            .and_modify(|existing| *existing = None)
+            .or_insert(Some(relation));
+    }
+    let join_type = plan.get("Join Type").and_then(Value::as_str);
+    let children = plan.get("Plans").and_then(Value::as_array);
+    for (index, child) in children.into_iter().flatten().enumerate() {
+        // The first child is the outer side of a join, the second the inner one.
+        let nullable_side = match join_type {
+            Some("Left") => index == 1,
+            Some("Right") => index == 0,
+            Some("Full") => true,
+            _ => false,
+        };
+        collect_relations(child, nullable || nullable_side, relations);
+    }
+}
+
+fn node_type(plan: &Value) -> &str {
+    plan.get("Node Type")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+/// Source of each output of `plan`. The outputs of a set operation are those of its first
+/// branch, so every branch is looked at instead.
+fn plan_outputs(
+    plan: &Value,
+    relations: &HashMap<String, Option<Relation>>,
+) -> Option<Vec<Column>> {
+    let children = plan
+        .get("Plans")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let set_operation = |plan: &Value| matches!(node_type(plan), "Append" | "Merge Append");
+    if set_operation(plan) {
+        let mut branches = children.iter().map(|child| plan_outputs(child, relations));
+        let first = branches.next()??;
+        return branches.try_fold(first, |outputs, branch| {
+            let branch = branch?;
+            (branch.len() == outputs.len()).then(|| {
+                outputs
+                    .into_iter()
+                    .zip(branch)
+                    .map(|(left, right)| Column::either(left, right))
+                    .collect()
+            })
+        });
+    }
+    // Nodes keeping the rows of a set operation, e.g. deduplicating them for `UNION`.
+    if let [child] = children
+        && matches!(
+            node_type(plan),
+            "Unique" | "Sort" | "Incremental Sort" | "Limit" | "Aggregate" | "SetOp"
+        )
+        && (set_operation(child) || !matches!(node_type(plan), "Aggregate"))
+        && contains_set_operation(child)
+    {
+        return plan_outputs(child, relations);
+    }
+    let outputs = plan.get("Output").and_then(Value::as_array)?;
+    outputs
+        .iter()
+        .map(|output| Some(to_column(output.as_str()?, relations)))
+        .collect()
+}
+
+/// Whether `plan` is a set operation, possibly under nodes keeping its rows.
+fn contains_set_operation(plan: &Value) -> bool {
+    match node_type(plan) {
+        "Append" | "Merge Append" => true,
+        "Unique" | "Sort" | "Incremental Sort" | "Limit" | "SetOp" => plan
+            .get("Plans")
+            .and_then(Value::as_array)
+            .is_some_and(|children| children.len() == 1 && contains_set_operation(&children[0])),
+        _ => false,
+    }
+}
+
+/// Source of the output expression `output` of a plan, e.g. `u.name` or `(u.id)::text`.
+fn to_column(output: &str, relations: &HashMap<String, Option<Relation>>) -> Column {
+    let unknown = || Column::Unknown {
+        sql: output.to_string(),
+    };
+    if let Some((source, data_type)) = output
+        .strip_prefix('(')
+        .and_then(|output| output.rsplit_once(")::"))
+    {
+        let data_type = Parser::new(&PostgreSqlDialect {})
+            .try_with_sql(data_type)
+            .and_then(|mut parser| parser.parse_data_type());
+        return match (to_column(source, relations), data_type) {
+            (Column::Unknown { .. }, _) | (_, Err(_)) => unknown(),
+            (source, Ok(data_type)) => source.cast(data_type),
+        };
+    }
+    if let Some(value) = to_value(output) {
+        return Column::value(value);
+    }
+    let Some((alias, column)) = split_reference(output) else {
+        return unknown();
+    };
+    match relations.get(&alias) {
+        Some(Some(relation)) => {
+            let column = Column::depends_on(&relation.name, column);
+            match relation.nullable {
+                true => column.maybe(),
+                false => column,
+            }
+        }
+        _ => unknown(),
+    }
+}
+
+/// Constant output, e.g. `1`, `NULL::text` or `'x'::character varying`.
+fn to_value(output: &str) -> Option<ValueType> {
+    let literal = match output.rsplit_once("::") {
+        Some((literal, _)) if !literal.ends_with(')') => literal,
+        _ => output,
+    };
+    match literal {
+        "NULL" => Some(ValueType::Null),
+        "true" | "false" => Some(ValueType::Boolean),
+        _ if literal.starts_with('\'') && literal.ends_with('\'') && literal.len() > 1 => {
+            Some(ValueType::String)
+        }
+        _ if literal.parse::<i64>().is_ok() => Some(ValueType::Int),
+        _ if literal.parse::<f64>().is_ok() => Some(ValueType::Float),
+        _ => None,
+    }
+}
+
+/// Alias and column of a reference like `u.id` or `"User"."first name"`.
+fn split_reference(output: &str) -> Option<(String, String)> {
+    let (alias, rest) = identifier(output)?;
+    let (column, rest) = identifier(rest.strip_prefix('.')?)?;
+    rest.is_empty().then_some((alias, column))
+}
+
+/// Identifier at the start of `sql`, unquoted, along with the rest of `sql`.
+fn identifier(sql: &str) -> Option<(String, &str)> {
+    if let Some(quoted) = sql.strip_prefix('"') {
+        let mut identifier = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((index, char)) = chars.next() {
+            if char != '"' {
+                identifier.push(char);
+                continue;
+            }
+            match quoted[index + 1..].starts_with('"') {
+                true => {
+                    identifier.push('"');
+                    chars.next();
+                }
+                false => return Some((identifier, &quoted[index + 1..])),
+            }
+        }
+        return None;
+    }
+    let end = sql
+        .find(|char: char| !(char.is_alphanumeric() || char == '_' || char == '$'))
+        .unwrap_or(sql.len());
+    (end > 0).then(|| (sql[..end].to_string(), &sql[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::parser::{Column, ValueType};
+
+    use super::{Relation, to_column};
+
+    #[test]
+    fn outputs_are_resolved() {
+        let relations = HashMap::from([
+            (
+                "u".to_string(),
+                Some(Relation {
+                    name: "users".to_string(),
+                    nullable: false,
+                }),
+            ),
+            (
+                "Order".to_string(),
+                Some(Relation {
+                    name: "orders".to_string(),
+                    nullable: true,
+                }),
+            ),
+            ("x".to_string(), None),
+        ]);
+        assert_eq!(
+            to_column("u.id", &relations),
+            Column::depends_on("users", "id")
+        );
+        assert_eq!(
+            to_column("\"Order\".\"total \"\"net\"\"\"", &relations),
+            Column::depends_on("orders", "total \"net\"").maybe()
+        );
+        assert_eq!(
+            to_column("(u.name)::text", &relations).to_string(),
+            "cast(users.name, TEXT)"
+        );
+        assert_eq!(to_column("x.id", &relations).to_string(), "unknown(x.id)");
+        assert_eq!(
+            to_column("NULL::text", &relations),
+            Column::value(ValueType::Null)
+        );
+        assert_eq!(
+            to_column("upper((u.name)::text)", &relations).to_string(),
+            "unknown(upper((u.name)::text))"
+        );
+    }
+}
@@ -16,6 +16,63 @@ impl UseInformationSchema for ColumnNullability {
     ) {
         column.nullable = column_is_nullable(source, schemas);
     }
+
+    fn explain(
+        &self,
+        schemas: &HashMap<Column, InformationSchema>,
+        source: &Column,
+        column: &super::QueryItem,
+    ) -> Option<String> {
+        if column.nullable != Nullability::Unknown {
+            return None;
+        }
+        let reason = unknown_reason(source, schemas)?;
+        Some(format!(
+            "nullability of {} is unknown as {reason}, inferred from {source}",
+            column.name
+        ))
+    }
+}
+
+/// Why the nullability of `col` is unknown, following the same path as `column_is_nullable`.
+fn unknown_reason(col: &Column, schemas: &HashMap<Column, InformationSchema>) -> Option<String> {
+    match col {
+        Column::DependsOn { table, column } => match schemas.get(col) {
+            None => Some(format!("{table}.{column} is not a column of a known table")),
+            Some(schema) if schema.is_nullable.is_none() => Some(format!(
+                "the information schema does not tell whether {table}.{column} is nullable"
+            )),
+            Some(_) => None,
+        },
+        Column::Maybe { .. } | Column::Value(_) => None,
+        Column::Either { left, right } | Column::BinaryOp { left, right, .. } => {
+            if let Column::BinaryOp { op, .. } = col
+                && (op.not_null() == Some(true) || op.maybe_null())
+            {
+                return None;
+            }
+            match column_is_nullable(left, schemas) {
+                Nullability::True => None,
+                Nullability::False => unknown_reason(right, schemas),
+                Nullability::Unknown => unknown_reason(left, schemas),
+            }
+        }
+        Column::Unknown { sql } => Some(format!("`{sql}` is not supported by inference")),
+        Column::Function { function, args } => {
+            let strict = &args[..function.strict_arguments().min(args.len())];
+            if strict
+                .iter()
+                .any(|arg| column_is_nullable(arg, schemas) == Nullability::True)
+            {
+                return None;
+            }
+            strict.iter().find_map(|arg| unknown_reason(arg, schemas))
+        }
+        Column::Subscript { slice: false, .. } => None,
+        Column::Cast { source, .. } | Column::Subscript { source, .. } => {
+            unknown_reason(source, schemas)
+        }
+    }
 }
 
 fn column_is_nullable(col: &Column, schemas: &HashMap<Column, InformationSchema>) -> Nullability {
@@ -37,10 +94,27 @@ fn column_is_nullable(col: &Column, schemas: &HashMap<Column, InformationSchema>
         },
         Column::Unknown { .. } => Nullability::Unknown,
         Column::Cast { source, .. } => column_is_nullable(source, schemas),
+        Column::Function { function, args } => args
+            .iter()
+            .take(function.strict_arguments())
+            .map(|arg| column_is_nullable(arg, schemas))
+            .fold(Nullability::False, |nullability, arg| {
+                match (nullability, arg) {
+                    (Nullability::True, _) | (_, Nullability::True) => Nullability::True,
+                    (Nullability::Unknown, _) | (_, Nullability::Unknown) => Nullability::Unknown,
+                    (Nullability::False, Nullability::False) => Nullability::False,
+                }
+            }),
+        // Out of range subscripts give NULL, out of range slices an empty array.
+        Column::Subscript { slice: false, .. } => Nullability::True,
+        Column::Subscript { source, .. } => column_is_nullable(source, schemas),
         Column::BinaryOp { op, left, right } => {
             if op.not_null() == Some(true) {
                 return Nullability::False;
             }
+            if op.maybe_null() {
+                return Nullability::True;
+            }
             match column_is_nullable(left, schemas) {
                 Nullability::True => Nullability::True,
                 Nullability::False => column_is_nullable(right, schemas),
@@ -53,3 +127,121 @@ fn column_is_nullable(col: &Column, schemas: &HashMap<Column, InformationSchema>
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        inference::{InformationSchema, Nullability},
+        parser::{Column, ValueType, find_fields, to_ast},
+    };
+
+    use super::{column_is_nullable, unknown_reason};
+
+    #[test]
+    fn unknown_nullability_is_explained() {
+        let schemas = HashMap::new();
+        let missing = Column::depends_on("users", "id");
+        assert_eq!(
+            unknown_reason(&missing, &schemas).as_deref(),
+            Some("users.id is not a column of a known table")
+        );
+        let expression = Column::Unknown {
+            sql: "f(x)".to_string(),
+        };
+        assert_eq!(
+            unknown_reason(
+                &Column::either(Column::Value(ValueType::Null), missing),
+                &schemas
+            ),
+            None
+        );
+        assert_eq!(
+            unknown_reason(
+                &Column::either(Column::Value(ValueType::Int), expression),
+                &schemas
+            )
+            .as_deref(),
+            Some("`f(x)` is not supported by inference")
+        );
+    }
+
+    #[test]
+    fn json_access_may_be_null() {
+        let schema = InformationSchema {
+            is_nullable: Some(false),
+            character_maximum_length: None,
+            numeric_precision: None,
+            numeric_precision_radix: None,
+            numeric_scale: None,
+            column_default: None,
+        };
+        let schemas = HashMap::from([(Column::depends_on("events", "data"), schema)]);
+        let ast = to_ast(
+            "select data, data -> 'a' as a, data ->> 'b' as b, data #>> '{c,d}' as c from events",
+        )
+        .unwrap();
+        let fields = find_fields(&ast[0]).unwrap();
+        assert_eq!(
+            column_is_nullable(&fields["data"], &schemas),
+            Nullability::False
+        );
+        for name in ["a", "b", "c"] {
+            assert_eq!(
+                column_is_nullable(&fields[name], &schemas),
+                Nullability::True
+            );
+            assert_eq!(unknown_reason(&fields[name], &schemas), None);
+        }
+    }
+
+    #[test]
+    fn concat_is_never_null() {
+        let schemas = HashMap::new();
+        let ast = to_ast(
+            "select concat(a, null) as a, concat_ws(', ', a, b) as b, concat_ws(a, b) as c, \
+             format(null, a) as d from t",
+        )
+        .unwrap();
+        let fields = find_fields(&ast[0]).unwrap();
+        assert_eq!(
+            column_is_nullable(&fields["a"], &schemas),
+            Nullability::False
+        );
+        assert_eq!(
+            column_is_nullable(&fields["b"], &schemas),
+            Nullability::False
+        );
+        assert_eq!(
+            column_is_nullable(&fields["c"], &schemas),
+            Nullability::Unknown
+        );
+        assert_eq!(
+            unknown_reason(&fields["c"], &schemas).as_deref(),
+            Some("t.a is not a column of a known table")
+        );
+        assert_eq!(
+            column_is_nullable(&fields["d"], &schemas),
+            Nullability::True
+        );
+    }
+
+    #[test]
+    fn null_tests_are_never_null() {
+        let schemas = HashMap::new();
+        let ast = to_ast(
+            "select a is null as x, a is not null as y, a is distinct from b as z, \
+             a is not distinct from null as w from t",
+        )
+        .unwrap();
+        let fields = find_fields(&ast[0]).unwrap();
+        for name in ["x", "y", "z", "w"] {
+            assert_eq!(fields[name], Column::Value(ValueType::Boolean));
+            assert_eq!(
+                column_is_nullable(&fields[name], &schemas),
+                Nullability::False
+            );
+        }
+    }
+}
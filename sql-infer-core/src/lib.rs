@@ -1,6 +1,8 @@
-use std::error::Error;
+use std::{error::Error, sync::Arc};
 
-use crate::inference::{Passes, QueryTypes, UseInformationSchema};
+use crate::inference::{
+    InferenceTimings, Passes, PrefetchedColumns, QueryTypes, UseInformationSchema,
+};
 
 pub mod inference;
 pub mod parser;
@@ -8,6 +10,8 @@ pub mod parser;
 #[must_use]
 pub struct SqlInferBuilder {
     passes: Passes,
+    prefetched: Option<Arc<PrefetchedColumns>>,
+    rename_duplicate_columns: bool,
 }
 
 impl Default for SqlInferBuilder {
@@ -15,7 +19,10 @@ impl Default for SqlInferBuilder {
         Self {
             passes: Passes {
                 information_schema: vec![],
+                explain_origins: false,
             },
+            prefetched: None,
+            rename_duplicate_columns: false,
         }
     }
 }
@@ -29,15 +36,38 @@ impl SqlInferBuilder {
         self
     }
 
+    /// Serve column metadata from `columns` instead of querying it for every column.
+    pub fn prefetched_columns(&mut self, columns: Arc<PrefetchedColumns>) -> &mut Self {
+        self.prefetched = Some(columns);
+        self
+    }
+
+    /// Resolve the source of output columns from the `EXPLAIN` plan of queries, which follows
+    /// CTEs and set operations the parser does not, falling back to the parser otherwise.
+    pub fn explain_column_origins(&mut self, explain: bool) -> &mut Self {
+        self.passes.explain_origins = explain;
+        self
+    }
+
+    /// Suffix output columns sharing a name with `_2`, `_3` and so on instead of failing.
+    pub fn rename_duplicate_columns(&mut self, rename: bool) -> &mut Self {
+        self.rename_duplicate_columns = rename;
+        self
+    }
+
     pub fn build(self) -> SqlInfer {
         SqlInfer {
             passes: self.passes,
+            prefetched: self.prefetched,
+            rename_duplicate_columns: self.rename_duplicate_columns,
         }
     }
 }
 
 pub struct SqlInfer {
     passes: Passes,
+    prefetched: Option<Arc<PrefetchedColumns>>,
+    rename_duplicate_columns: bool,
 }
 
 impl SqlInfer {
@@ -46,7 +76,43 @@ impl SqlInfer {
         pool: &sqlx::Pool<sqlx::Postgres>,
         query: &str,
     ) -> Result<QueryTypes, Box<dyn Error>> {
-        inference::check_statement(pool, query, &self.passes).await
+        self.infer_named_types(pool, query, &[]).await
+    }
+
+    /// Like [`SqlInfer::infer_types`], with the input for `$n` named `param_names[n - 1]`.
+    /// Parameters past the end of `param_names` are named after their placeholder, e.g. `$1`.
+    pub async fn infer_named_types(
+        &self,
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        query: &str,
+        param_names: &[String],
+    ) -> Result<QueryTypes, Box<dyn Error>> {
+        let (query_types, _) = self
+            .infer_types_with_timings(pool, query, &[], param_names)
+            .await?;
+        Ok(query_types)
+    }
+
+    /// Like [`SqlInfer::infer_named_types`], along with the time spent on each step. Parameters
+    /// with a type in `param_types` are prepared with that type instead of the one Postgres
+    /// infers.
+    pub async fn infer_types_with_timings(
+        &self,
+        pool: &sqlx::Pool<sqlx::Postgres>,
+        query: &str,
+        param_types: &[Option<String>],
+        param_names: &[String],
+    ) -> Result<(QueryTypes, InferenceTimings), Box<dyn Error>> {
+        inference::check_statement(
+            pool,
+            self.prefetched.as_deref(),
+            query,
+            param_types,
+            param_names,
+            &self.passes,
+            self.rename_duplicate_columns,
+        )
+        .await
     }
 
     pub async fn infer_table_types(
@@ -57,12 +123,13 @@ impl SqlInfer {
     ) -> Result<QueryTypes, Box<dyn Error>> {
         let columns = inference::get_table_columns(pool, schema, table).await?;
         let query = format!(
-            "select {} from {}",
+            "select {} from {}.{}",
             columns
                 .into_iter()
                 .map(|col| escape_ident(&col))
                 .collect::<Vec<_>>()
                 .join(","),
+            escape_ident(schema),
             escape_ident(table),
         );
         self.infer_types(pool, &query).await
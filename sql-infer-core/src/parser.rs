@@ -1,15 +1,18 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::error::Error;
 use std::fmt::Display;
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
 use sqlparser::ast::{
-    BinaryOperator, DataType, DollarQuotedString, Expr, FromTable, Function, JoinOperator,
-    QuoteDelimitedString, SelectItem, SetExpr, Statement, TableFactor, TableObject, TableWithJoins,
-    Update, ValueWithSpan,
+    AccessExpr, BinaryOperator, CopySource, CopyTarget, DataType, DollarQuotedString, Expr,
+    FromTable, Function, FunctionArg, FunctionArgExpr, FunctionArguments, Ident, JoinConstraint,
+    JoinOperator, ObjectName, QuoteDelimitedString, SelectItem, SetExpr, Statement, Subscript,
+    TableFactor, TableObject, TableWithJoins, Update, ValueWithSpan,
 };
 use sqlparser::dialect::PostgreSqlDialect;
 use sqlparser::parser::Parser;
+use sqlparser::tokenizer::{Token, Tokenizer};
 
 use crate::inference::SqlType;
 
@@ -93,6 +96,10 @@ pub enum BinaryOpData {
     Numeric {
         inner: BinaryOperator,
     },
+    /// `->`, `->>`, `#>` and `#>>`, giving NULL for a missing key or path.
+    Json {
+        inner: BinaryOperator,
+    },
     Concat,
 }
 
@@ -112,19 +119,42 @@ impl BinaryOpData {
         Self::Numeric { inner: op }
     }
 
+    fn json(op: BinaryOperator) -> Self {
+        Self::Json { inner: op }
+    }
+
     fn concat() -> Self {
         Self::Concat
     }
 
+    /// The SQL operator, `None` for string concatenation.
+    pub fn operator(&self) -> Option<&BinaryOperator> {
+        match self {
+            BinaryOpData::Unknown { inner }
+            | BinaryOpData::ConstantType { inner, .. }
+            | BinaryOpData::Numeric { inner }
+            | BinaryOpData::Json { inner } => Some(inner),
+            BinaryOpData::Concat => None,
+        }
+    }
+
     /// Returns boolean indicating whether the output is guaranteed to be not null regardless of arguments.
     pub fn not_null(&self) -> Option<bool> {
         Some(false)
     }
 
+    /// Returns boolean indicating whether the output may be null even when no argument is.
+    pub fn maybe_null(&self) -> bool {
+        matches!(self, BinaryOpData::Json { .. })
+    }
+
     /// Returns type if the output of this operation is a single type regardless of the arguments
     pub fn try_constant(&self) -> Option<SqlType> {
         match self {
             BinaryOpData::ConstantType { sql_type, .. } => Some(sql_type.clone()),
+            BinaryOpData::Json {
+                inner: BinaryOperator::LongArrow | BinaryOperator::HashLongArrow,
+            } => Some(SqlType::Text),
             _ => None,
         }
     }
@@ -134,7 +164,10 @@ impl BinaryOpData {
         match self {
             BinaryOpData::Unknown { .. } => None,
             BinaryOpData::ConstantType { sql_type, .. } => Some(sql_type.clone()),
-            BinaryOpData::Numeric { .. } => {
+            BinaryOpData::Numeric { inner } => {
+                if let Some(sql_type) = temporal_arithmetic(inner, &left, &right) {
+                    return Some(sql_type);
+                }
                 if !(left.is_numeric() || right.is_numeric()) {
                     return None;
                 }
@@ -143,6 +176,11 @@ impl BinaryOpData {
                     _ => Some(right),
                 }
             }
+            BinaryOpData::Json { .. } => match self.try_constant() {
+                Some(sql_type) => Some(sql_type),
+                // `->` and `#>` keep the type of the document.
+                None => matches!(left, SqlType::Json | SqlType::Jsonb).then_some(left),
+            },
             BinaryOpData::Concat => {
                 if left.is_text() || right.is_text() {
                     return Some(SqlType::Text);
@@ -153,6 +191,47 @@ impl BinaryOpData {
     }
 }
 
+/// Result of `+`, `-`, `*` and `/` with a date, time or interval operand.
+fn temporal_arithmetic(op: &BinaryOperator, left: &SqlType, right: &SqlType) -> Option<SqlType> {
+    use SqlType::{Date, Int2, Int4, Interval, Time, Timestamp};
+    // https://www.postgresql.org/docs/current/functions-datetime.html
+    Some(match (op, left, right) {
+        (BinaryOperator::Plus, Date, Int2 | Int4) | (BinaryOperator::Plus, Int2 | Int4, Date) => {
+            Date
+        }
+        (BinaryOperator::Minus, Date, Int2 | Int4) => Date,
+        (BinaryOperator::Minus, Date, Date) => Int4,
+        (BinaryOperator::Plus, Date, Interval) | (BinaryOperator::Plus, Interval, Date) => {
+            Timestamp { tz: false }
+        }
+        (BinaryOperator::Minus, Date, Interval) => Timestamp { tz: false },
+        (BinaryOperator::Plus, Date, Time { tz }) | (BinaryOperator::Plus, Time { tz }, Date) => {
+            Timestamp { tz: *tz }
+        }
+        (BinaryOperator::Plus, Timestamp { tz }, Interval)
+        | (BinaryOperator::Plus, Interval, Timestamp { tz })
+        | (BinaryOperator::Minus, Timestamp { tz }, Interval) => Timestamp { tz: *tz },
+        (BinaryOperator::Minus, Timestamp { tz: left }, Timestamp { tz: right })
+            if left == right =>
+        {
+            Interval
+        }
+        (BinaryOperator::Plus, Time { tz }, Interval)
+        | (BinaryOperator::Plus, Interval, Time { tz })
+        | (BinaryOperator::Minus, Time { tz }, Interval) => Time { tz: *tz },
+        (BinaryOperator::Minus, Time { tz: false }, Time { tz: false }) => Interval,
+        (BinaryOperator::Plus | BinaryOperator::Minus, Interval, Interval) => Interval,
+        (BinaryOperator::Multiply, Interval, right)
+        | (BinaryOperator::Multiply, right, Interval)
+            if right.is_numeric() =>
+        {
+            Interval
+        }
+        (BinaryOperator::Divide, Interval, right) if right.is_numeric() => Interval,
+        _ => return None,
+    })
+}
+
 impl From<BinaryOperator> for BinaryOpData {
     fn from(value: BinaryOperator) -> Self {
         // https://www.postgresql.org/docs/current/functions-math.html
@@ -163,6 +242,11 @@ impl From<BinaryOperator> for BinaryOpData {
             | BinaryOperator::Divide
             | BinaryOperator::Modulo => BinaryOpData::numeric(value),
             BinaryOperator::StringConcat => BinaryOpData::concat(),
+            // https://www.postgresql.org/docs/current/functions-json.html
+            BinaryOperator::Arrow
+            | BinaryOperator::LongArrow
+            | BinaryOperator::HashArrow
+            | BinaryOperator::HashLongArrow => BinaryOpData::json(value),
             BinaryOperator::Gt
             | BinaryOperator::Lt
             | BinaryOperator::GtEq
@@ -171,7 +255,16 @@ impl From<BinaryOperator> for BinaryOpData {
             | BinaryOperator::NotEq
             | BinaryOperator::And
             | BinaryOperator::Or
-            | BinaryOperator::Xor => BinaryOpData::constant(value, SqlType::Bool),
+            | BinaryOperator::Xor
+            // https://www.postgresql.org/docs/current/functions-matching.html
+            | BinaryOperator::PGLikeMatch
+            | BinaryOperator::PGILikeMatch
+            | BinaryOperator::PGNotLikeMatch
+            | BinaryOperator::PGNotILikeMatch
+            | BinaryOperator::PGRegexMatch
+            | BinaryOperator::PGRegexIMatch
+            | BinaryOperator::PGRegexNotMatch
+            | BinaryOperator::PGRegexNotIMatch => BinaryOpData::constant(value, SqlType::Bool),
             _ => BinaryOpData::unknown(value),
         }
     }
@@ -180,7 +273,9 @@ impl From<BinaryOperator> for BinaryOpData {
 impl Display for BinaryOpData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            BinaryOpData::Unknown { inner } | BinaryOpData::Numeric { inner } => {
+            BinaryOpData::Unknown { inner }
+            | BinaryOpData::Numeric { inner }
+            | BinaryOpData::Json { inner } => {
                 write!(f, "{inner}")
             }
             BinaryOpData::ConstantType { inner, sql_type } => {
@@ -191,6 +286,64 @@ impl Display for BinaryOpData {
     }
 }
 
+/// Functions whose result inference knows, beyond the type Postgres reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KnownFunction {
+    Concat,
+    ConcatWs,
+    Format,
+    DatePart,
+    Extract,
+}
+
+impl KnownFunction {
+    fn from_name(name: &str) -> Option<Self> {
+        // https://www.postgresql.org/docs/current/functions-string.html
+        Some(match name {
+            "concat" => Self::Concat,
+            "concat_ws" => Self::ConcatWs,
+            "format" => Self::Format,
+            // https://www.postgresql.org/docs/current/functions-datetime.html
+            "date_part" => Self::DatePart,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KnownFunction::Concat => "concat",
+            KnownFunction::ConcatWs => "concat_ws",
+            KnownFunction::Format => "format",
+            KnownFunction::DatePart => "date_part",
+            KnownFunction::Extract => "extract",
+        }
+    }
+
+    /// Type returned regardless of the arguments.
+    pub fn sql_type(&self) -> SqlType {
+        match self {
+            KnownFunction::Concat | KnownFunction::ConcatWs | KnownFunction::Format => {
+                SqlType::Text
+            }
+            KnownFunction::DatePart => SqlType::Float8,
+            KnownFunction::Extract => SqlType::Decimal {
+                precision: None,
+                precision_radix: None,
+            },
+        }
+    }
+
+    /// Number of leading arguments whose NULL makes the output NULL, the others being ignored
+    /// or formatted, e.g. the separator of `concat_ws` or the format string of `format`.
+    pub fn strict_arguments(&self) -> usize {
+        match self {
+            KnownFunction::Concat => 0,
+            KnownFunction::ConcatWs | KnownFunction::Format | KnownFunction::Extract => 1,
+            KnownFunction::DatePart => 2,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ValueType {
     Boolean,
@@ -238,6 +391,15 @@ pub enum Column {
         left: Arc<Column>,
         right: Arc<Column>,
     },
+    Function {
+        function: KnownFunction,
+        args: Vec<Column>,
+    },
+    /// An element of an array, `arr[1]`, or with `slice`, a part of it, `arr[1:3]`.
+    Subscript {
+        source: Arc<Column>,
+        slice: bool,
+    },
     Value(ValueType),
 }
 
@@ -250,11 +412,144 @@ impl Display for Column {
             Column::Unknown { sql } => write!(f, "unknown({sql})"),
             Column::Cast { source, data_type } => write!(f, "cast({source}, {data_type})"),
             Column::BinaryOp { op, left, right } => write!(f, "binop({op}, {left}, {right})"),
+            Column::Function { function, args } => {
+                let args = args.iter().map(Column::to_string).collect::<Vec<_>>();
+                write!(f, "{}({})", function.name(), args.join(", "))
+            }
+            Column::Subscript {
+                source,
+                slice: false,
+            } => write!(f, "subscript({source})"),
+            Column::Subscript {
+                source,
+                slice: true,
+            } => write!(f, "slice({source})"),
             Column::Value(value) => write!(f, "{value}"),
         }
     }
 }
 
+/// Serializable form of a [`Column`], what an output column is computed from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "kind")]
+pub enum Lineage {
+    Column {
+        table: String,
+        column: String,
+    },
+    /// The source may be missing, e.g. on the nullable side of an outer join.
+    Maybe {
+        source: Box<Lineage>,
+    },
+    Either {
+        left: Box<Lineage>,
+        right: Box<Lineage>,
+    },
+    Cast {
+        source: Box<Lineage>,
+        data_type: String,
+    },
+    BinaryOp {
+        op: String,
+        left: Box<Lineage>,
+        right: Box<Lineage>,
+    },
+    Function {
+        name: String,
+        args: Vec<Lineage>,
+    },
+    Subscript {
+        source: Box<Lineage>,
+        slice: bool,
+    },
+    Value {
+        value: String,
+    },
+    /// An expression inference does not follow.
+    Unknown {
+        sql: String,
+    },
+}
+
+impl From<&Column> for Lineage {
+    fn from(column: &Column) -> Self {
+        let boxed = |column: &Column| Box::new(Lineage::from(column));
+        match column {
+            Column::DependsOn { table, column } => Lineage::Column {
+                table: table.clone(),
+                column: column.clone(),
+            },
+            Column::Maybe { column } => Lineage::Maybe {
+                source: boxed(column),
+            },
+            Column::Either { left, right } => Lineage::Either {
+                left: boxed(left),
+                right: boxed(right),
+            },
+            Column::Unknown { sql } => Lineage::Unknown { sql: sql.clone() },
+            Column::Cast { source, data_type } => Lineage::Cast {
+                source: boxed(source),
+                data_type: data_type.to_string(),
+            },
+            Column::BinaryOp { op, left, right } => Lineage::BinaryOp {
+                op: op.to_string(),
+                left: boxed(left),
+                right: boxed(right),
+            },
+            Column::Function { function, args } => Lineage::Function {
+                name: function.name().to_string(),
+                args: args.iter().map(Lineage::from).collect(),
+            },
+            Column::Subscript { source, slice } => Lineage::Subscript {
+                source: boxed(source),
+                slice: *slice,
+            },
+            Column::Value(value) => Lineage::Value {
+                value: value.to_string(),
+            },
+        }
+    }
+}
+
+impl Lineage {
+    /// Table columns the output is computed from, as `(table, column)`.
+    pub fn columns(&self) -> BTreeSet<(&str, &str)> {
+        let mut columns = BTreeSet::new();
+        self.visit(&mut |lineage| {
+            if let Lineage::Column { table, column } = lineage {
+                columns.insert((table.as_str(), column.as_str()));
+            }
+        });
+        columns
+    }
+
+    /// Whether part of the output comes from an expression inference does not follow.
+    pub fn is_partial(&self) -> bool {
+        let mut partial = false;
+        self.visit(&mut |lineage| partial |= matches!(lineage, Lineage::Unknown { .. }));
+        partial
+    }
+
+    fn visit<'a>(&'a self, visitor: &mut impl FnMut(&'a Lineage)) {
+        visitor(self);
+        match self {
+            Lineage::Maybe { source }
+            | Lineage::Cast { source, .. }
+            | Lineage::Subscript { source, .. } => source.visit(visitor),
+            Lineage::Either { left, right } | Lineage::BinaryOp { left, right, .. } => {
+                left.visit(visitor);
+                right.visit(visitor);
+            }
+            Lineage::Function { args, .. } => {
+                for arg in args {
+                    arg.visit(visitor);
+                }
+            }
+            Lineage::Column { .. } | Lineage::Value { .. } | Lineage::Unknown { .. } => {}
+        }
+    }
+}
+
 impl Column {
     pub fn depends_on(table: impl Into<String>, column: impl Into<String>) -> Column {
         Self::DependsOn {
@@ -291,6 +586,13 @@ impl Column {
         }
     }
 
+    pub fn subscript(self, slice: bool) -> Self {
+        Column::Subscript {
+            source: self.into(),
+            slice,
+        }
+    }
+
     pub fn value(value: ValueType) -> Self {
         Self::Value(value)
     }
@@ -471,6 +773,17 @@ fn find_field_in_expr(expr: &Expr, tables: &[Arc<Table>]) -> Option<Column> {
             result
         }
         Expr::Nested(expr) => find_field_in_expr(expr, tables),
+        // Postgres treats every subscript as a slice when any of them is one.
+        Expr::CompoundFieldAccess { root, access_chain }
+            if access_chain
+                .iter()
+                .all(|access| matches!(access, AccessExpr::Subscript(_))) =>
+        {
+            let slice = access_chain
+                .iter()
+                .any(|access| matches!(access, AccessExpr::Subscript(Subscript::Slice { .. })));
+            Some(find_field_in_expr(root, tables)?.subscript(slice))
+        }
         Expr::BinaryOp { left, op, right } => Some(Column::bin_op(
             op.clone(),
             find_field_in_expr(left, tables)?,
@@ -515,6 +828,98 @@ fn find_field_in_expr(expr: &Expr, tables: &[Arc<Table>]) -> Option<Column> {
         Expr::Function(Function { name, .. }) if name.to_string().to_lowercase() == "count" => {
             Some(Column::Value(ValueType::Int))
         }
+        Expr::Function(function) if let Some(column) = known_function(function, tables) => {
+            Some(column)
+        }
+        // Postgres expands these into comparisons, so they are typed and nullable like them.
+        Expr::Between {
+            expr,
+            negated,
+            low,
+            high,
+        } => {
+            let expr = find_field_in_expr(expr, tables)?;
+            let low = find_field_in_expr(low, tables)?;
+            let high = find_field_in_expr(high, tables)?;
+            Some(match negated {
+                false => Column::bin_op(
+                    BinaryOperator::And,
+                    Column::bin_op(BinaryOperator::GtEq, expr.clone(), low),
+                    Column::bin_op(BinaryOperator::LtEq, expr, high),
+                ),
+                true => Column::bin_op(
+                    BinaryOperator::Or,
+                    Column::bin_op(BinaryOperator::Lt, expr.clone(), low),
+                    Column::bin_op(BinaryOperator::Gt, expr, high),
+                ),
+            })
+        }
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            let expr = find_field_in_expr(expr, tables)?;
+            let (compare, combine) = match negated {
+                false => (BinaryOperator::Eq, BinaryOperator::Or),
+                true => (BinaryOperator::NotEq, BinaryOperator::And),
+            };
+            list.iter()
+                .map(|item| {
+                    let item = find_field_in_expr(item, tables)?;
+                    Some(Column::bin_op(compare.clone(), expr.clone(), item))
+                })
+                .reduce(|left, right| Some(Column::bin_op(combine.clone(), left?, right?)))?
+        }
+        // Postgres runs these as the `~~`, `~~*` and `~` operators.
+        Expr::Like {
+            negated,
+            any: false,
+            expr,
+            pattern,
+            ..
+        } => pattern_match(
+            match negated {
+                true => BinaryOperator::PGNotLikeMatch,
+                false => BinaryOperator::PGLikeMatch,
+            },
+            expr,
+            pattern,
+            tables,
+        ),
+        Expr::ILike {
+            negated,
+            any: false,
+            expr,
+            pattern,
+            ..
+        } => pattern_match(
+            match negated {
+                true => BinaryOperator::PGNotILikeMatch,
+                false => BinaryOperator::PGILikeMatch,
+            },
+            expr,
+            pattern,
+            tables,
+        ),
+        Expr::SimilarTo {
+            negated,
+            expr,
+            pattern,
+            ..
+        } => pattern_match(
+            match negated {
+                true => BinaryOperator::PGRegexNotMatch,
+                false => BinaryOperator::PGRegexMatch,
+            },
+            expr,
+            pattern,
+            tables,
+        ),
+        Expr::Extract { expr, .. } => Some(Column::Function {
+            function: KnownFunction::Extract,
+            args: vec![find_field_in_expr(expr, tables)?],
+        }),
         Expr::IsNull(_)
         | Expr::IsNotNull(_)
         | Expr::IsTrue(_)
@@ -529,8 +934,13 @@ fn find_field_in_expr(expr: &Expr, tables: &[Arc<Table>]) -> Option<Column> {
     }
 }
 
-fn find_fields_in_items(items: &[SelectItem], tables: &[Arc<Table>]) -> HashMap<String, Column> {
-    let mut columns = HashMap::new();
+/// Source of each named item in order, `None` when it is not known. Items sharing a name are
+/// all kept.
+fn find_fields_in_items(
+    items: &[SelectItem],
+    tables: &[Arc<Table>],
+) -> Vec<(String, Option<Column>)> {
+    let mut columns = vec![];
     for item in items {
         match item {
             SelectItem::UnnamedExpr(expr) => {
@@ -542,16 +952,10 @@ fn find_fields_in_items(items: &[SelectItem], tables: &[Arc<Table>]) -> HashMap<
                 let Some(ident) = ident else {
                     continue;
                 };
-                let Some(column) = find_field_in_expr(expr, tables) else {
-                    continue;
-                };
-                columns.insert(ident.value.clone(), column);
+                columns.push((ident.value.clone(), find_field_in_expr(expr, tables)));
             }
             SelectItem::ExprWithAlias { expr, alias } => {
-                let Some(column) = find_field_in_expr(expr, tables) else {
-                    continue;
-                };
-                columns.insert(alias.value.clone(), column);
+                columns.push((alias.value.clone(), find_field_in_expr(expr, tables)));
             }
             _ => {}
         }
@@ -584,7 +988,29 @@ pub fn find_tables(statement: &Statement) -> Vec<Arc<Table>> {
     }
 }
 
+/// Source of each named output, items sharing a name are left out as they can not be told apart.
 pub fn find_fields(statement: &Statement) -> Result<HashMap<String, Column>, ParserError> {
+    let mut columns = HashMap::new();
+    let mut duplicates = vec![];
+    for (name, column) in find_named_fields(statement)? {
+        let Some(column) = column else {
+            continue;
+        };
+        if columns.insert(name.clone(), column).is_some() {
+            duplicates.push(name);
+        }
+    }
+    for name in duplicates {
+        columns.remove(&name);
+    }
+    Ok(columns)
+}
+
+/// Source of each named output in order, `None` when it is not known. Outputs sharing a name are
+/// all kept, so they can be matched by position among those of the same name.
+pub fn find_named_fields(
+    statement: &Statement,
+) -> Result<Vec<(String, Option<Column>)>, ParserError> {
     match statement {
         Statement::Query(query) => {
             if query.with.is_some() {
@@ -615,7 +1041,7 @@ pub fn find_fields(statement: &Statement) -> Result<HashMap<String, Column>, Par
             };
             Ok(match &insert.returning {
                 Some(returning) => find_fields_in_items(returning, &[table]),
-                None => HashMap::new(),
+                None => vec![],
             })
         }
         Statement::Update(Update {
@@ -624,7 +1050,7 @@ pub fn find_fields(statement: &Statement) -> Result<HashMap<String, Column>, Par
             let table = get_join(table);
             Ok(match &returning {
                 Some(returning) => find_fields_in_items(returning, &[table]),
-                None => HashMap::new(),
+                None => vec![],
             })
         }
         Statement::Delete(delete) => {
@@ -635,25 +1061,440 @@ pub fn find_fields(statement: &Statement) -> Result<HashMap<String, Column>, Par
             };
             Ok(match &delete.returning {
                 Some(returning) => find_fields_in_items(returning, &tables),
-                None => HashMap::new(),
+                None => vec![],
             })
         }
+        Statement::Call(_) => Ok(vec![]),
         _ => Err(ParserError::UnsupportedStatement {
             statement: statement.to_string(),
         }),
     }
 }
 
+/// Call to a function by name, e.g. `public.user_stats($1)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCall {
+    pub schema: Option<String>,
+    pub name: String,
+    /// Number of arguments passed.
+    pub args: usize,
+}
+
+/// Function called by each item of the select list of `statement`, in the order of the output
+/// columns. Empty when the select list contains `*`, as its items no longer match the output.
+pub fn projection_functions(statement: &Statement) -> Vec<Option<FunctionCall>> {
+    let Statement::Query(query) = statement else {
+        return vec![];
+    };
+    let SetExpr::Select(select) = &*query.body else {
+        return vec![];
+    };
+    let mut calls = vec![];
+    for item in &select.projection {
+        let (SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. }) = item else {
+            return vec![];
+        };
+        calls.push(match expr {
+            Expr::Function(function) => function_call(function),
+            _ => None,
+        });
+    }
+    calls
+}
+
+/// Argument of a `CALL` statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallArgument {
+    /// Name of the parameter the argument is given for, as in `p(name => 1)`.
+    pub name: Option<String>,
+    /// Number of the placeholder passed as the argument, as in `p($1)`.
+    pub placeholder: Option<usize>,
+}
+
+/// Procedure called by a `CALL` statement along with its arguments.
+pub fn called_procedure(statement: &Statement) -> Option<(FunctionCall, Vec<CallArgument>)> {
+    let Statement::Call(function) = statement else {
+        return None;
+    };
+    let call = function_call(function)?;
+    let args = match &function.args {
+        FunctionArguments::List(list) => list.args.as_slice(),
+        _ => &[],
+    };
+    let placeholder = |arg: &FunctionArgExpr| match arg {
+        FunctionArgExpr::Expr(Expr::Value(ValueWithSpan {
+            value: sqlparser::ast::Value::Placeholder(placeholder),
+            ..
+        })) => placeholder.strip_prefix('$')?.parse().ok(),
+        _ => None,
+    };
+    let args = args
+        .iter()
+        .map(|arg| match arg {
+            FunctionArg::Named { name, arg, .. }
+            | FunctionArg::ExprNamed {
+                name: Expr::Identifier(name),
+                arg,
+                ..
+            } => CallArgument {
+                name: Some(name.value.clone()),
+                placeholder: placeholder(arg),
+            },
+            FunctionArg::ExprNamed { arg, .. } | FunctionArg::Unnamed(arg) => CallArgument {
+                name: None,
+                placeholder: placeholder(arg),
+            },
+        })
+        .collect();
+    Some((call, args))
+}
+
+fn pattern_match(
+    op: BinaryOperator,
+    expr: &Expr,
+    pattern: &Expr,
+    tables: &[Arc<Table>],
+) -> Option<Column> {
+    Some(Column::bin_op(
+        op,
+        find_field_in_expr(expr, tables)?,
+        find_field_in_expr(pattern, tables)?,
+    ))
+}
+
+/// Source of a call to a [`KnownFunction`] with positional arguments.
+fn known_function(function: &Function, tables: &[Arc<Table>]) -> Option<Column> {
+    let (schema, name) = schema_and_name(&function.name)?;
+    if schema.is_some_and(|schema| schema != "pg_catalog") {
+        return None;
+    }
+    let known = KnownFunction::from_name(&name)?;
+    let FunctionArguments::List(list) = &function.args else {
+        return None;
+    };
+    let args = list
+        .args
+        .iter()
+        .map(|arg| match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Some(
+                find_field_in_expr(expr, tables).unwrap_or_else(|| Column::Unknown {
+                    sql: expr.to_string(),
+                }),
+            ),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    Some(Column::Function {
+        function: known,
+        args,
+    })
+}
+
+fn function_call(function: &Function) -> Option<FunctionCall> {
+    let args = match &function.args {
+        FunctionArguments::None => 0,
+        FunctionArguments::Subquery(_) => 1,
+        FunctionArguments::List(list) => list.args.len(),
+    };
+    let (schema, name) = schema_and_name(&function.name)?;
+    Some(FunctionCall { schema, name, args })
+}
+
+/// Postgres folds unquoted identifiers to lower case.
+fn fold_ident(ident: &Ident) -> String {
+    match ident.quote_style {
+        Some(_) => ident.value.clone(),
+        None => ident.value.to_lowercase(),
+    }
+}
+
+/// Schema, if given, and name of an object like `public.users`.
+fn schema_and_name(name: &ObjectName) -> Option<(Option<String>, String)> {
+    let parts = name
+        .0
+        .iter()
+        .map(|part| part.as_ident().map(fold_ident))
+        .collect::<Option<Vec<_>>>()?;
+    match parts.as_slice() {
+        [name] => Some((None, name.clone())),
+        [schema, name] => Some((Some(schema.clone()), name.clone())),
+        _ => None,
+    }
+}
+
+/// Table rows are loaded into by a `COPY ... FROM STDIN` statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyInto {
+    pub schema: Option<String>,
+    pub table: String,
+    /// Columns given a value by each row, every column of the table when empty.
+    pub columns: Vec<String>,
+}
+
+/// Table loaded by `statement` if it is a `COPY ... FROM STDIN`.
+pub fn copy_from_stdin(statement: &Statement) -> Option<CopyInto> {
+    let Statement::Copy {
+        source: CopySource::Table {
+            table_name,
+            columns,
+        },
+        to: false,
+        target: CopyTarget::Stdin,
+        ..
+    } = statement
+    else {
+        return None;
+    };
+    let (schema, table) = schema_and_name(table_name)?;
+    Some(CopyInto {
+        schema,
+        table,
+        columns: columns.iter().map(fold_ident).collect(),
+    })
+}
+
+fn join_conditions(tables: &[TableWithJoins]) -> Vec<&Expr> {
+    let mut conditions = vec![];
+    for table in tables {
+        for join in &table.joins {
+            let (JoinOperator::Inner(constraint)
+            | JoinOperator::Join(constraint)
+            | JoinOperator::Left(constraint)
+            | JoinOperator::LeftOuter(constraint)
+            | JoinOperator::Right(constraint)
+            | JoinOperator::RightOuter(constraint)
+            | JoinOperator::FullOuter(constraint)) = &join.join_operator
+            else {
+                continue;
+            };
+            if let JoinConstraint::On(expr) = constraint {
+                conditions.push(expr);
+            }
+        }
+    }
+    conditions
+}
+
+/// Split a condition on `AND` and `OR` so unsupported expressions only hide their own predicate.
+fn split_predicates<'a>(expr: &'a Expr, predicates: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And | BinaryOperator::Or,
+            right,
+        } => {
+            split_predicates(left, predicates);
+            split_predicates(right, predicates);
+        }
+        Expr::Nested(expr) => split_predicates(expr, predicates),
+        expr => predicates.push(expr),
+    }
+}
+
+/// Sources of the predicates in the `WHERE` and `JOIN ... ON` conditions of a statement.
+pub fn find_conditions(statement: &Statement) -> Vec<Column> {
+    let (tables, mut conditions, selection) = match statement {
+        Statement::Query(query) => match &*query.body {
+            SetExpr::Select(select) => (
+                identify_tables(&select.from),
+                join_conditions(&select.from),
+                &select.selection,
+            ),
+            _ => return vec![],
+        },
+        Statement::Update(Update {
+            table, selection, ..
+        }) => (
+            vec![get_join(table)],
+            join_conditions(std::slice::from_ref(table)),
+            selection,
+        ),
+        Statement::Delete(delete) => match &delete.from {
+            FromTable::WithoutKeyword(tables) | FromTable::WithFromKeyword(tables) => (
+                identify_tables(tables),
+                join_conditions(tables),
+                &delete.selection,
+            ),
+        },
+        _ => return vec![],
+    };
+    conditions.extend(selection);
+    let mut predicates = vec![];
+    for condition in conditions {
+        split_predicates(condition, &mut predicates);
+    }
+    predicates
+        .into_iter()
+        .filter_map(|predicate| find_field_in_expr(predicate, &tables))
+        .collect()
+}
+
+/// Whether `statement` inserts, updates or deletes rows, on its own or after a `WITH`.
+pub fn modifies_rows(statement: &Statement) -> bool {
+    match statement {
+        Statement::Insert(_)
+        | Statement::Update(_)
+        | Statement::Delete(_)
+        | Statement::Merge(_) => true,
+        Statement::Query(query) => matches!(
+            *query.body,
+            SetExpr::Insert(_) | SetExpr::Update(_) | SetExpr::Delete(_) | SetExpr::Merge(_)
+        ),
+        _ => false,
+    }
+}
+
+/// What a statement does to the database, ordered from the least to the most disruptive.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatementKind {
+    /// `SELECT`, `VALUES` or `EXPLAIN`.
+    #[default]
+    Read,
+    /// Session and maintenance statements, e.g. `SET`, `SHOW`, `LISTEN` or `VACUUM`.
+    Utility,
+    /// Anything else, e.g. `LOCK` or `CALL`.
+    Other,
+    /// Changes rows, e.g. `INSERT`, `TRUNCATE` or `COPY ... FROM`.
+    Write,
+    /// Changes the schema or privileges, e.g. `CREATE`, `ALTER`, `DROP` or `GRANT`.
+    Ddl,
+}
+
+pub fn statement_kind(statement: &Statement) -> StatementKind {
+    if modifies_rows(statement)
+        || matches!(
+            statement,
+            Statement::Truncate(_) | Statement::Copy { to: false, .. }
+        )
+    {
+        return StatementKind::Write;
+    }
+    if matches!(statement, Statement::Query(_) | Statement::Explain { .. }) {
+        return StatementKind::Read;
+    }
+    if matches!(
+        statement,
+        Statement::Set(_)
+            | Statement::Reset(_)
+            | Statement::ShowVariable { .. }
+            | Statement::ShowVariables { .. }
+            | Statement::Discard { .. }
+            | Statement::LISTEN { .. }
+            | Statement::UNLISTEN { .. }
+            | Statement::NOTIFY { .. }
+            | Statement::Analyze(_)
+            | Statement::Vacuum(_)
+            | Statement::Load { .. }
+    ) {
+        return StatementKind::Utility;
+    }
+    match statement {
+        Statement::CreateTable(_)
+        | Statement::CreateView(_)
+        | Statement::CreateIndex(_)
+        | Statement::CreateSchema { .. }
+        | Statement::CreateDatabase { .. }
+        | Statement::CreateSequence { .. }
+        | Statement::CreateType { .. }
+        | Statement::CreateDomain(_)
+        | Statement::CreateFunction(_)
+        | Statement::CreateProcedure { .. }
+        | Statement::CreateTrigger(_)
+        | Statement::CreateExtension(_)
+        | Statement::CreateCollation(_)
+        | Statement::CreateOperator(_)
+        | Statement::CreateOperatorFamily(_)
+        | Statement::CreateOperatorClass(_)
+        | Statement::CreatePolicy(_)
+        | Statement::CreateRole(_)
+        | Statement::CreateUser(_)
+        | Statement::CreateServer(_)
+        | Statement::AlterTable(_)
+        | Statement::AlterSchema(_)
+        | Statement::AlterIndex { .. }
+        | Statement::AlterView { .. }
+        | Statement::AlterType(_)
+        | Statement::AlterFunction(_)
+        | Statement::AlterCollation(_)
+        | Statement::AlterOperator(_)
+        | Statement::AlterOperatorFamily(_)
+        | Statement::AlterOperatorClass(_)
+        | Statement::AlterPolicy(_)
+        | Statement::AlterRole { .. }
+        | Statement::AlterUser(_)
+        | Statement::Drop { .. }
+        | Statement::DropFunction(_)
+        | Statement::DropProcedure { .. }
+        | Statement::DropTrigger(_)
+        | Statement::DropDomain(_)
+        | Statement::DropExtension(_)
+        | Statement::DropOperator(_)
+        | Statement::DropOperatorFamily(_)
+        | Statement::DropOperatorClass(_)
+        | Statement::DropPolicy(_)
+        | Statement::Comment { .. }
+        | Statement::Grant(_)
+        | Statement::Revoke(_) => StatementKind::Ddl,
+        _ => StatementKind::Other,
+    }
+}
+
 pub fn to_ast(query: &str) -> Result<Vec<Statement>, Box<dyn Error>> {
     let dialect = PostgreSqlDialect {};
     Ok(Parser::parse_sql(&dialect, query)?)
 }
 
+/// One of the statements of a query, along with the `$n` placeholders it uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitStatement {
+    pub sql: String,
+    pub placeholders: BTreeSet<usize>,
+}
+
+/// Split `query` on `;`, statements holding nothing but comments are left out.
+pub fn split_statements(query: &str) -> Result<Vec<SplitStatement>, Box<dyn Error>> {
+    let dialect = PostgreSqlDialect {};
+    let tokens = Tokenizer::new(&dialect, query)
+        .with_unescape(false)
+        .tokenize()?;
+    let mut statements = vec![];
+    for statement in tokens.split(|token| *token == Token::SemiColon) {
+        if statement
+            .iter()
+            .all(|token| matches!(token, Token::Whitespace(_)))
+        {
+            continue;
+        }
+        statements.push(SplitStatement {
+            sql: statement.iter().map(ToString::to_string).collect(),
+            placeholders: statement
+                .iter()
+                .filter_map(|token| match token {
+                    Token::Placeholder(placeholder) => placeholder.strip_prefix('$')?.parse().ok(),
+                    _ => None,
+                })
+                .collect(),
+        });
+    }
+    Ok(statements)
+}
+
 #[cfg(test)]
 mod tests {
-    use sqlparser::ast::Statement;
+    use sqlparser::ast::{BinaryOperator, Statement};
 
-    use crate::parser::{Column, find_fields, to_ast};
+    use crate::{
+        inference::SqlType,
+        parser::{
+            BinaryOpData, CallArgument, Column, CopyInto, FunctionCall, KnownFunction, Lineage,
+            StatementKind, ValueType, called_procedure, copy_from_stdin, find_conditions,
+            find_fields, find_named_fields, modifies_rows, projection_functions, split_statements,
+            statement_kind, to_ast,
+        },
+    };
 
     const TABLES: &[&str] = &["a", "b", "c", "d", "e", "f"];
     const COLUMNS: &[&str] = &["a", "b", "c"];
@@ -744,4 +1585,315 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn conditions_are_split_into_predicates() {
+        let ast =
+            to_ast("select a.a from a join b on b.b = a.a where a.c = $1 and (b.c = a.b)").unwrap();
+        let conditions = find_conditions(&ast[0])
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conditions,
+            vec![
+                "binop(op(=) -> bool, b.b, a.a)",
+                "binop(op(=) -> bool, b.c, a.b)"
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_names_have_no_source() {
+        let ast = to_ast("select a.a, b.a, b.b from a join b on b.c = a.c").unwrap();
+        let fields = find_fields(&ast[0]).unwrap();
+        assert!(!fields.contains_key("a"));
+        assert_eq!(fields["b"], Column::depends_on("b", "b"));
+    }
+
+    #[test]
+    fn statements_are_split_on_semicolons() {
+        let statements = split_statements(
+            "set local work_mem = '64MB';\nselect $2, ';' from a where b = $1;\n-- done\n",
+        )
+        .unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[1].sql, "\nselect $2, ';' from a where b = $1");
+        assert_eq!(
+            statements[1].placeholders.iter().collect::<Vec<_>>(),
+            [&1, &2]
+        );
+    }
+
+    #[test]
+    fn writes_modify_rows() {
+        let modifies = |query: &str| modifies_rows(&to_ast(query).unwrap()[0]);
+        assert!(modifies("update a set b = 1"));
+        assert!(modifies(
+            "with x as (select 1) delete from a where b in (select * from x)"
+        ));
+        assert!(!modifies("select b from a"));
+        assert!(!modifies(
+            "with x as (delete from a returning b) select b from x"
+        ));
+    }
+
+    #[test]
+    fn statements_are_classified() {
+        let kind = |query: &str| statement_kind(&to_ast(query).unwrap()[0]);
+        assert_eq!(kind("select b from a"), StatementKind::Read);
+        assert_eq!(kind("delete from a"), StatementKind::Write);
+        assert_eq!(kind("truncate a"), StatementKind::Write);
+        assert_eq!(kind("alter table a add column d int"), StatementKind::Ddl);
+        assert_eq!(kind("create index on a (b)"), StatementKind::Ddl);
+        assert_eq!(kind("drop view if exists v"), StatementKind::Ddl);
+        assert_eq!(kind("comment on table a is 'rows'"), StatementKind::Ddl);
+        assert_eq!(kind("grant select on a to reader"), StatementKind::Ddl);
+        assert_eq!(kind("set local work_mem = '64MB'"), StatementKind::Utility);
+        assert_eq!(kind("listen jobs"), StatementKind::Utility);
+        assert_eq!(kind("lock table a"), StatementKind::Other);
+    }
+
+    #[test]
+    fn projection_functions_are_found() {
+        let calls = |query: &str| projection_functions(&to_ast(query).unwrap()[0]);
+        assert_eq!(
+            calls("select a, Stats.\"Totals\"(1, $1) as t from a"),
+            [
+                None,
+                Some(FunctionCall {
+                    schema: Some("stats".to_string()),
+                    name: "Totals".to_string(),
+                    args: 2,
+                })
+            ]
+        );
+        assert_eq!(calls("select *, totals() from a"), []);
+    }
+
+    #[test]
+    fn procedure_arguments_are_found() {
+        let (call, args) =
+            called_procedure(&to_ast("call transfer($2, 10, amount => $1)").unwrap()[0]).unwrap();
+        assert_eq!(call.name, "transfer");
+        assert_eq!(
+            args,
+            [
+                CallArgument {
+                    name: None,
+                    placeholder: Some(2),
+                },
+                CallArgument {
+                    name: None,
+                    placeholder: None,
+                },
+                CallArgument {
+                    name: Some("amount".to_string()),
+                    placeholder: Some(1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn columns_sharing_a_name_are_kept_in_order() {
+        let ast = to_ast("select a.id, b.id, 1 as id from a join b on a.x = b.x").unwrap();
+        let depends_on = |table: &str| Column::DependsOn {
+            table: table.into(),
+            column: "id".into(),
+        };
+        let fields = find_named_fields(&ast[0]).unwrap();
+        assert_eq!(
+            fields
+                .iter()
+                .map(|(name, column)| (name.as_str(), column.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("id", Some(depends_on("a"))),
+                ("id", Some(depends_on("b"))),
+                ("id", Some(Column::Value(ValueType::Int))),
+            ]
+        );
+        assert!(find_fields(&ast[0]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn copy_from_stdin_is_found() {
+        let copy = |query: &str| copy_from_stdin(&to_ast(query).unwrap()[0]);
+        assert_eq!(
+            copy("copy Stats.\"Events\" (Id, kind) from stdin"),
+            Some(CopyInto {
+                schema: Some("stats".to_string()),
+                table: "Events".to_string(),
+                columns: vec!["id".to_string(), "kind".to_string()],
+            })
+        );
+        assert_eq!(copy("copy events to stdout"), None);
+    }
+
+    #[test]
+    fn lineage_lists_source_columns() {
+        let ast =
+            to_ast("select a.a + b.b as x, upper(a.c) as y from a left join b on true").unwrap();
+        let fields = find_fields(&ast[0]).unwrap();
+        let x = Lineage::from(&fields["x"]);
+        assert_eq!(
+            x.columns().into_iter().collect::<Vec<_>>(),
+            [("a", "a"), ("b", "b")]
+        );
+        assert!(!x.is_partial());
+        assert!(Lineage::from(&fields["y"]).is_partial());
+    }
+
+    #[test]
+    fn json_operators_are_typed() {
+        let op = |op: BinaryOperator| BinaryOpData::from(op);
+        assert_eq!(
+            op(BinaryOperator::LongArrow).try_constant(),
+            Some(SqlType::Text)
+        );
+        assert_eq!(
+            op(BinaryOperator::HashArrow).try_from_operands(SqlType::Jsonb, SqlType::Text),
+            Some(SqlType::Jsonb)
+        );
+        assert_eq!(
+            op(BinaryOperator::Arrow).try_from_operands(SqlType::Json, SqlType::Int4),
+            Some(SqlType::Json)
+        );
+        assert_eq!(
+            op(BinaryOperator::Arrow).try_from_operands(SqlType::Unknown, SqlType::Text),
+            None
+        );
+        assert!(op(BinaryOperator::HashLongArrow).maybe_null());
+    }
+
+    #[test]
+    fn subscripts_are_typed_from_the_array() {
+        let ast = to_ast("select a[1] as x, a[1:2] as y, (b.c)[1][2:3] as z from b").unwrap();
+        let fields = find_fields(&ast[0]).unwrap();
+        assert_eq!(fields["x"], Column::depends_on("b", "a").subscript(false));
+        assert_eq!(fields["y"], Column::depends_on("b", "a").subscript(true));
+        assert_eq!(fields["z"], Column::depends_on("b", "c").subscript(true));
+        let tags = SqlType::Array(Box::new(SqlType::Text));
+        assert_eq!(tags.subscript(false), Some(SqlType::Text));
+        assert_eq!(tags.subscript(true), Some(tags.clone()));
+        assert_eq!(SqlType::Text.subscript(false), None);
+    }
+
+    #[test]
+    fn temporal_arithmetic_is_typed() {
+        let op = |op: BinaryOperator, left: SqlType, right: SqlType| {
+            BinaryOpData::from(op).try_from_operands(left, right)
+        };
+        let timestamptz = SqlType::Timestamp { tz: true };
+        assert_eq!(
+            op(BinaryOperator::Plus, timestamptz.clone(), SqlType::Interval),
+            Some(timestamptz.clone())
+        );
+        assert_eq!(
+            op(BinaryOperator::Minus, timestamptz.clone(), timestamptz),
+            Some(SqlType::Interval)
+        );
+        assert_eq!(
+            op(BinaryOperator::Minus, SqlType::Date, SqlType::Date),
+            Some(SqlType::Int4)
+        );
+        assert_eq!(
+            op(BinaryOperator::Plus, SqlType::Date, SqlType::Interval),
+            Some(SqlType::Timestamp { tz: false })
+        );
+        assert_eq!(
+            op(BinaryOperator::Multiply, SqlType::Float8, SqlType::Interval),
+            Some(SqlType::Interval)
+        );
+        assert_eq!(op(BinaryOperator::Plus, SqlType::Date, SqlType::Date), None);
+        assert_eq!(
+            op(BinaryOperator::Plus, SqlType::Int4, SqlType::Int8),
+            Some(SqlType::Int8)
+        );
+    }
+
+    #[test]
+    fn extract_follows_its_source() {
+        let ast = to_ast(
+            "select extract(epoch from a) as x, date_part('epoch', a) as y, \
+             extract(year from a)::int as z from b",
+        )
+        .unwrap();
+        let fields = find_fields(&ast[0]).unwrap();
+        let extract = Column::Function {
+            function: KnownFunction::Extract,
+            args: vec![Column::depends_on("b", "a")],
+        };
+        assert_eq!(fields["x"], extract);
+        assert_eq!(
+            fields["y"],
+            Column::Function {
+                function: KnownFunction::DatePart,
+                args: vec![
+                    Column::value(ValueType::String),
+                    Column::depends_on("b", "a")
+                ],
+            }
+        );
+        assert_eq!(
+            fields["z"],
+            extract.cast(sqlparser::ast::DataType::Int(None))
+        );
+        assert_eq!(KnownFunction::DatePart.sql_type(), SqlType::Float8);
+    }
+
+    #[test]
+    fn pattern_matches_are_boolean() {
+        let ast = to_ast(
+            "select a like 'x%' as x, b not ilike c as y, a similar to '(x|y)%' as z from b",
+        )
+        .unwrap();
+        let fields = find_fields(&ast[0]).unwrap();
+        let string = || Column::value(ValueType::String);
+        assert_eq!(
+            fields["x"],
+            Column::bin_op(
+                BinaryOperator::PGLikeMatch,
+                Column::depends_on("b", "a"),
+                string()
+            )
+        );
+        assert_eq!(
+            fields["y"],
+            Column::bin_op(
+                BinaryOperator::PGNotILikeMatch,
+                Column::depends_on("b", "b"),
+                Column::depends_on("b", "c")
+            )
+        );
+        let Column::BinaryOp { op, .. } = &fields["z"] else {
+            panic!("{} is not an operator", fields["z"]);
+        };
+        assert_eq!(op.try_constant(), Some(SqlType::Bool));
+    }
+
+    #[test]
+    fn in_lists_and_between_are_comparisons() {
+        let ast = to_ast("select a in (1, 2) as x, a not between b and 3 as y from b").unwrap();
+        let fields = find_fields(&ast[0]).unwrap();
+        let a = || Column::depends_on("b", "a");
+        let int = || Column::value(ValueType::Int);
+        assert_eq!(
+            fields["x"],
+            Column::bin_op(
+                BinaryOperator::Or,
+                Column::bin_op(BinaryOperator::Eq, a(), int()),
+                Column::bin_op(BinaryOperator::Eq, a(), int())
+            )
+        );
+        assert_eq!(
+            fields["y"],
+            Column::bin_op(
+                BinaryOperator::Or,
+                Column::bin_op(BinaryOperator::Lt, a(), Column::depends_on("b", "b")),
+                Column::bin_op(BinaryOperator::Gt, a(), int())
+            )
+        );
+    }
 }